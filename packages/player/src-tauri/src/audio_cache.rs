@@ -0,0 +1,638 @@
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult};
+
+// Disk cache for audio proxied through the nuclear-stream protocol.
+// Entries are keyed on a stable track identity supplied by the frontend rather than the upstream URL,
+// since signed streaming URLs change every time a track is resolved.
+// Each entry is a sparse data file plus a JSON sidecar listing which byte ranges are present.
+
+const CACHE_DIR_NAME: &str = "audio-cache";
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024; // 1GB
+                                                   // Kept next to the cache directory rather than inside it, where it would be mistaken for a sidecar
+const SETTINGS_FILE_NAME: &str = "audio-cache.json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct CacheEntry {
+    total: Option<u64>,
+    content_type: Option<String>,
+    // Sorted, non-overlapping, half-open [start, end) intervals.
+    ranges: Vec<(u64, u64)>,
+    last_access: u64,
+    // Whether this session has written last_access to the sidecar yet. Later hits only
+    // bump it in memory so playback doesn't rewrite the sidecar for every chunk.
+    #[serde(skip)]
+    access_persisted: bool,
+}
+
+impl CacheEntry {
+    fn size(&self) -> u64 {
+        self.ranges.iter().map(|(start, end)| end - start).sum()
+    }
+
+    fn insert_range(&mut self, start: u64, end: u64) {
+        let mut merged_start = start;
+        let mut merged_end = end;
+        let mut ranges = Vec::with_capacity(self.ranges.len() + 1);
+
+        for &(range_start, range_end) in &self.ranges {
+            if range_end < merged_start || range_start > merged_end {
+                ranges.push((range_start, range_end));
+            } else {
+                merged_start = merged_start.min(range_start);
+                merged_end = merged_end.max(range_end);
+            }
+        }
+
+        ranges.push((merged_start, merged_end));
+        ranges.sort_unstable();
+        self.ranges = ranges;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CachedRange {
+    pub body: Vec<u8>,
+    pub total: Option<u64>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioCacheStats {
+    pub entries: usize,
+    pub size_bytes: u64,
+    pub max_bytes: u64,
+}
+
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    max_bytes: u64,
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn size(&self) -> u64 {
+        self.entries.values().map(CacheEntry::size).sum()
+    }
+}
+
+#[derive(Clone)]
+pub struct AudioCache {
+    root: PathBuf,
+    index: Arc<Mutex<CacheIndex>>,
+}
+
+fn differs<T: PartialEq + ?Sized>(cached: Option<&T>, incoming: Option<&T>) -> bool {
+    matches!((cached, incoming), (Some(cached), Some(incoming)) if cached != incoming)
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+impl AudioCache {
+    pub fn open(root: PathBuf, max_bytes: u64) -> std::io::Result<Self> {
+        fs::create_dir_all(&root)?;
+
+        let mut entries = HashMap::new();
+        for dir_entry in fs::read_dir(&root)? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(hash) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match fs::read(&path).map(|bytes| serde_json::from_slice::<CacheEntry>(&bytes)) {
+                Ok(Ok(entry)) => {
+                    entries.insert(hash.to_string(), entry);
+                }
+                _ => {
+                    warn!("[AudioCache] Dropping unreadable entry {:?}", path);
+                    let _ = fs::remove_file(&path);
+                    let _ = fs::remove_file(path.with_extension("data"));
+                }
+            }
+        }
+
+        let clock = entries.values().map(|e| e.last_access).max().unwrap_or(0);
+        debug!(
+            "[AudioCache] Loaded {} entries from {:?}",
+            entries.len(),
+            root
+        );
+
+        Ok(Self {
+            root,
+            index: Arc::new(Mutex::new(CacheIndex {
+                entries,
+                max_bytes,
+                clock,
+            })),
+        })
+    }
+
+    fn data_path(&self, hash: &str) -> PathBuf {
+        self.root.join(format!("{}.data", hash))
+    }
+
+    fn meta_path(&self, hash: &str) -> PathBuf {
+        self.root.join(format!("{}.json", hash))
+    }
+
+    fn write_meta(&self, hash: &str, entry: &CacheEntry) -> std::io::Result<()> {
+        fs::write(self.meta_path(hash), serde_json::to_vec(entry)?)
+    }
+
+    fn remove_files(&self, hash: &str) {
+        let _ = fs::remove_file(self.meta_path(hash));
+        let _ = fs::remove_file(self.data_path(hash));
+    }
+
+    /// Returns up to `max_len` cached bytes starting exactly at `start`, if that offset is cached.
+    pub fn read(&self, key: &str, start: u64, max_len: u64) -> Option<CachedRange> {
+        let hash = hash_key(key);
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = index.tick();
+        let entry = index.entries.get_mut(&hash)?;
+
+        let &(_, range_end) = entry
+            .ranges
+            .iter()
+            .find(|(range_start, range_end)| *range_start <= start && start < *range_end)?;
+        let len = (range_end - start).min(max_len);
+
+        let read = || -> std::io::Result<Vec<u8>> {
+            let mut file = File::open(self.data_path(&hash))?;
+            file.seek(SeekFrom::Start(start))?;
+            let mut body = vec![0; len as usize];
+            file.read_exact(&mut body)?;
+            Ok(body)
+        };
+
+        match read() {
+            Ok(body) => {
+                entry.last_access = clock;
+                if !entry.access_persisted {
+                    entry.access_persisted = true;
+                    let _ = self.write_meta(&hash, entry);
+                }
+                let entry = entry.clone();
+                Some(CachedRange {
+                    body,
+                    total: entry.total,
+                    content_type: entry.content_type,
+                })
+            }
+            Err(e) => {
+                error!("[AudioCache] Failed to read cached data: {}", e);
+                index.entries.remove(&hash);
+                self.remove_files(&hash);
+                None
+            }
+        }
+    }
+
+    /// Start of the first cached range after `start`, so an upstream fetch can stop short of it.
+    pub fn next_cached_offset(&self, key: &str, start: u64) -> Option<u64> {
+        let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index
+            .entries
+            .get(&hash_key(key))?
+            .ranges
+            .iter()
+            .map(|(range_start, _)| *range_start)
+            .find(|range_start| *range_start > start)
+    }
+
+    pub fn write(
+        &self,
+        key: &str,
+        start: u64,
+        data: &[u8],
+        total: Option<u64>,
+        content_type: Option<&str>,
+    ) -> std::io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let hash = hash_key(key);
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+
+        // A different length or content type means the key now points at another encoding
+        // of the track, so the bytes already on disk can't be mixed with the new ones.
+        let stale = index.entries.get(&hash).is_some_and(|entry| {
+            differs(entry.total.as_ref(), total.as_ref())
+                || differs(entry.content_type.as_deref(), content_type)
+        });
+        if stale {
+            debug!("[AudioCache] Stream changed for {}, dropping entry", hash);
+            index.entries.remove(&hash);
+            self.remove_files(&hash);
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.data_path(&hash))?;
+        file.seek(SeekFrom::Start(start))?;
+        file.write_all(data)?;
+
+        let clock = index.tick();
+        let entry = index.entries.entry(hash.clone()).or_default();
+        entry.insert_range(start, start + data.len() as u64);
+        entry.total = total.or(entry.total);
+        if let Some(content_type) = content_type {
+            entry.content_type = Some(content_type.to_string());
+        }
+        entry.last_access = clock;
+        entry.access_persisted = true;
+
+        let entry = entry.clone();
+        self.write_meta(&hash, &entry)?;
+        self.evict(&mut index, Some(&hash));
+        Ok(())
+    }
+
+    /// Drops least recently used entries until the cache fits its size cap.
+    fn evict(&self, index: &mut CacheIndex, keep: Option<&str>) {
+        let mut size = index.size();
+        if size <= index.max_bytes {
+            return;
+        }
+
+        let mut candidates: Vec<(String, u64, u64)> = index
+            .entries
+            .iter()
+            .filter(|(hash, _)| Some(hash.as_str()) != keep)
+            .map(|(hash, entry)| (hash.clone(), entry.last_access, entry.size()))
+            .collect();
+        candidates.sort_by_key(|(_, last_access, _)| *last_access);
+
+        for (hash, _, entry_size) in candidates {
+            if size <= index.max_bytes {
+                break;
+            }
+            debug!("[AudioCache] Evicting {} ({} bytes)", hash, entry_size);
+            index.entries.remove(&hash);
+            self.remove_files(&hash);
+            size -= entry_size;
+        }
+    }
+
+    pub fn set_max_bytes(&self, max_bytes: u64) {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        index.max_bytes = max_bytes;
+        self.evict(&mut index, None);
+    }
+
+    pub fn clear(&self) {
+        let mut index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        for hash in index.entries.keys() {
+            self.remove_files(hash);
+        }
+        index.entries.clear();
+    }
+
+    pub fn stats(&self) -> AudioCacheStats {
+        let index = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        AudioCacheStats {
+            entries: index.entries.len(),
+            size_bytes: index.size(),
+            max_bytes: index.max_bytes,
+        }
+    }
+}
+
+// The cap the user picked, persisted so it survives restarts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+struct AudioCacheSettings {
+    max_bytes: u64,
+}
+
+impl Default for AudioCacheSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+impl AudioCacheSettings {
+    fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("[AudioCache] Ignoring invalid settings {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+fn app_data_path(app_handle: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map(|dir| dir.join(name))
+        .map_err(|e| e.to_string())
+}
+
+pub fn init_audio_cache(app_handle: AppHandle) {
+    let settings = app_data_path(&app_handle, SETTINGS_FILE_NAME)
+        .map(|path| AudioCacheSettings::load(&path))
+        .unwrap_or_default();
+    let cache = app_data_path(&app_handle, CACHE_DIR_NAME)
+        .and_then(|root| AudioCache::open(root, settings.max_bytes).map_err(|e| e.to_string()));
+
+    match cache {
+        Ok(cache) => {
+            app_handle.manage(cache);
+        }
+        Err(e) => error!("[AudioCache] Disabled, failed to open cache: {}", e),
+    }
+}
+
+fn cache_state(app_handle: &AppHandle) -> AppResult<AudioCache> {
+    app_handle
+        .try_state::<AudioCache>()
+        .map(|cache| cache.inner().clone())
+        .ok_or_else(|| AppError::unknown("Audio cache is not available"))
+}
+
+#[tauri::command]
+pub fn audio_cache_stats(app_handle: AppHandle) -> AppResult<AudioCacheStats> {
+    Ok(cache_state(&app_handle)?.stats())
+}
+
+#[tauri::command]
+pub fn audio_cache_set_max_bytes(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    max_bytes: u64,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
+    let cache = cache_state(&app_handle)?;
+    let path = app_data_path(&app_handle, SETTINGS_FILE_NAME).map_err(AppError::unknown)?;
+    AudioCacheSettings { max_bytes }.save(&path)?;
+    cache.set_max_bytes(max_bytes);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_cache_clear(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
+    cache_state(&app_handle)?.clear();
+    info!("[AudioCache] Cleared");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const KEY: &str = "youtube:dQw4w9WgXcQ";
+
+    fn open_cache(root: &std::path::Path, max_bytes: u64) -> AudioCache {
+        AudioCache::open(root.to_path_buf(), max_bytes).unwrap()
+    }
+
+    mod read {
+        use super::*;
+
+        #[test]
+        fn returns_written_bytes() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache
+                .write(KEY, 0, b"hello world", Some(100), Some("audio/mp4"))
+                .unwrap();
+
+            assert_eq!(
+                cache.read(KEY, 6, 1024),
+                Some(CachedRange {
+                    body: b"world".to_vec(),
+                    total: Some(100),
+                    content_type: Some("audio/mp4".to_string()),
+                })
+            );
+        }
+
+        #[test]
+        fn caps_read_at_max_len() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache.write(KEY, 0, b"0123456789", None, None).unwrap();
+
+            assert_eq!(cache.read(KEY, 2, 3).unwrap().body, b"234");
+        }
+
+        #[test]
+        fn misses_uncached_offsets() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache.write(KEY, 10, b"0123456789", None, None).unwrap();
+
+            assert_eq!(cache.read(KEY, 5, 10), None);
+            assert_eq!(cache.read(KEY, 20, 10), None);
+            assert_eq!(cache.read("other", 10, 10), None);
+        }
+
+        #[test]
+        fn merges_adjacent_and_overlapping_ranges() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache.write(KEY, 0, b"aaaa", None, None).unwrap();
+            cache.write(KEY, 8, b"cccc", None, None).unwrap();
+            cache.write(KEY, 4, b"bbbbb", None, None).unwrap();
+
+            assert_eq!(cache.read(KEY, 0, 100).unwrap().body, b"aaaabbbbbccc");
+            assert_eq!(cache.stats().size_bytes, 12);
+        }
+
+        #[test]
+        fn survives_reopening() {
+            let temp = tempdir().unwrap();
+            open_cache(temp.path(), DEFAULT_MAX_BYTES)
+                .write(KEY, 0, b"persisted", Some(9), None)
+                .unwrap();
+
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            assert_eq!(cache.read(KEY, 0, 100).unwrap().body, b"persisted");
+        }
+
+        #[test]
+        fn does_not_rewrite_the_sidecar_on_every_hit() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+            cache.write(KEY, 0, b"data", None, None).unwrap();
+            let meta_path = cache.meta_path(&hash_key(KEY));
+
+            cache.read(KEY, 0, 4).unwrap();
+            fs::write(&meta_path, b"untouched").unwrap();
+            cache.read(KEY, 0, 4).unwrap();
+
+            assert_eq!(fs::read(&meta_path).unwrap(), b"untouched");
+        }
+    }
+
+    mod write {
+        use super::*;
+
+        #[test]
+        fn drops_cached_bytes_when_the_stream_changes() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache
+                .write(KEY, 0, b"mp4 bytes", Some(100), Some("audio/mp4"))
+                .unwrap();
+            cache
+                .write(KEY, 50, b"webm", Some(80), Some("audio/webm"))
+                .unwrap();
+
+            assert_eq!(cache.read(KEY, 0, 100), None);
+            assert_eq!(
+                cache.read(KEY, 50, 100),
+                Some(CachedRange {
+                    body: b"webm".to_vec(),
+                    total: Some(80),
+                    content_type: Some("audio/webm".to_string()),
+                })
+            );
+            assert_eq!(cache.stats().size_bytes, 4);
+        }
+    }
+
+    mod next_cached_offset {
+        use super::*;
+
+        #[test]
+        fn finds_start_of_next_range() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache.write(KEY, 100, b"later", None, None).unwrap();
+
+            assert_eq!(cache.next_cached_offset(KEY, 0), Some(100));
+            assert_eq!(cache.next_cached_offset(KEY, 100), None);
+            assert_eq!(cache.next_cached_offset("other", 0), None);
+        }
+    }
+
+    mod eviction {
+        use super::*;
+
+        #[test]
+        fn evicts_least_recently_used_entries_over_cap() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), 20);
+
+            cache.write("first", 0, &[1; 10], None, None).unwrap();
+            cache.write("second", 0, &[2; 10], None, None).unwrap();
+            cache.read("first", 0, 10).unwrap();
+            cache.write("third", 0, &[3; 10], None, None).unwrap();
+
+            assert!(cache.read("first", 0, 10).is_some());
+            assert!(cache.read("second", 0, 10).is_none());
+            assert!(cache.read("third", 0, 10).is_some());
+            assert_eq!(cache.stats().size_bytes, 20);
+        }
+
+        #[test]
+        fn lowering_the_cap_evicts_immediately() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache.write("first", 0, &[1; 10], None, None).unwrap();
+            cache.write("second", 0, &[2; 10], None, None).unwrap();
+            cache.set_max_bytes(10);
+
+            assert_eq!(
+                cache.stats(),
+                AudioCacheStats {
+                    entries: 1,
+                    size_bytes: 10,
+                    max_bytes: 10,
+                }
+            );
+            assert!(cache.read("second", 0, 10).is_some());
+        }
+
+        #[test]
+        fn clear_removes_all_files() {
+            let temp = tempdir().unwrap();
+            let cache = open_cache(temp.path(), DEFAULT_MAX_BYTES);
+
+            cache.write(KEY, 0, b"data", None, None).unwrap();
+            cache.clear();
+
+            assert_eq!(cache.stats().entries, 0);
+            assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 0);
+        }
+    }
+
+    mod settings {
+        use super::*;
+
+        #[test]
+        fn remembers_the_cap() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join(SETTINGS_FILE_NAME);
+
+            AudioCacheSettings { max_bytes: 4096 }.save(&path).unwrap();
+
+            assert_eq!(AudioCacheSettings::load(&path).max_bytes, 4096);
+        }
+
+        #[test]
+        fn defaults_when_missing_or_invalid() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join(SETTINGS_FILE_NAME);
+            assert_eq!(AudioCacheSettings::load(&path).max_bytes, DEFAULT_MAX_BYTES);
+
+            fs::write(&path, "not json").unwrap();
+            assert_eq!(AudioCacheSettings::load(&path).max_bytes, DEFAULT_MAX_BYTES);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::command;
+
+use crate::error::{AppError, AppResult, ErrorKind};
+
+// Tells the app's own code apart from plugin code on the IPC boundary.
+// Plugins are evaluated in the same webview as the app, so the window a command arrives from says
+// nothing about who sent it. Instead the app claims a token each time the page loads, before any
+// plugin code runs, and has a token minted for each plugin it loads. Plugins only ever get their
+// own token. Privileged commands require the app token, and plugin-facing commands identify the
+// plugin from its token rather than from an id argument the webview controls.
+// Since plugins share the app's JavaScript realm, a hostile plugin can still intercept the IPC
+// transport and read the app token off a later call. The tokens keep plugins from reaching
+// privileged commands by accident or through the API they're given, they are not a sandbox.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Caller {
+    App,
+    Plugin(String),
+}
+
+impl Caller {
+    pub fn plugin_id(&self) -> Option<&str> {
+        match self {
+            Caller::App => None,
+            Caller::Plugin(plugin_id) => Some(plugin_id),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tokens {
+    app: Option<String>,
+    // token -> plugin id
+    plugins: HashMap<String, String>,
+}
+
+#[derive(Default)]
+pub struct Callers {
+    tokens: Mutex<Tokens>,
+}
+
+fn denied(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorKind::PermissionDenied, message)
+}
+
+fn new_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+impl Callers {
+    fn tokens(&self) -> std::sync::MutexGuard<'_, Tokens> {
+        self.tokens.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Hands out the app token. Only the first call after a page load succeeds, so code that runs
+    /// later, like plugins, can't obtain it.
+    pub fn claim_app_token(&self) -> AppResult<String> {
+        let mut tokens = self.tokens();
+        if tokens.app.is_some() {
+            return Err(denied("The app token has already been claimed"));
+        }
+        let token = new_token();
+        tokens.app = Some(token.clone());
+        Ok(token)
+    }
+
+    /// Forgets every token, for when the webview loads a page. The new page starts without the
+    /// tokens of the old one, claims the app token again and has plugin tokens issued anew.
+    pub fn reset(&self) {
+        *self.tokens() = Tokens::default();
+        debug!("Caller tokens reset");
+    }
+
+    pub fn resolve(&self, token: Option<&str>) -> AppResult<Caller> {
+        let token = token.ok_or_else(|| denied("Missing caller token"))?;
+        let tokens = self.tokens();
+        if tokens.app.as_deref() == Some(token) {
+            return Ok(Caller::App);
+        }
+        tokens
+            .plugins
+            .get(token)
+            .map(|plugin_id| Caller::Plugin(plugin_id.clone()))
+            .ok_or_else(|| denied("Unknown caller token"))
+    }
+
+    /// Fails unless `token` is the app token.
+    pub fn require_app(&self, token: Option<&str>) -> AppResult<()> {
+        match self.resolve(token)? {
+            Caller::App => Ok(()),
+            Caller::Plugin(plugin_id) => Err(denied(format!(
+                "Plugin {} is not allowed to call this command",
+                plugin_id
+            ))),
+        }
+    }
+
+    /// Mints a token for `plugin_id`, replacing any it had before.
+    pub fn issue_plugin_token(
+        &self,
+        app_token: Option<&str>,
+        plugin_id: &str,
+    ) -> AppResult<String> {
+        self.require_app(app_token)?;
+        let mut tokens = self.tokens();
+        tokens.plugins.retain(|_, id| id != plugin_id);
+        let token = new_token();
+        tokens.plugins.insert(token.clone(), plugin_id.to_string());
+        debug!("Issued caller token for plugin {}", plugin_id);
+        Ok(token)
+    }
+
+    pub fn revoke_plugin_token(&self, app_token: Option<&str>, plugin_id: &str) -> AppResult<()> {
+        self.require_app(app_token)?;
+        self.tokens().plugins.retain(|_, id| id != plugin_id);
+        debug!("Revoked caller token for plugin {}", plugin_id);
+        Ok(())
+    }
+}
+
+#[command]
+pub fn caller_claim_app_token(callers: tauri::State<'_, Callers>) -> AppResult<String> {
+    let token = callers.claim_app_token()?;
+    info!("App caller token claimed");
+    Ok(token)
+}
+
+#[command]
+pub fn caller_issue_plugin_token(
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: String,
+) -> AppResult<String> {
+    callers.issue_plugin_token(token.as_deref(), &plugin_id)
+}
+
+#[command]
+pub fn caller_revoke_plugin_token(
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: String,
+) -> AppResult<()> {
+    callers.revoke_plugin_token(token.as_deref(), &plugin_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod claim_app_token {
+        use super::*;
+
+        #[test]
+        fn can_only_be_claimed_once() {
+            let callers = Callers::default();
+
+            let token = callers.claim_app_token().unwrap();
+
+            assert_eq!(
+                callers.claim_app_token().unwrap_err().kind,
+                ErrorKind::PermissionDenied
+            );
+            assert_eq!(callers.resolve(Some(&token)).unwrap(), Caller::App);
+        }
+
+        #[test]
+        fn can_be_claimed_again_after_a_reset() {
+            let callers = Callers::default();
+            let old_app = callers.claim_app_token().unwrap();
+            let old_plugin = callers
+                .issue_plugin_token(Some(&old_app), "lastfm")
+                .unwrap();
+
+            callers.reset();
+            let app = callers.claim_app_token().unwrap();
+
+            assert_eq!(callers.resolve(Some(&app)).unwrap(), Caller::App);
+            assert!(callers.resolve(Some(&old_app)).is_err());
+            assert!(callers.resolve(Some(&old_plugin)).is_err());
+        }
+    }
+
+    mod resolve {
+        use super::*;
+
+        #[test]
+        fn identifies_plugins_by_their_token() {
+            let callers = Callers::default();
+            let app = callers.claim_app_token().unwrap();
+            let plugin = callers.issue_plugin_token(Some(&app), "lastfm").unwrap();
+
+            assert_eq!(
+                callers.resolve(Some(&plugin)).unwrap(),
+                Caller::Plugin("lastfm".to_string())
+            );
+        }
+
+        #[test]
+        fn rejects_missing_and_unknown_tokens() {
+            let callers = Callers::default();
+            callers.claim_app_token().unwrap();
+
+            assert_eq!(
+                callers.resolve(None).unwrap_err().kind,
+                ErrorKind::PermissionDenied
+            );
+            assert_eq!(
+                callers.resolve(Some("forged")).unwrap_err().kind,
+                ErrorKind::PermissionDenied
+            );
+        }
+
+        #[test]
+        fn forgets_revoked_and_replaced_tokens() {
+            let callers = Callers::default();
+            let app = callers.claim_app_token().unwrap();
+            let first = callers.issue_plugin_token(Some(&app), "lastfm").unwrap();
+            let second = callers.issue_plugin_token(Some(&app), "lastfm").unwrap();
+
+            assert!(callers.resolve(Some(&first)).is_err());
+            assert!(callers.resolve(Some(&second)).is_ok());
+
+            callers.revoke_plugin_token(Some(&app), "lastfm").unwrap();
+
+            assert!(callers.resolve(Some(&second)).is_err());
+        }
+    }
+
+    mod require_app {
+        use super::*;
+
+        #[test]
+        fn rejects_plugin_tokens() {
+            let callers = Callers::default();
+            let app = callers.claim_app_token().unwrap();
+            let plugin = callers.issue_plugin_token(Some(&app), "lastfm").unwrap();
+
+            assert!(callers.require_app(Some(&app)).is_ok());
+            assert!(callers.require_app(Some(&plugin)).is_err());
+            assert!(callers.issue_plugin_token(Some(&plugin), "other").is_err());
+        }
+    }
+}
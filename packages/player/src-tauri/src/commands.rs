@@ -1,16 +1,27 @@
+use serde::Serialize;
 use std::fs::{self, File};
-use std::io::BufReader;
+use std::io::{BufReader, Read, Seek};
 use std::path::{Path, PathBuf};
 use tauri::command;
 use zip::ZipArchive;
 
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult, ErrorKind};
+
 #[command]
 pub fn is_flatpak() -> bool {
     std::env::var("FLATPAK_ID").is_ok()
 }
 
+/// Reserved for the app, which copies dev plugins into the managed plugins dir with it.
 #[command]
-pub fn copy_dir_recursive(from: PathBuf, to: PathBuf) -> Result<(), String> {
+pub fn copy_dir_recursive(
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    from: PathBuf,
+    to: PathBuf,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
     fn inner(from: &Path, to: &Path) -> Result<(), std::io::Error> {
         fs::create_dir_all(to)?;
         for entry in fs::read_dir(from)? {
@@ -49,90 +60,240 @@ pub fn copy_dir_recursive(from: PathBuf, to: PathBuf) -> Result<(), String> {
     let to_p = Path::new(&to);
     inner(from_p, to_p).map_err(|e| {
         log::error!("copy_dir_recursive failed: {}", e);
-        e.to_string()
+        AppError::from(e)
     })
 }
 
-#[command]
-pub fn extract_zip(zip_path: PathBuf, dest_path: PathBuf) -> Result<(), String> {
-    fn inner(zip_path: &Path, dest_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        let file = File::open(zip_path)?;
-        let mut archive = ZipArchive::new(BufReader::new(file))?;
-        fs::create_dir_all(dest_path)?;
+const MAX_EXTRACTED_BYTES: u64 = 512 * 1024 * 1024;
+const MAX_ARCHIVE_ENTRIES: usize = 10_000;
+const MAX_COMPRESSION_RATIO: u64 = 100;
+
+/// Limits applied when extracting third-party archives, guarding against zip bombs.
+/// Commands always use the defaults, the webview can't loosen them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtractLimits {
+    pub max_total_bytes: u64,
+    pub max_entries: usize,
+    // Only checked for entries larger than RATIO_CHECK_MIN_BYTES, small text files compress well.
+    pub max_compression_ratio: u64,
+}
 
-        for i in 0..archive.len() {
-            let mut entry = archive.by_index(i)?;
-            // Use mangled_name to prevent zip slip attacks
-            let out_path = dest_path.join(entry.mangled_name());
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_total_bytes: MAX_EXTRACTED_BYTES,
+            max_entries: MAX_ARCHIVE_ENTRIES,
+            max_compression_ratio: MAX_COMPRESSION_RATIO,
+        }
+    }
+}
 
-            if entry.is_dir() {
-                fs::create_dir_all(&out_path)?;
-            } else {
-                if let Some(parent) = out_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                let mut out_file = File::create(&out_path)?;
-                std::io::copy(&mut entry, &mut out_file)?;
+const RATIO_CHECK_MIN_BYTES: u64 = 1024 * 1024;
 
-                #[cfg(unix)]
-                if let Some(mode) = entry.unix_mode() {
-                    use std::os::unix::fs::PermissionsExt;
-                    fs::set_permissions(&out_path, fs::Permissions::from_mode(mode)).ok();
-                }
-            }
+// Strips setuid/setgid/sticky bits and group/world write from extracted files.
+#[cfg(unix)]
+const PERMISSION_MASK: u32 = 0o755;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractReport {
+    pub files: Vec<ExtractedFile>,
+    pub total_bytes: u64,
+}
+
+fn unsafe_archive(message: String) -> AppError {
+    AppError::new(ErrorKind::UnsafeArchive, message)
+}
+
+// Checks the central directory before anything is written to disk.
+fn check_archive<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    limits: &ExtractLimits,
+) -> AppResult<()> {
+    if archive.len() > limits.max_entries {
+        return Err(unsafe_archive(format!(
+            "Archive has {} entries, the limit is {}",
+            archive.len(),
+            limits.max_entries
+        )));
+    }
+
+    let mut declared_total = 0u64;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i)?;
+
+        if entry.is_symlink() {
+            return Err(unsafe_archive(format!(
+                "Archive contains a symlink: {}",
+                entry.name()
+            )));
         }
-        Ok(())
+
+        if entry.size() > RATIO_CHECK_MIN_BYTES
+            && entry.size() / entry.compressed_size().max(1) > limits.max_compression_ratio
+        {
+            return Err(unsafe_archive(format!(
+                "Entry {} exceeds the compression ratio limit of {}",
+                entry.name(),
+                limits.max_compression_ratio
+            )));
+        }
+
+        declared_total = declared_total.saturating_add(entry.size());
     }
 
-    inner(&zip_path, &dest_path).map_err(|e| {
-        log::error!("extract_zip failed for {:?}: {}", zip_path, e);
-        e.to_string()
-    })?;
+    if declared_total > limits.max_total_bytes {
+        return Err(unsafe_archive(format!(
+            "Archive expands to {} bytes, the limit is {}",
+            declared_total, limits.max_total_bytes
+        )));
+    }
 
-    log::info!("Extracted {:?} to {:?}", zip_path, dest_path);
     Ok(())
 }
 
-#[command]
-pub async fn download_file(url: String, dest_path: PathBuf) -> Result<(), String> {
-    use std::time::Duration;
-    use tokio::io::AsyncWriteExt;
+fn extract_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    dest_path: &Path,
+    limits: &ExtractLimits,
+    report: &mut ExtractReport,
+) -> AppResult<()> {
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        // Use mangled_name to prevent zip slip attacks
+        let relative_path = entry.mangled_name();
+        let out_path = dest_path.join(&relative_path);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
 
-    async fn inner(url: &str, dest_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        log::info!("Downloading {} to {:?}", url, dest_path);
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out_file = File::create(&out_path)?;
+        report.files.push(ExtractedFile {
+            path: relative_path.to_string_lossy().into_owned(),
+            size: 0,
+        });
+
+        // Declared sizes can lie, so also cap what actually gets written.
+        let remaining = limits.max_total_bytes - report.total_bytes;
+        let written = std::io::copy(
+            &mut (&mut entry).take(remaining.saturating_add(1)),
+            &mut out_file,
+        )?;
+        if written > remaining {
+            return Err(unsafe_archive(format!(
+                "Archive expands to more than {} bytes",
+                limits.max_total_bytes
+            )));
+        }
+        report.total_bytes += written;
+        if let Some(file) = report.files.last_mut() {
+            file.size = written;
+        }
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(300))
-            .connect_timeout(Duration::from_secs(30))
-            .build()?;
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                &out_path,
+                fs::Permissions::from_mode(mode & PERMISSION_MASK),
+            )
+            .ok();
+        }
+    }
+    Ok(())
+}
 
-        let response = client.get(url).send().await?;
+// Sibling of `dest_path`, so moving the extracted files into place is a rename on the same filesystem.
+fn staging_path_for(dest_path: &Path) -> PathBuf {
+    let mut file_name = std::ffi::OsString::from(".");
+    file_name.push(dest_path.file_name().unwrap_or_default());
+    file_name.push(format!(".extracting-{}", uuid::Uuid::new_v4()));
+    dest_path.with_file_name(file_name)
+}
 
-        if !response.status().is_success() {
-            return Err(format!("HTTP error: {}", response.status()).into());
+// Moves everything in `source` into `target`, replacing files that already exist there.
+fn merge_into(source: &Path, target: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target_path = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() && target_path.is_dir() {
+            merge_into(&entry.path(), &target_path)?;
+        } else {
+            if target_path.is_dir() {
+                fs::remove_dir_all(&target_path)?;
+            }
+            fs::rename(entry.path(), &target_path)?;
         }
+    }
+    Ok(())
+}
+
+/// Extracts `zip_path` into `dest_path` within `limits`, for the plugin installer. Entries are extracted into a staging dir first and only moved into
+/// `dest_path` once all of them were written, so a failed extraction leaves nothing behind.
+pub fn extract_archive(
+    zip_path: &Path,
+    dest_path: &Path,
+    limits: &ExtractLimits,
+) -> AppResult<ExtractReport> {
+    fn inner(
+        zip_path: &Path,
+        dest_path: &Path,
+        limits: &ExtractLimits,
+    ) -> AppResult<ExtractReport> {
+        let file = File::open(zip_path)?;
+        let mut archive = ZipArchive::new(BufReader::new(file))?;
+        check_archive(&mut archive, limits)?;
 
         if let Some(parent) = dest_path.parent() {
             fs::create_dir_all(parent)?;
         }
+        let staging = staging_path_for(dest_path);
+        fs::create_dir(&staging)?;
 
-        let mut file = tokio::fs::File::create(dest_path).await?;
-        let mut stream = response.bytes_stream();
+        let mut report = ExtractReport::default();
+        let result = extract_entries(&mut archive, &staging, limits, &mut report).and_then(|()| {
+            if dest_path.exists() {
+                merge_into(&staging, dest_path)?;
+            } else {
+                fs::rename(&staging, dest_path)?;
+            }
+            Ok(())
+        });
 
-        use futures::StreamExt;
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            file.write_all(&chunk).await?;
+        if staging.exists() {
+            if let Err(e) = fs::remove_dir_all(&staging) {
+                log::warn!("Failed to remove staging dir {:?}: {}", staging, e);
+            }
         }
-
-        log::info!("Download complete: {:?}", dest_path);
-        Ok(())
+        result.map(|()| report)
     }
 
-    inner(&url, &dest_path).await.map_err(|e| {
-        log::error!("download_file failed for {}: {}", url, e);
-        e.to_string()
-    })
+    let report = inner(zip_path, dest_path, limits).map_err(|e| {
+        log::error!("extract_archive failed for {:?}: {}", zip_path, e);
+        e
+    })?;
+
+    log::info!(
+        "Extracted {} files ({} bytes) from {:?} to {:?}",
+        report.files.len(),
+        report.total_bytes,
+        zip_path,
+        dest_path
+    );
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -154,7 +315,7 @@ mod tests {
         zip.finish().unwrap();
     }
 
-    mod extract_zip {
+    mod extract_archive {
         use super::*;
         use tempfile::tempdir;
 
@@ -173,7 +334,7 @@ mod tests {
                 ],
             );
 
-            extract_zip(zip_path, dest_path.clone()).unwrap();
+            extract_archive(&zip_path, &dest_path, &ExtractLimits::default()).unwrap();
 
             assert_eq!(
                 fs::read_to_string(dest_path.join("file1.txt")).unwrap(),
@@ -204,7 +365,7 @@ mod tests {
                 ],
             );
 
-            extract_zip(zip_path, dest_path.clone()).unwrap();
+            extract_archive(&zip_path, &dest_path, &ExtractLimits::default()).unwrap();
 
             assert_eq!(
                 fs::read_to_string(dest_path.join("root.txt")).unwrap(),
@@ -229,19 +390,66 @@ mod tests {
             create_test_zip(&zip_path, &[("file.txt", b"content")]);
 
             assert!(!dest_path.exists());
-            extract_zip(zip_path, dest_path.clone()).unwrap();
+            extract_archive(&zip_path, &dest_path, &ExtractLimits::default()).unwrap();
             assert!(dest_path.exists());
         }
 
+        #[test]
+        fn merges_into_existing_destination() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("test.zip");
+            let dest_path = temp.path().join("output");
+            fs::create_dir_all(dest_path.join("sub")).unwrap();
+            fs::write(dest_path.join("kept.txt"), b"kept").unwrap();
+            fs::write(dest_path.join("sub/replaced.txt"), b"old").unwrap();
+
+            create_test_zip(&zip_path, &[("sub/replaced.txt", b"new")]);
+
+            extract_archive(&zip_path, &dest_path, &ExtractLimits::default()).unwrap();
+
+            assert_eq!(
+                fs::read_to_string(dest_path.join("kept.txt")).unwrap(),
+                "kept"
+            );
+            assert_eq!(
+                fs::read_to_string(dest_path.join("sub/replaced.txt")).unwrap(),
+                "new"
+            );
+            assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 2);
+        }
+
+        #[test]
+        fn leaves_nothing_behind_when_extraction_fails() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("test.zip");
+            let dest_path = temp.path().join("output");
+
+            // "file" is extracted as a file, so "file/inner.txt" can't be created under it
+            create_test_zip(
+                &zip_path,
+                &[
+                    ("sub/first.txt", b"first"),
+                    ("file", b"file"),
+                    ("file/inner.txt", b"inner"),
+                ],
+            );
+
+            let result = extract_archive(&zip_path, &dest_path, &ExtractLimits::default());
+
+            assert!(result.is_err());
+            assert!(!dest_path.exists());
+            assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 1);
+        }
+
         #[test]
         fn returns_error_for_nonexistent_zip() {
             let temp = tempdir().unwrap();
             let zip_path = temp.path().join("nonexistent.zip");
             let dest_path = temp.path().join("output");
 
-            let result = extract_zip(zip_path, dest_path);
+            let result = extract_archive(&zip_path, &dest_path, &ExtractLimits::default());
 
-            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().kind, ErrorKind::Io);
         }
 
         #[test]
@@ -252,9 +460,137 @@ mod tests {
 
             fs::write(&zip_path, b"not a zip file").unwrap();
 
-            let result = extract_zip(zip_path, dest_path);
+            let result = extract_archive(&zip_path, &dest_path, &ExtractLimits::default());
 
-            assert!(result.is_err());
+            assert_eq!(result.unwrap_err().kind, ErrorKind::ParseError);
+        }
+
+        #[test]
+        fn reports_extracted_files() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("test.zip");
+            let dest_path = temp.path().join("output");
+
+            create_test_zip(&zip_path, &[("a.txt", b"abc"), ("sub/b.txt", b"de")]);
+
+            let report = extract_archive(&zip_path, &dest_path, &ExtractLimits::default()).unwrap();
+
+            assert_eq!(
+                report,
+                ExtractReport {
+                    files: vec![
+                        ExtractedFile {
+                            path: "a.txt".to_string(),
+                            size: 3,
+                        },
+                        ExtractedFile {
+                            path: "sub/b.txt".to_string(),
+                            size: 2,
+                        },
+                    ],
+                    total_bytes: 5,
+                }
+            );
+        }
+
+        #[test]
+        fn rejects_too_many_entries() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("test.zip");
+            let dest_path = temp.path().join("output");
+            let limits = ExtractLimits {
+                max_entries: 2,
+                ..ExtractLimits::default()
+            };
+
+            create_test_zip(&zip_path, &[("1", b"1"), ("2", b"2"), ("3", b"3")]);
+
+            let result = extract_archive(&zip_path, &dest_path, &limits);
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::UnsafeArchive);
+            assert!(!dest_path.exists());
+        }
+
+        #[test]
+        fn rejects_archives_over_size_limit() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("test.zip");
+            let dest_path = temp.path().join("output");
+            let limits = ExtractLimits {
+                max_total_bytes: 10,
+                ..ExtractLimits::default()
+            };
+
+            create_test_zip(&zip_path, &[("a.txt", b"123456"), ("b.txt", b"123456")]);
+
+            let result = extract_archive(&zip_path, &dest_path, &limits);
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::UnsafeArchive);
+            assert!(error.message.contains("limit is 10"));
+            assert!(!dest_path.exists());
+        }
+
+        #[test]
+        fn rejects_high_compression_ratio() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("bomb.zip");
+            let dest_path = temp.path().join("output");
+            let zeros = vec![0u8; 4 * 1024 * 1024];
+
+            create_test_zip(&zip_path, &[("zeros.bin", &zeros)]);
+
+            let result = extract_archive(&zip_path, &dest_path, &ExtractLimits::default());
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::UnsafeArchive);
+            assert!(error.message.contains("compression ratio"));
+            assert!(!dest_path.exists());
+        }
+
+        #[test]
+        fn rejects_symlinks() {
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("link.zip");
+            let dest_path = temp.path().join("output");
+
+            let mut zip = ZipWriter::new(File::create(&zip_path).unwrap());
+            let options: FileOptions<'_, ()> = FileOptions::default();
+            zip.start_file("plugin.js", options).unwrap();
+            zip.write_all(b"code").unwrap();
+            zip.add_symlink("secrets", "/etc/passwd", options).unwrap();
+            zip.finish().unwrap();
+
+            let result = extract_archive(&zip_path, &dest_path, &ExtractLimits::default());
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::UnsafeArchive);
+            assert!(error.message.contains("secrets"));
+            assert!(!dest_path.exists());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn masks_unsafe_permission_bits() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let temp = tempdir().unwrap();
+            let zip_path = temp.path().join("setuid.zip");
+            let dest_path = temp.path().join("output");
+
+            let mut zip = ZipWriter::new(File::create(&zip_path).unwrap());
+            let options: FileOptions<'_, ()> = FileOptions::default().unix_permissions(0o4777);
+            zip.start_file("run.sh", options).unwrap();
+            zip.write_all(b"#!/bin/sh").unwrap();
+            zip.finish().unwrap();
+
+            extract_archive(&zip_path, &dest_path, &ExtractLimits::default()).unwrap();
+
+            let mode = fs::metadata(dest_path.join("run.sh"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o7777, 0o755);
         }
     }
 }
@@ -0,0 +1,55 @@
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_dialog::{DialogExt, FilePath};
+use tokio::sync::oneshot;
+
+use crate::error::{AppError, AppResult};
+
+// Native file dialogs opened from Rust, for commands that read or write a file of the user's
+// choosing. Taking the path from the dialog rather than from an argument means code in the
+// webview, plugins included, can't point those commands at arbitrary files.
+
+async fn into_path(
+    picked: oneshot::Receiver<Option<FilePath>>,
+) -> AppResult<Option<std::path::PathBuf>> {
+    match picked.await.ok().flatten() {
+        Some(path) => path
+            .into_path()
+            .map(Some)
+            .map_err(|e| AppError::io(format!("Invalid path from file dialog: {}", e))),
+        None => Ok(None),
+    }
+}
+
+/// Asks the user for a file to open. None if they cancel.
+pub async fn pick_file<R: Runtime>(
+    app: &AppHandle<R>,
+    filter_name: &str,
+    extensions: &[&str],
+) -> AppResult<Option<std::path::PathBuf>> {
+    let (picked, receiver) = oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter(filter_name, extensions)
+        .pick_file(move |path| {
+            let _ = picked.send(path);
+        });
+    into_path(receiver).await
+}
+
+/// Asks the user where to save a file, suggesting `file_name`. None if they cancel.
+pub async fn pick_save_path<R: Runtime>(
+    app: &AppHandle<R>,
+    file_name: &str,
+    filter_name: &str,
+    extensions: &[&str],
+) -> AppResult<Option<std::path::PathBuf>> {
+    let (picked, receiver) = oneshot::channel();
+    app.dialog()
+        .file()
+        .set_file_name(file_name)
+        .add_filter(filter_name, extensions)
+        .save_file(move |path| {
+            let _ = picked.send(path);
+        });
+    into_path(receiver).await
+}
@@ -0,0 +1,1036 @@
+use futures::StreamExt;
+use log::{debug, error, info, warn};
+use once_cell::sync::Lazy;
+use reqwest::header::{self, HeaderValue};
+use reqwest::{Client, ClientBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{command, AppHandle, Emitter, Manager};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult, ErrorKind};
+use crate::http::client::{HttpClients, RedirectMode};
+use crate::network::NetworkSettings;
+
+// File downloads with progress reporting, cancellation and resume after dropped connections.
+// Downloads land in a sibling .part file and are only renamed into place once complete and verified,
+// so the destination never holds a truncated file. The .part file is named after the URL too, so
+// downloads of different files to one destination don't share it. It's removed whenever a download
+// fails, so nothing is left behind and a later call never appends to bytes of another version.
+
+const PROGRESS_EVENT: &str = "download:progress";
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_RESUME_ATTEMPTS: u32 = 3;
+// How long a cancellation for a download that hasn't started yet is remembered
+const EARLY_CANCEL_TTL: Duration = Duration::from_secs(60);
+
+enum DownloadSlot {
+    Running(CancellationToken),
+    // cancel_download arrived before download_file registered the id
+    Cancelled(Instant),
+}
+
+static ACTIVE_DOWNLOADS: Lazy<Mutex<HashMap<String, DownloadSlot>>> = Lazy::new(Default::default);
+
+// .part files currently being written, so two downloads never write the same one
+static ACTIVE_PARTS: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(Default::default);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferProgress {
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub bytes_per_second: f64,
+}
+
+/// Optional integrity checks for a download, supplied by the caller.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpectedFile {
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+// What was actually written, checked against ExpectedFile once the transfer completes.
+struct Transferred {
+    size: u64,
+    sha256: String,
+}
+
+impl ExpectedFile {
+    fn verify(&self, transferred: &Transferred) -> AppResult<()> {
+        if let Some(size) = self.size {
+            if size != transferred.size {
+                return Err(AppError::new(
+                    ErrorKind::VerificationFailed,
+                    format!(
+                        "Size mismatch: expected {} bytes, got {}",
+                        size, transferred.size
+                    ),
+                ));
+            }
+        }
+
+        if let Some(sha256) = &self.sha256 {
+            if !sha256.eq_ignore_ascii_case(&transferred.sha256) {
+                return Err(
+                    AppError::new(ErrorKind::VerificationFailed, "Checksum mismatch").with_details(
+                        format!("expected sha256 {}, got {}", sha256, transferred.sha256),
+                    ),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Payload of the download:progress event
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DownloadProgressEvent {
+    id: String,
+    #[serde(flatten)]
+    progress: TransferProgress,
+}
+
+fn client(builder: ClientBuilder) -> AppResult<Client> {
+    builder
+        .timeout(Duration::from_secs(300))
+        .connect_timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| AppError::unknown(format!("Failed to create HTTP client: {}", e)))
+}
+
+// Start offset and complete length from a Content-Range header like "bytes 100-199/200".
+fn content_range(response: &Response) -> Option<(u64, Option<u64>)> {
+    let value = response
+        .headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.parse().ok()?, total.parse().ok()))
+}
+
+// Identifies the version of the file a response carries: a strong ETag, or failing that
+// Last-Modified. Sent back as If-Range, so a resume never appends bytes of another version.
+fn validator(response: &Response) -> Option<HeaderValue> {
+    let headers = response.headers();
+    headers
+        .get(header::ETAG)
+        .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+        .or_else(|| headers.get(header::LAST_MODIFIED))
+        .cloned()
+}
+
+// Where an interrupted download carries on from, and what it must match.
+struct Resume<'a> {
+    offset: u64,
+    validator: &'a HeaderValue,
+    total: Option<u64>,
+}
+
+// Requests `url`, from `resume` if given. Returns whether the server honoured the range for the
+// same version and length of the file.
+async fn open(
+    client: &Client,
+    url: &str,
+    resume: Option<Resume<'_>>,
+) -> AppResult<(Response, bool)> {
+    let mut request = client.get(url);
+    if let Some(resume) = &resume {
+        request = request
+            .header(header::RANGE, format!("bytes={}-", resume.offset))
+            .header(header::IF_RANGE, resume.validator.clone());
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(AppError::http_status(response.status().as_u16()));
+    }
+
+    let resumed = resume.is_some_and(|resume| {
+        response.status() == StatusCode::PARTIAL_CONTENT
+            && content_range(&response).is_some_and(|(start, total)| {
+                start == resume.offset
+                    && (resume.total.is_none() || total.is_none() || total == resume.total)
+            })
+    });
+    Ok((response, resumed))
+}
+
+async fn transfer(
+    client: &Client,
+    url: &str,
+    file: &mut File,
+    cancel: &CancellationToken,
+    on_progress: &mut impl FnMut(TransferProgress),
+) -> AppResult<Transferred> {
+    let mut hasher = Sha256::new();
+    let started = Instant::now();
+    let mut downloaded = 0u64;
+    let mut total = None;
+    let mut validator = None;
+    let mut attempts = 0;
+
+    let progress = |downloaded: u64, total: Option<u64>| TransferProgress {
+        downloaded_bytes: downloaded,
+        total_bytes: total,
+        bytes_per_second: downloaded as f64 / started.elapsed().as_secs_f64().max(0.001),
+    };
+
+    loop {
+        let resume = validator
+            .as_ref()
+            .filter(|_| downloaded > 0)
+            .map(|validator| Resume {
+                offset: downloaded,
+                validator,
+                total,
+            });
+        let (response, resumed) = tokio::select! {
+            _ = cancel.cancelled() => return Err(AppError::cancelled()),
+            opened = open(client, url, resume) => opened?,
+        };
+
+        if !resumed {
+            if downloaded > 0 {
+                warn!("Can't resume download of {}, restarting download", url);
+                file.set_len(0).await?;
+                file.seek(SeekFrom::Start(0)).await?;
+                hasher = Sha256::new();
+                downloaded = 0;
+                total = None;
+            }
+            validator = self::validator(&response);
+        }
+        total = response
+            .content_length()
+            .map(|length| length + downloaded)
+            .or(total);
+
+        let mut stream = response.bytes_stream();
+        let mut last_report = Instant::now();
+        let interrupted = loop {
+            let chunk = tokio::select! {
+                _ = cancel.cancelled() => return Err(AppError::cancelled()),
+                chunk = stream.next() => chunk,
+            };
+
+            match chunk {
+                None => break None,
+                Some(Err(e)) => break Some(e),
+                Some(Ok(chunk)) => {
+                    file.write_all(&chunk).await?;
+                    hasher.update(&chunk);
+                    downloaded += chunk.len() as u64;
+                    if last_report.elapsed() >= PROGRESS_INTERVAL {
+                        on_progress(progress(downloaded, total));
+                        last_report = Instant::now();
+                    }
+                }
+            }
+        };
+
+        match interrupted {
+            None => break,
+            Some(e) if attempts < MAX_RESUME_ATTEMPTS => {
+                attempts += 1;
+                warn!(
+                    "Download of {} interrupted at {} bytes, resuming (attempt {}): {}",
+                    url, downloaded, attempts, e
+                );
+            }
+            Some(e) => return Err(AppError::network(format!("Connection dropped: {}", e))),
+        }
+    }
+
+    file.flush().await?;
+    on_progress(progress(downloaded, total));
+    Ok(Transferred {
+        size: downloaded,
+        sha256: hex::encode(hasher.finalize()),
+    })
+}
+
+fn part_path(dest_path: &Path, url: &str) -> PathBuf {
+    let url_hash = hex::encode(Sha256::digest(url.as_bytes()));
+    let mut file_name = dest_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}.part", &url_hash[..16]));
+    dest_path.with_file_name(file_name)
+}
+
+// Marks a .part file as in use for as long as it's held.
+struct PartClaim(PathBuf);
+
+impl PartClaim {
+    fn acquire(part_path: &Path) -> AppResult<Self> {
+        let mut parts = ACTIVE_PARTS.lock().unwrap_or_else(|e| e.into_inner());
+        if !parts.insert(part_path.to_path_buf()) {
+            return Err(AppError::io(format!(
+                "{:?} is already being downloaded",
+                part_path
+            )));
+        }
+        Ok(Self(part_path.to_path_buf()))
+    }
+}
+
+impl Drop for PartClaim {
+    fn drop(&mut self) {
+        ACTIVE_PARTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.0);
+    }
+}
+
+/// Streams `url` into `dest_path`, reporting progress as it goes. Resumes with a Range request
+/// if the connection drops and the server can tell the file didn't change. The file is written to
+/// a sibling `.part` file, checked against `expected`, then renamed into place. On failure, or
+/// cancellation, nothing is left behind.
+pub async fn download_with_progress(
+    network: &NetworkSettings,
+    url: &str,
+    dest_path: &Path,
+    expected: &ExpectedFile,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(TransferProgress),
+) -> AppResult<()> {
+    let client = client(network.client_builder()?)?;
+    download_with_client(&client, url, dest_path, expected, cancel, on_progress).await
+}
+
+async fn download_with_client(
+    client: &Client,
+    url: &str,
+    dest_path: &Path,
+    expected: &ExpectedFile,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(TransferProgress),
+) -> AppResult<()> {
+    info!("Downloading {} to {:?}", url, dest_path);
+
+    let part_path = part_path(dest_path, url);
+    let _claim = PartClaim::acquire(&part_path)?;
+
+    if let Some(parent) = dest_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let result: AppResult<()> = async {
+        let mut file = File::create(&part_path).await?;
+        let transferred = transfer(client, url, &mut file, cancel, &mut on_progress).await?;
+        drop(file);
+        expected.verify(&transferred)?;
+        fs::rename(&part_path, dest_path).await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            info!("Download complete: {:?}", dest_path);
+            Ok(())
+        }
+        Err(e) => {
+            if let Err(remove_error) = fs::remove_file(&part_path).await {
+                debug!(
+                    "Failed to remove partial download {:?}: {}",
+                    part_path, remove_error
+                );
+            }
+            error!("download_file failed for {}: {}", url, e);
+            Err(e)
+        }
+    }
+}
+
+/// Streams `url` into `dest_path`. Shared by the download_file command and the yt-dlp manager.
+pub async fn download_to_path(
+    network: &NetworkSettings,
+    url: &str,
+    dest_path: &Path,
+) -> AppResult<()> {
+    download_with_progress(
+        network,
+        url,
+        dest_path,
+        &ExpectedFile::default(),
+        &CancellationToken::new(),
+        |_| {},
+    )
+    .await
+}
+
+// Registers `id` as running. Fails if it's already running, or was cancelled before it started.
+fn register_download(id: &str) -> AppResult<CancellationToken> {
+    let mut downloads = ACTIVE_DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner());
+    downloads.retain(|_, slot| match slot {
+        DownloadSlot::Cancelled(at) => at.elapsed() < EARLY_CANCEL_TTL,
+        DownloadSlot::Running(_) => true,
+    });
+
+    match downloads.remove(id) {
+        Some(DownloadSlot::Running(cancel)) => {
+            downloads.insert(id.to_string(), DownloadSlot::Running(cancel));
+            Err(AppError::io(format!("Download {} is already running", id)))
+        }
+        Some(DownloadSlot::Cancelled(_)) => {
+            info!("Download {} was cancelled before it started", id);
+            Err(AppError::cancelled())
+        }
+        None => {
+            let cancel = CancellationToken::new();
+            downloads.insert(id.to_string(), DownloadSlot::Running(cancel.clone()));
+            Ok(cancel)
+        }
+    }
+}
+
+fn cancel_registered(id: &str) {
+    let mut downloads = ACTIVE_DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner());
+    match downloads.get(id) {
+        Some(DownloadSlot::Running(cancel)) => {
+            info!("Cancelling download {}", id);
+            cancel.cancel();
+        }
+        _ => {
+            debug!("Download {} hasn't started, cancelling it once it does", id);
+            downloads.insert(id.to_string(), DownloadSlot::Cancelled(Instant::now()));
+        }
+    }
+}
+
+/// Downloads `url` to `dest_path` under the caller's network policy, like http_fetch.
+#[command]
+pub async fn download_file(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    url: String,
+    dest_path: PathBuf,
+    download_id: Option<String>,
+    expected: Option<ExpectedFile>,
+) -> AppResult<()> {
+    let caller = callers.resolve(token.as_deref())?;
+    let clients = app_handle
+        .try_state::<HttpClients>()
+        .ok_or_else(|| AppError::unknown("HTTP clients are not available"))?;
+    let parsed = Url::parse(&url).map_err(|e| AppError::parse(format!("Invalid URL: {}", e)))?;
+    clients.authorize(caller.plugin_id(), &parsed).await?;
+    let builder = clients.network().settings().client_builder()?;
+    let client = client(clients.with_policy(builder, caller.plugin_id(), RedirectMode::Follow))?;
+
+    let expected = expected.unwrap_or_default();
+    let Some(id) = download_id else {
+        return download_with_client(
+            &client,
+            &url,
+            &dest_path,
+            &expected,
+            &CancellationToken::new(),
+            |_| {},
+        )
+        .await;
+    };
+
+    let cancel = register_download(&id)?;
+    let result = download_with_client(&client, &url, &dest_path, &expected, &cancel, |progress| {
+        let event = DownloadProgressEvent {
+            id: id.clone(),
+            progress,
+        };
+        if let Err(e) = app_handle.emit(PROGRESS_EVENT, event) {
+            debug!("Failed to emit download progress: {}", e);
+        }
+    })
+    .await;
+
+    ACTIVE_DOWNLOADS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&id);
+    result
+}
+
+/// Cancels the download registered under `download_id`. A cancellation that arrives before the
+/// download starts is remembered, and the download fails as cancelled as soon as it does.
+#[command]
+pub fn cancel_download(download_id: String) -> AppResult<()> {
+    cancel_registered(&download_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode as AxumStatus};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    const CHUNK_SIZE: usize = 16 * 1024;
+
+    fn payload() -> Vec<u8> {
+        (0..CHUNK_SIZE * 8).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[derive(Clone)]
+    struct Upstream {
+        payload: Arc<Vec<u8>>,
+        // Delay between chunks
+        pacing: Duration,
+        // Drop the connection after this many chunks on the first request
+        fail_after_chunks: Option<usize>,
+        // Keep dropping the connection on every request, not just the first
+        fail_every_request: bool,
+        supports_ranges: bool,
+        // ETag of the first response, None to send no validator
+        etag: Option<&'static str>,
+        // Answer later requests with a different ETag, as if the file was replaced
+        changes_after_first_request: bool,
+        requests: Arc<Mutex<Vec<Option<String>>>>,
+        if_ranges: Arc<Mutex<Vec<Option<String>>>>,
+        served: Arc<AtomicUsize>,
+    }
+
+    impl Upstream {
+        fn new() -> Self {
+            Self {
+                payload: Arc::new(payload()),
+                pacing: Duration::ZERO,
+                fail_after_chunks: None,
+                fail_every_request: false,
+                supports_ranges: true,
+                etag: Some("\"v1\""),
+                changes_after_first_request: false,
+                requests: Arc::default(),
+                if_ranges: Arc::default(),
+                served: Arc::default(),
+            }
+        }
+    }
+
+    async fn serve_file(State(upstream): State<Upstream>, headers: HeaderMap) -> impl IntoResponse {
+        let range = headers
+            .get("range")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let if_range = headers
+            .get("if-range")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let first_request = {
+            let mut requests = upstream.requests.lock().unwrap();
+            requests.push(range.clone());
+            requests.len() == 1
+        };
+        upstream.if_ranges.lock().unwrap().push(if_range.clone());
+
+        let replaced = upstream.changes_after_first_request && !first_request;
+        let etag = upstream
+            .etag
+            .map(|etag| if replaced { "\"v2\"" } else { etag }.to_string());
+        let same_version = if_range.is_none() || if_range == etag;
+        let start = match (&range, upstream.supports_ranges && same_version) {
+            (Some(range), true) => range
+                .trim_start_matches("bytes=")
+                .trim_end_matches('-')
+                .parse::<usize>()
+                .unwrap(),
+            _ => 0,
+        };
+        let total = upstream.payload.len();
+        let fail_after = upstream
+            .fail_after_chunks
+            .filter(|_| first_request || upstream.fail_every_request);
+
+        let chunks: Vec<Vec<u8>> = upstream.payload[start..]
+            .chunks(CHUNK_SIZE)
+            .map(<[u8]>::to_vec)
+            .collect();
+        let pacing = upstream.pacing;
+        let served = upstream.served.clone();
+        let stream =
+            futures::stream::iter(chunks.into_iter().enumerate()).then(move |(index, chunk)| {
+                let served = served.clone();
+                async move {
+                    tokio::time::sleep(pacing).await;
+                    if fail_after == Some(index) {
+                        return Err(std::io::Error::other("connection dropped"));
+                    }
+                    served.fetch_add(chunk.len(), Ordering::SeqCst);
+                    Ok(chunk)
+                }
+            });
+
+        let mut response = axum::http::Response::builder()
+            .header("content-length", (total - start).to_string())
+            .header("accept-ranges", "bytes");
+        if let Some(etag) = etag {
+            response = response.header("etag", etag);
+        }
+        if start > 0 {
+            response = response.status(AxumStatus::PARTIAL_CONTENT).header(
+                "content-range",
+                format!("bytes {}-{}/{}", start, total - 1, total),
+            );
+        }
+        response.body(Body::from_stream(stream)).unwrap()
+    }
+
+    async fn serve(upstream: Upstream) -> String {
+        let router = Router::new()
+            .route("/file", get(serve_file))
+            .route("/missing", get(|| async { AxumStatus::NOT_FOUND }))
+            .with_state(upstream);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    // Stands in for a forwarding proxy, answering with the absolute URL it was asked for
+    async fn serve_proxy() -> String {
+        let router = Router::new()
+            .fallback(|uri: axum::http::Uri| async move { format!("proxied {}", uri) });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    mod download_with_progress {
+        use super::*;
+
+        #[tokio::test]
+        async fn goes_through_the_configured_proxy() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let network = NetworkSettings {
+                proxy_url: Some(serve_proxy().await),
+                ..Default::default()
+            };
+
+            download_with_progress(
+                &network,
+                "http://mirror.example/file.bin",
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                std::fs::read_to_string(&dest).unwrap(),
+                "proxied http://mirror.example/file.bin"
+            );
+        }
+
+        #[tokio::test]
+        async fn writes_file_and_reports_progress() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("nested/file.bin");
+            let upstream = Upstream {
+                pacing: Duration::from_millis(30),
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+            let mut reports = Vec::new();
+
+            download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |progress| reports.push(progress),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+            assert!(reports.len() > 1);
+            let last = reports.last().unwrap();
+            assert_eq!(last.downloaded_bytes, upstream.payload.len() as u64);
+            assert_eq!(last.total_bytes, Some(upstream.payload.len() as u64));
+            assert!(last.bytes_per_second > 0.0);
+            assert!(reports
+                .windows(2)
+                .all(|pair| pair[0].downloaded_bytes <= pair[1].downloaded_bytes));
+        }
+
+        #[tokio::test]
+        async fn cancels_and_removes_partial_file() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                pacing: Duration::from_millis(50),
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+            let cancel = CancellationToken::new();
+            let cancel_on_progress = cancel.clone();
+
+            let result = download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &cancel,
+                |_| cancel_on_progress.cancel(),
+            )
+            .await;
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::Cancelled);
+            assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+            assert!(upstream.served.load(Ordering::SeqCst) < upstream.payload.len());
+        }
+
+        #[tokio::test]
+        async fn resumes_with_range_after_dropped_connection() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                fail_after_chunks: Some(3),
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+
+            download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+            let requests = upstream.requests.lock().unwrap().clone();
+            assert_eq!(requests.len(), 2);
+            assert_eq!(requests[0], None);
+            assert_eq!(requests[1], Some(format!("bytes={}-", CHUNK_SIZE * 3)));
+            let if_ranges = upstream.if_ranges.lock().unwrap().clone();
+            assert_eq!(if_ranges[1].as_deref(), Some("\"v1\""));
+        }
+
+        #[tokio::test]
+        async fn restarts_when_the_file_changed_in_between() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                fail_after_chunks: Some(3),
+                changes_after_first_request: true,
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+
+            download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+            assert_eq!(
+                upstream.served.load(Ordering::SeqCst),
+                CHUNK_SIZE * 3 + upstream.payload.len()
+            );
+        }
+
+        #[tokio::test]
+        async fn restarts_without_a_validator() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                fail_after_chunks: Some(3),
+                etag: None,
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+
+            download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+            assert_eq!(*upstream.requests.lock().unwrap(), vec![None, None]);
+        }
+
+        #[tokio::test]
+        async fn applies_the_network_policy_of_the_plugin() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let url = serve(Upstream::new())
+                .await
+                .replace("127.0.0.1", "localhost");
+            let clients = HttpClients::default();
+            let builder = NetworkSettings::default().client_builder().unwrap();
+            let client =
+                client(clients.with_policy(builder, Some("lastfm"), RedirectMode::Follow)).unwrap();
+
+            let result = download_with_client(
+                &client,
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await;
+
+            assert!(result.is_err());
+            assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+        }
+
+        #[tokio::test]
+        async fn refuses_to_download_the_same_file_twice_at_once() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                pacing: Duration::from_millis(10),
+                ..Upstream::new()
+            };
+            let url = format!("{}/file", serve(upstream.clone()).await);
+            let (network, expected, cancel) = Default::default();
+            let download =
+                || download_with_progress(&network, &url, &dest, &expected, &cancel, |_| {});
+
+            let (first, second) = tokio::join!(download(), download());
+
+            first.unwrap();
+            assert!(second
+                .unwrap_err()
+                .message
+                .contains("already being downloaded"));
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+        }
+
+        #[test]
+        fn gives_each_url_its_own_part_file() {
+            let dest = Path::new("/downloads/file.bin");
+
+            assert_ne!(
+                part_path(dest, "https://a.example/file"),
+                part_path(dest, "https://b.example/file")
+            );
+        }
+
+        #[tokio::test]
+        async fn restarts_when_server_ignores_range() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                fail_after_chunks: Some(3),
+                supports_ranges: false,
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+
+            download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+        }
+
+        #[tokio::test]
+        async fn returns_http_status_and_removes_file() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let url = serve(Upstream::new()).await;
+
+            let result = download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/missing", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await;
+
+            assert_eq!(
+                result.unwrap_err().kind,
+                ErrorKind::HttpStatus { code: 404 }
+            );
+            assert!(!dest.exists());
+        }
+
+        #[tokio::test]
+        async fn verifies_expected_checksum_and_size() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream::new();
+            let url = serve(upstream.clone()).await;
+            let expected = ExpectedFile {
+                sha256: Some(hex::encode(Sha256::digest(&*upstream.payload)).to_uppercase()),
+                size: Some(upstream.payload.len() as u64),
+            };
+
+            download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &expected,
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(std::fs::read(&dest).unwrap(), *upstream.payload);
+            assert!(!part_path(&dest, &format!("{}/file", url)).exists());
+        }
+
+        #[tokio::test]
+        async fn rejects_checksum_mismatch_and_keeps_existing_file() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            std::fs::write(&dest, b"previous version").unwrap();
+            let url = serve(Upstream::new()).await;
+            let expected = ExpectedFile {
+                sha256: Some(hex::encode(Sha256::digest(b"something else"))),
+                size: None,
+            };
+
+            let result = download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &expected,
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await;
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::VerificationFailed);
+            assert_eq!(std::fs::read(&dest).unwrap(), b"previous version");
+            assert!(!part_path(&dest, &format!("{}/file", url)).exists());
+        }
+
+        #[tokio::test]
+        async fn rejects_size_mismatch() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let url = serve(Upstream::new()).await;
+            let expected = ExpectedFile {
+                sha256: None,
+                size: Some(1),
+            };
+
+            let result = download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &expected,
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await;
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::VerificationFailed);
+            assert!(error.message.contains("Size mismatch"));
+            assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+        }
+
+        #[tokio::test]
+        async fn leaves_nothing_behind_when_connection_keeps_dropping() {
+            let temp = tempdir().unwrap();
+            let dest = temp.path().join("file.bin");
+            let upstream = Upstream {
+                fail_after_chunks: Some(1),
+                fail_every_request: true,
+                ..Upstream::new()
+            };
+            let url = serve(upstream.clone()).await;
+
+            let result = download_with_progress(
+                &NetworkSettings::default(),
+                &format!("{}/file", url),
+                &dest,
+                &ExpectedFile::default(),
+                &CancellationToken::new(),
+                |_| {},
+            )
+            .await;
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::Network);
+            assert!(upstream.served.load(Ordering::SeqCst) > 0);
+            assert_eq!(std::fs::read_dir(temp.path()).unwrap().count(), 0);
+        }
+    }
+
+    mod register_download {
+        use super::*;
+
+        #[test]
+        fn rejects_an_id_that_is_already_running() {
+            let cancel = register_download("duplicate").unwrap();
+
+            let error = register_download("duplicate").unwrap_err();
+            cancel_registered("duplicate");
+
+            assert!(error.message.contains("already running"));
+            assert!(cancel.is_cancelled());
+            ACTIVE_DOWNLOADS.lock().unwrap().remove("duplicate");
+        }
+
+        #[test]
+        fn fails_a_download_cancelled_before_it_started() {
+            cancel_registered("early");
+
+            assert_eq!(
+                register_download("early").unwrap_err().kind,
+                ErrorKind::Cancelled
+            );
+            assert!(register_download("early").is_ok());
+            ACTIVE_DOWNLOADS.lock().unwrap().remove("early");
+        }
+    }
+}
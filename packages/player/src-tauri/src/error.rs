@@ -0,0 +1,183 @@
+use serde::Serialize;
+use std::fmt;
+
+// These types correspond to Typescript types in packages/plugin-sdk/src/types/errors.ts
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotInstalled,
+    Network,
+    Timeout,
+    HttpStatus { code: u16 },
+    ParseError,
+    VideoUnavailable,
+    AgeRestricted,
+    GeoRestricted,
+    VerificationFailed,
+    UnsafeArchive,
+    Unsigned,
+    UnknownSigningKey,
+    BadSignature,
+    PermissionDenied,
+    Cancelled,
+    Io,
+    Unknown,
+}
+
+/// Error returned by every Tauri command, serialized as
+/// `{ kind, message, details? }` (plus `code` for `http_status`).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AppError {
+    #[serde(flatten)]
+    pub kind: ErrorKind,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Network, message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::ParseError, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Io, message)
+    }
+
+    pub fn unknown(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Unknown, message)
+    }
+
+    pub fn http_status(code: u16) -> Self {
+        Self::new(
+            ErrorKind::HttpStatus { code },
+            format!("HTTP error: {}", code),
+        )
+    }
+
+    pub fn cancelled() -> Self {
+        Self::new(ErrorKind::Cancelled, "Cancelled")
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::TimedOut => Self::new(ErrorKind::Timeout, e.to_string()),
+            _ => Self::io(e.to_string()),
+        }
+    }
+}
+
+// The URL is left out, it can carry credentials in its query and callers know which URL they asked for
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        let e = e.without_url();
+        if e.is_timeout() {
+            Self::new(ErrorKind::Timeout, e.to_string())
+        } else if let Some(status) = e.status() {
+            Self::http_status(status.as_u16())
+        } else if e.is_decode() {
+            Self::parse(e.to_string())
+        } else {
+            Self::network(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::parse(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for AppError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => e.into(),
+            e => Self::parse(format!("Invalid archive: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod serialize {
+        use super::*;
+
+        #[test]
+        fn flattens_kind_into_error() {
+            let error = AppError::new(ErrorKind::VideoUnavailable, "Private video");
+
+            assert_eq!(
+                serde_json::to_value(&error).unwrap(),
+                serde_json::json!({
+                    "kind": "video_unavailable",
+                    "message": "Private video",
+                })
+            );
+        }
+
+        #[test]
+        fn includes_status_code_and_details() {
+            let error = AppError::http_status(404).with_details("Not Found");
+
+            assert_eq!(
+                serde_json::to_value(&error).unwrap(),
+                serde_json::json!({
+                    "kind": "http_status",
+                    "code": 404,
+                    "message": "HTTP error: 404",
+                    "details": "Not Found",
+                })
+            );
+        }
+    }
+
+    mod from_io_error {
+        use super::*;
+
+        #[test]
+        fn maps_timeouts() {
+            let error: AppError = std::io::Error::from(std::io::ErrorKind::TimedOut).into();
+
+            assert_eq!(error.kind, ErrorKind::Timeout);
+        }
+
+        #[test]
+        fn maps_other_errors_to_io() {
+            let error: AppError = std::io::Error::from(std::io::ErrorKind::NotFound).into();
+
+            assert_eq!(error.kind, ErrorKind::Io);
+        }
+    }
+}
@@ -0,0 +1,719 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tauri::command;
+
+use super::client::HttpClients;
+use crate::error::AppResult;
+
+// Private HTTP cache for http_fetch, kept in memory and mirrored to disk so it survives restarts.
+// Entries are keyed by plugin and URL, and only GET responses are stored. Responses to requests
+// that carry credentials, an Authorization header or cookies, are only stored when marked
+// Cache-Control: public. Freshness comes from Cache-Control max-age or Expires; stale entries with
+// an ETag or Last-Modified are revalidated with a conditional request.
+
+const CACHE_DIR_NAME: &str = "http-cache";
+const MAX_MEMORY_BYTES: usize = 32 * 1024 * 1024;
+const MAX_DISK_BYTES: u64 = 256 * 1024 * 1024;
+// Larger responses are media rather than metadata and are not worth keeping
+const MAX_ENTRY_BYTES: usize = 8 * 1024 * 1024;
+
+const CACHEABLE_STATUSES: &[u16] = &[200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// Mirrors the `cache` option of `fetch`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheMode {
+    // Use fresh entries, revalidate stale ones
+    #[default]
+    Default,
+    // Neither read nor write the cache
+    NoStore,
+    // Always go to the network, then store the response
+    Reload,
+    // Always revalidate, even fresh entries
+    NoCache,
+    // Use any entry, however stale, and only go to the network without one
+    ForceCache,
+    // Use any entry, and fail without one
+    OnlyIfCached,
+}
+
+/// Where an `http_fetch` response came from.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheStatus {
+    Hit,
+    // The server answered a conditional request with 304 Not Modified
+    Revalidated,
+    Miss,
+}
+
+pub fn cache_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(CACHE_DIR_NAME)
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn parse_http_date(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+fn header_value<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn cache_control(headers: &HashMap<String, String>) -> HashMap<String, Option<String>> {
+    header_value(headers, "cache-control")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| match directive.split_once('=') {
+            Some((name, value)) => (
+                name.trim().to_lowercase(),
+                Some(value.trim().trim_matches('"').to_string()),
+            ),
+            None => (directive.to_lowercase(), None),
+        })
+        .collect()
+}
+
+fn vary_names(headers: &HashMap<String, String>) -> Vec<String> {
+    header_value(headers, "vary")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Unix timestamp until which a response can be used without revalidation,
+/// or None if it must always be revalidated.
+fn fresh_until(headers: &HashMap<String, String>, now: i64) -> Option<i64> {
+    let directives = cache_control(headers);
+    if directives.contains_key("no-cache") {
+        return None;
+    }
+
+    let age = header_value(headers, "age")
+        .and_then(|age| age.trim().parse::<i64>().ok())
+        .unwrap_or_default();
+    if let Some(max_age) = directives
+        .get("max-age")
+        .and_then(|value| value.as_deref()?.parse::<i64>().ok())
+    {
+        return Some(now - age + max_age);
+    }
+
+    let expires = header_value(headers, "expires")?;
+    // Invalid dates such as "0" mean already expired
+    let Some(expires) = parse_http_date(expires) else {
+        return Some(0);
+    };
+    let date = header_value(headers, "date")
+        .and_then(parse_http_date)
+        .unwrap_or(now);
+    Some(now + (expires - date))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    pub url: String,
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    // Values of the request headers named in Vary, lowercase names
+    vary: HashMap<String, String>,
+    stored_at: i64,
+    fresh_until: Option<i64>,
+    // Kept in a separate file next to the metadata
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl CacheEntry {
+    pub fn is_fresh(&self) -> bool {
+        self.fresh_until.is_some_and(|until| until > now())
+    }
+
+    /// Headers that turn a request into a conditional one for this entry.
+    pub fn validators(&self) -> Vec<(&'static str, String)> {
+        let mut validators = Vec::new();
+        if let Some(etag) = header_value(&self.headers, "etag") {
+            validators.push(("if-none-match", etag.to_string()));
+        }
+        if let Some(last_modified) = header_value(&self.headers, "last-modified") {
+            validators.push(("if-modified-since", last_modified.to_string()));
+        }
+        validators
+    }
+
+    fn matches(&self, request_headers: &HashMap<String, String>) -> bool {
+        self.vary.iter().all(|(name, value)| {
+            header_value(request_headers, name).unwrap_or_default() == value.as_str()
+        })
+    }
+}
+
+#[derive(Default)]
+struct MemoryCache {
+    entries: HashMap<String, Arc<CacheEntry>>,
+    // Insertion order, oldest first, for eviction
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl MemoryCache {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.body.len();
+            self.order.retain(|k| k != key);
+        }
+    }
+
+    fn insert(&mut self, key: String, entry: Arc<CacheEntry>) {
+        self.remove(&key);
+        self.bytes += entry.body.len();
+        self.order.push_back(key.clone());
+        self.entries.insert(key, entry);
+        while self.bytes > MAX_MEMORY_BYTES {
+            let Some(oldest) = self.order.front().cloned() else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+}
+
+// Without a dir, entries only live in memory
+#[derive(Default)]
+pub struct HttpCache {
+    dir: Option<PathBuf>,
+    memory: Mutex<MemoryCache>,
+    // Bytes used on disk, counted on first write
+    disk_bytes: Mutex<Option<u64>>,
+}
+
+impl HttpCache {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            ..Self::default()
+        }
+    }
+
+    pub fn key(plugin_id: Option<&str>, url: &str) -> String {
+        let mut hasher = Sha256::new();
+        match plugin_id {
+            Some(id) => {
+                hasher.update([1]);
+                hasher.update(id);
+            }
+            None => hasher.update([0]),
+        }
+        hasher.update([0]);
+        hasher.update(url);
+        hex::encode(hasher.finalize())
+    }
+
+    /// Returns the entry for `key` if it was stored for a request with the same Vary headers.
+    /// Entries not in memory are read from disk on a blocking task.
+    pub async fn get(
+        &self,
+        key: &str,
+        request_headers: &HashMap<String, String>,
+    ) -> Option<Arc<CacheEntry>> {
+        let in_memory = self
+            .memory
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .get(key)
+            .cloned();
+        let entry = match in_memory {
+            Some(entry) => entry,
+            None => {
+                let (dir, disk_key) = (self.dir.clone()?, key.to_string());
+                let entry = tokio::task::spawn_blocking(move || read_disk(&dir, &disk_key))
+                    .await
+                    .ok()??;
+                let mut memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+                // Whatever was stored while the disk was read is newer
+                match memory.entries.get(key) {
+                    Some(entry) => entry.clone(),
+                    None => {
+                        let entry = Arc::new(entry);
+                        memory.insert(key.to_string(), entry.clone());
+                        entry
+                    }
+                }
+            }
+        };
+        entry.matches(request_headers).then_some(entry)
+    }
+
+    /// Stores a response if its status and headers allow it. Returns whether it was stored.
+    pub fn store(
+        &self,
+        key: &str,
+        url: &str,
+        request_headers: &HashMap<String, String>,
+        status: u16,
+        headers: HashMap<String, String>,
+        body: &[u8],
+    ) -> bool {
+        let vary = vary_names(&headers);
+        let now = now();
+        let fresh_until = fresh_until(&headers, now);
+        let entry = CacheEntry {
+            url: url.to_string(),
+            status,
+            vary: vary
+                .iter()
+                .map(|name| {
+                    let value = header_value(request_headers, name).unwrap_or_default();
+                    (name.clone(), value.to_string())
+                })
+                .collect(),
+            stored_at: now,
+            fresh_until,
+            body: body.to_vec(),
+            headers,
+        };
+
+        let directives = cache_control(&entry.headers);
+        let credentialed = ["authorization", "cookie"]
+            .iter()
+            .any(|name| header_value(request_headers, name).is_some());
+        let storable = CACHEABLE_STATUSES.contains(&status)
+            && body.len() <= MAX_ENTRY_BYTES
+            && !directives.contains_key("no-store")
+            && (!credentialed || directives.contains_key("public"))
+            && !vary.iter().any(|name| name == "*")
+            && (entry.is_fresh() || !entry.validators().is_empty());
+        if !storable {
+            self.remove(key);
+            return false;
+        }
+
+        self.insert(key, entry);
+        true
+    }
+
+    /// Refreshes an entry after the server answered 304 Not Modified with `headers`.
+    pub fn revalidated(
+        &self,
+        key: &str,
+        entry: &CacheEntry,
+        headers: &HashMap<String, String>,
+    ) -> Arc<CacheEntry> {
+        let mut entry = entry.clone();
+        for (name, value) in headers {
+            // A 304 carries no body, so its framing headers don't describe the cached one
+            if !matches!(name.as_str(), "content-length" | "transfer-encoding") {
+                entry.headers.insert(name.clone(), value.clone());
+            }
+        }
+        let now = now();
+        entry.stored_at = now;
+        entry.fresh_until = fresh_until(&entry.headers, now);
+        self.insert(key, entry)
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.memory
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(key);
+        if let Some(dir) = &self.dir {
+            let _ = fs::remove_file(dir.join(format!("{}.json", key)));
+            let _ = fs::remove_file(dir.join(format!("{}.body", key)));
+        }
+    }
+
+    pub fn clear(&self) -> AppResult<()> {
+        *self.memory.lock().unwrap_or_else(|e| e.into_inner()) = MemoryCache::default();
+        if let Some(dir) = &self.dir {
+            match fs::remove_dir_all(dir) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        *self.disk_bytes.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    fn insert(&self, key: &str, entry: CacheEntry) -> Arc<CacheEntry> {
+        if let Err(e) = self.write_disk(key, &entry) {
+            warn!(target: "http", "Failed to write cache entry for {}: {}", entry.url, e);
+        }
+        let entry = Arc::new(entry);
+        self.memory
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string(), entry.clone());
+        entry
+    }
+
+    // The body is written before the metadata, so an entry is only found once both exist
+    fn write_disk(&self, key: &str, entry: &CacheEntry) -> std::io::Result<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let metadata = serde_json::to_vec(entry)?;
+        for (extension, contents) in [("body", entry.body.as_slice()), ("json", &metadata)] {
+            let path = dir.join(format!("{}.{}", key, extension));
+            let temp_path = path.with_extension(format!("{}.tmp", extension));
+            fs::write(&temp_path, contents)?;
+            fs::rename(&temp_path, &path)?;
+        }
+
+        let mut disk_bytes = self.disk_bytes.lock().unwrap_or_else(|e| e.into_inner());
+        let used = match *disk_bytes {
+            Some(used) => used + (entry.body.len() + metadata.len()) as u64,
+            None => disk_usage(dir)?.values().map(|(_, size)| size).sum(),
+        };
+        *disk_bytes = Some(if used > MAX_DISK_BYTES {
+            prune(dir, MAX_DISK_BYTES * 3 / 4)?
+        } else {
+            used
+        });
+        Ok(())
+    }
+}
+
+fn read_disk(dir: &Path, key: &str) -> Option<CacheEntry> {
+    let metadata = fs::read(dir.join(format!("{}.json", key))).ok()?;
+    let mut entry: CacheEntry = serde_json::from_slice(&metadata).ok()?;
+    entry.body = fs::read(dir.join(format!("{}.body", key))).ok()?;
+    Some(entry)
+}
+
+// Last modification time and total size of each entry's files, by key
+fn disk_usage(dir: &Path) -> std::io::Result<HashMap<String, (SystemTime, u64)>> {
+    let mut usage: HashMap<String, (SystemTime, u64)> = HashMap::new();
+    for file in fs::read_dir(dir)? {
+        let file = file?;
+        let metadata = file.metadata()?;
+        let Some(key) = file
+            .file_name()
+            .to_str()
+            .and_then(|name| name.split('.').next())
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        let modified = metadata.modified()?;
+        let (latest, size) = usage.entry(key).or_insert((modified, 0));
+        *latest = (*latest).max(modified);
+        *size += metadata.len();
+    }
+    Ok(usage)
+}
+
+// Removes the least recently written entries until at most `target` bytes are used
+fn prune(dir: &Path, target: u64) -> std::io::Result<u64> {
+    let mut entries: Vec<_> = disk_usage(dir)?.into_iter().collect();
+    entries.sort_by_key(|(_, (modified, _))| *modified);
+    let mut used: u64 = entries.iter().map(|(_, (_, size))| size).sum();
+    debug!(target: "http", "Pruning HTTP cache from {} bytes", used);
+    for (key, (_, size)) in entries {
+        if used <= target {
+            break;
+        }
+        for extension in ["json", "body"] {
+            let _ = fs::remove_file(dir.join(format!("{}.{}", key, extension)));
+        }
+        used -= size;
+    }
+    Ok(used)
+}
+
+#[command]
+pub async fn http_cache_clear(clients: tauri::State<'_, HttpClients>) -> AppResult<()> {
+    debug!(target: "http", "Clearing HTTP cache");
+    clients.cache().clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    mod fresh_until {
+        use super::*;
+
+        #[test]
+        fn uses_max_age_minus_age() {
+            let headers = headers(&[("cache-control", "public, max-age=60"), ("age", "10")]);
+
+            assert_eq!(fresh_until(&headers, 1000), Some(1050));
+        }
+
+        #[test]
+        fn uses_expires_relative_to_date() {
+            let headers = headers(&[
+                ("date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                ("expires", "Sun, 06 Nov 1994 08:50:37 GMT"),
+            ]);
+
+            assert_eq!(fresh_until(&headers, 1000), Some(1060));
+        }
+
+        #[test]
+        fn treats_invalid_expires_as_expired() {
+            let headers = headers(&[("expires", "0")]);
+
+            assert_eq!(fresh_until(&headers, 1000), Some(0));
+        }
+
+        #[test]
+        fn requires_revalidation_for_no_cache() {
+            let headers = headers(&[("cache-control", "no-cache, max-age=60")]);
+
+            assert_eq!(fresh_until(&headers, 1000), None);
+        }
+    }
+
+    mod store {
+        use super::*;
+
+        const URL: &str = "https://musicbrainz.org/ws/2/artist/1";
+
+        #[tokio::test]
+        async fn keeps_fresh_responses() {
+            let cache = HttpCache::default();
+            let key = HttpCache::key(None, URL);
+
+            let stored = cache.store(
+                &key,
+                URL,
+                &HashMap::new(),
+                200,
+                headers(&[("cache-control", "max-age=60")]),
+                b"artist",
+            );
+
+            let entry = cache.get(&key, &HashMap::new()).await.unwrap();
+            assert!(stored);
+            assert!(entry.is_fresh());
+            assert_eq!(entry.body, b"artist");
+        }
+
+        #[tokio::test]
+        async fn keeps_stale_responses_with_validators() {
+            let cache = HttpCache::default();
+            let key = HttpCache::key(None, URL);
+
+            cache.store(
+                &key,
+                URL,
+                &HashMap::new(),
+                200,
+                headers(&[("etag", "\"v1\"")]),
+                b"artist",
+            );
+
+            let entry = cache.get(&key, &HashMap::new()).await.unwrap();
+            assert!(!entry.is_fresh());
+            assert_eq!(
+                entry.validators(),
+                vec![("if-none-match", "\"v1\"".to_string())]
+            );
+        }
+
+        #[tokio::test]
+        async fn skips_uncacheable_responses() {
+            let cache = HttpCache::default();
+            let key = HttpCache::key(None, URL);
+
+            let no_store = cache.store(
+                &key,
+                URL,
+                &HashMap::new(),
+                200,
+                headers(&[("cache-control", "no-store, max-age=60")]),
+                b"",
+            );
+            let no_validators = cache.store(&key, URL, &HashMap::new(), 200, HashMap::new(), b"");
+            let server_error = cache.store(
+                &key,
+                URL,
+                &HashMap::new(),
+                500,
+                headers(&[("cache-control", "max-age=60")]),
+                b"",
+            );
+
+            assert!(!no_store && !no_validators && !server_error);
+            assert!(cache.get(&key, &HashMap::new()).await.is_none());
+        }
+
+        #[tokio::test]
+        async fn matches_vary_headers() {
+            let cache = HttpCache::default();
+            let key = HttpCache::key(None, URL);
+
+            cache.store(
+                &key,
+                URL,
+                &headers(&[("Accept-Language", "en")]),
+                200,
+                headers(&[("cache-control", "max-age=60"), ("vary", "Accept-Language")]),
+                b"artist",
+            );
+
+            assert!(cache
+                .get(&key, &headers(&[("accept-language", "en")]))
+                .await
+                .is_some());
+            assert!(cache
+                .get(&key, &headers(&[("accept-language", "de")]))
+                .await
+                .is_none());
+        }
+
+        #[tokio::test]
+        async fn skips_private_responses_to_credentialed_requests() {
+            let cache = HttpCache::default();
+            let key = HttpCache::key(None, URL);
+            let fresh = headers(&[("cache-control", "max-age=60")]);
+
+            let authorized = cache.store(
+                &key,
+                URL,
+                &headers(&[("Authorization", "Bearer secret")]),
+                200,
+                fresh.clone(),
+                b"me",
+            );
+            let with_cookies = cache.store(
+                &key,
+                URL,
+                &headers(&[("cookie", "session=abc")]),
+                200,
+                fresh,
+                b"me",
+            );
+            let public = cache.store(
+                &key,
+                URL,
+                &headers(&[("cookie", "session=abc")]),
+                200,
+                headers(&[("cache-control", "public, max-age=60")]),
+                b"artist",
+            );
+
+            assert!(!authorized && !with_cookies && public);
+            assert_eq!(
+                cache.get(&key, &HashMap::new()).await.unwrap().body,
+                b"artist"
+            );
+        }
+
+        #[test]
+        fn keys_entries_by_plugin() {
+            assert_ne!(
+                HttpCache::key(Some("lastfm"), URL),
+                HttpCache::key(Some("discogs"), URL)
+            );
+            assert_ne!(HttpCache::key(None, URL), HttpCache::key(Some(""), URL));
+        }
+
+        #[tokio::test]
+        async fn persists_to_disk() {
+            let temp = tempdir().unwrap();
+            let key = HttpCache::key(Some("lastfm"), URL);
+            HttpCache::new(Some(temp.path().to_path_buf())).store(
+                &key,
+                URL,
+                &HashMap::new(),
+                200,
+                headers(&[("cache-control", "max-age=60")]),
+                &[0, 159, 146, 150],
+            );
+
+            let reopened = HttpCache::new(Some(temp.path().to_path_buf()));
+            let entry = reopened.get(&key, &HashMap::new()).await.unwrap();
+
+            assert_eq!(entry.url, URL);
+            assert_eq!(entry.body, vec![0, 159, 146, 150]);
+        }
+    }
+
+    mod revalidated {
+        use super::*;
+
+        #[tokio::test]
+        async fn refreshes_headers_and_freshness() {
+            let cache = HttpCache::default();
+            let url = "https://example.com/album";
+            let key = HttpCache::key(None, url);
+            cache.store(
+                &key,
+                url,
+                &HashMap::new(),
+                200,
+                headers(&[("etag", "\"v1\""), ("content-length", "5")]),
+                b"album",
+            );
+            let stale = cache.get(&key, &HashMap::new()).await.unwrap();
+
+            let entry = cache.revalidated(
+                &key,
+                &stale,
+                &headers(&[("cache-control", "max-age=60"), ("content-length", "0")]),
+            );
+
+            assert!(entry.is_fresh());
+            assert_eq!(entry.headers["content-length"], "5");
+            assert_eq!(entry.body, b"album");
+        }
+    }
+
+    mod clear {
+        use super::*;
+
+        #[tokio::test]
+        async fn removes_memory_and_disk_entries() {
+            let temp = tempdir().unwrap();
+            let dir = temp.path().join(CACHE_DIR_NAME);
+            let cache = HttpCache::new(Some(dir.clone()));
+            let key = HttpCache::key(None, "https://example.com");
+            cache.store(
+                &key,
+                "https://example.com",
+                &HashMap::new(),
+                200,
+                headers(&[("cache-control", "max-age=60")]),
+                b"home",
+            );
+
+            cache.clear().unwrap();
+
+            assert!(cache.get(&key, &HashMap::new()).await.is_none());
+            assert!(!dir.exists());
+        }
+    }
+}
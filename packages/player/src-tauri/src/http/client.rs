@@ -0,0 +1,247 @@
+use log::{debug, error};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+use super::cache::{cache_dir, HttpCache};
+use super::cookies::{jar_path, CookieJar};
+use super::inspector::Inspector;
+use super::policy::{check_proxied_redirect, NetworkPolicyStore, PolicyResolver, POLICY_FILE};
+use super::rate_limit::RateLimiter;
+use super::redaction::{RedactionStore, REDACTION_FILE};
+use crate::error::{AppError, AppResult};
+use crate::network::{NetworkSettings, NetworkStore, NETWORK_FILE};
+
+// Long-lived reqwest clients shared by http_fetch calls so connections and TLS sessions are reused.
+// Clients are keyed by plugin and redirect mode. Each plugin gets its own cookie jar, shared by all of
+// its clients; requests without a plugin ID share an app-wide jar. Jars persist in `cookies_dir`.
+// The response cache, rate limiter, network policy, log redaction rules and inspector live here too, so
+// every request path shares them.
+// Clients only resolve and follow redirects to addresses the policy allows.
+// The network settings (proxy and TLS options) apply to every client; changing them drops the pool.
+
+const COOKIES_DIR_NAME: &str = "cookies";
+
+const MAX_REDIRECTS: usize = 10;
+
+/// Mirrors the `redirect` option of `fetch`.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RedirectMode {
+    #[default]
+    Follow,
+    // Return the 3xx response as is
+    Manual,
+    // Fail the request when the server redirects
+    Error,
+}
+
+impl RedirectMode {
+    // Behind a proxy the target is resolved by the proxy, which the redirect callback can't wait
+    // for, so only redirects the policy can check without a lookup are followed
+    fn policy(
+        self,
+        network: Arc<NetworkPolicyStore>,
+        plugin_id: Option<String>,
+        proxied: bool,
+    ) -> Policy {
+        match self {
+            RedirectMode::Follow => Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    return attempt.error("Too many redirects");
+                }
+                let checked = network
+                    .check(plugin_id.as_deref(), attempt.url())
+                    .and_then(|_| match proxied {
+                        true => check_proxied_redirect(attempt.url(), attempt.previous()),
+                        false => Ok(()),
+                    });
+                match checked {
+                    Ok(()) => attempt.follow(),
+                    Err(e) => attempt.error(e.message),
+                }
+            }),
+            RedirectMode::Manual => Policy::none(),
+            RedirectMode::Error => {
+                Policy::custom(|attempt| attempt.error("Redirects are not allowed"))
+            }
+        }
+    }
+}
+
+type ClientKey = (Option<String>, RedirectMode);
+
+// Without a cookies dir, jars only live in memory
+#[derive(Default)]
+pub struct HttpClients {
+    cookies_dir: Option<PathBuf>,
+    clients: Mutex<HashMap<ClientKey, Client>>,
+    jars: Mutex<HashMap<Option<String>, Arc<CookieJar>>>,
+    rate_limiter: RateLimiter,
+    cache: HttpCache,
+    policy: Arc<NetworkPolicyStore>,
+    redaction: RedactionStore,
+    inspector: Inspector,
+    network: NetworkStore,
+}
+
+impl HttpClients {
+    pub fn new(data_dir: Option<PathBuf>) -> Self {
+        Self {
+            cookies_dir: data_dir.as_ref().map(|dir| dir.join(COOKIES_DIR_NAME)),
+            cache: HttpCache::new(data_dir.as_deref().map(cache_dir)),
+            policy: Arc::new(NetworkPolicyStore::open(
+                data_dir.as_ref().map(|dir| dir.join(POLICY_FILE)),
+            )),
+            redaction: RedactionStore::open(data_dir.as_ref().map(|dir| dir.join(REDACTION_FILE))),
+            network: NetworkStore::open(data_dir.as_ref().map(|dir| dir.join(NETWORK_FILE))),
+            ..Self::default()
+        }
+    }
+
+    pub fn network(&self) -> &NetworkStore {
+        &self.network
+    }
+
+    /// Saves `settings` and drops the pooled clients so new requests pick them up.
+    /// Cookie jars are kept.
+    pub fn set_network(&self, settings: NetworkSettings) -> AppResult<()> {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        self.network.set(settings)?;
+        clients.clear();
+        Ok(())
+    }
+
+    pub fn policy(&self) -> &NetworkPolicyStore {
+        &self.policy
+    }
+
+    pub fn redaction(&self) -> &RedactionStore {
+        &self.redaction
+    }
+
+    pub fn inspector(&self) -> &Inspector {
+        &self.inspector
+    }
+
+    pub fn cache(&self) -> &HttpCache {
+        &self.cache
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Cookie jar for `plugin_id`, or the app-wide jar when there is none.
+    pub fn cookie_jar(&self, plugin_id: Option<&str>) -> AppResult<Arc<CookieJar>> {
+        let mut jars = self.jars.lock().unwrap_or_else(|e| e.into_inner());
+        let key = plugin_id.map(str::to_string);
+        if let Some(jar) = jars.get(&key) {
+            return Ok(jar.clone());
+        }
+
+        let jar = match &self.cookies_dir {
+            Some(dir) => CookieJar::open(jar_path(dir, plugin_id))?,
+            None => CookieJar::default(),
+        };
+        let jar = Arc::new(jar);
+        jars.insert(key, jar.clone());
+        Ok(jar)
+    }
+
+    /// Returns the pooled client for `plugin_id` and `redirect`, building it on first use.
+    pub fn get(&self, plugin_id: Option<&str>, redirect: RedirectMode) -> AppResult<Client> {
+        let key = (plugin_id.map(str::to_string), redirect);
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        debug!(
+            target: "http",
+            "Creating HTTP client for plugin={:?} redirect={:?}",
+            plugin_id, redirect
+        );
+        let builder = self
+            .network
+            .settings()
+            .client_builder()?
+            .cookie_provider(self.cookie_jar(plugin_id)?);
+        let client = self
+            .with_policy(builder, plugin_id, redirect)
+            .build()
+            .map_err(|e| AppError::unknown(format!("Failed to create HTTP client: {}", e)))?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+
+    /// Refuses `url` unless the policy allows `plugin_id` to reach it. A proxy resolves the host
+    /// itself, bypassing `PolicyResolver`, so then it is resolved here too.
+    pub async fn authorize(&self, plugin_id: Option<&str>, url: &Url) -> AppResult<()> {
+        self.policy.check(plugin_id, url)?;
+        if self.network.settings().proxy_host().is_some() {
+            return self.policy.check_resolved(plugin_id, url).await;
+        }
+        Ok(())
+    }
+
+    /// Makes a client built from `builder` resolve and follow redirects only to addresses the
+    /// policy allows `plugin_id`. Also for clients that aren't pooled here, like the downloader's.
+    pub fn with_policy(
+        &self,
+        builder: ClientBuilder,
+        plugin_id: Option<&str>,
+        redirect: RedirectMode,
+    ) -> ClientBuilder {
+        let proxy_host = self.network.settings().proxy_host();
+        builder
+            .redirect(redirect.policy(
+                self.policy.clone(),
+                plugin_id.map(str::to_string),
+                proxy_host.is_some(),
+            ))
+            .dns_resolver(Arc::new(PolicyResolver {
+                policy: self.policy.clone(),
+                plugin_id: plugin_id.map(str::to_string),
+                proxy_host,
+            }))
+    }
+}
+
+pub fn init_http_clients(app_handle: AppHandle) {
+    let data_dir = match app_handle.path().app_data_dir() {
+        Ok(dir) => Some(dir),
+        Err(e) => {
+            error!(target: "http", "Failed to resolve app data dir, HTTP settings will not persist: {}", e);
+            None
+        }
+    };
+    app_handle.manage(HttpClients::new(data_dir));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod cookie_jar {
+        use super::*;
+
+        #[test]
+        fn is_shared_per_plugin() {
+            let clients = HttpClients::default();
+
+            let first = clients.cookie_jar(Some("lastfm")).unwrap();
+            let second = clients.cookie_jar(Some("lastfm")).unwrap();
+            let other = clients.cookie_jar(Some("discogs")).unwrap();
+            let app = clients.cookie_jar(None).unwrap();
+
+            assert!(Arc::ptr_eq(&first, &second));
+            assert!(!Arc::ptr_eq(&first, &other));
+            assert!(!Arc::ptr_eq(&first, &app));
+        }
+    }
+}
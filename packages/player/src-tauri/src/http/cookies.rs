@@ -0,0 +1,632 @@
+use cookie_store::{Cookie, CookieDomain, CookieExpiration, CookieStore};
+use log::{debug, warn};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{command, AppHandle};
+
+use super::client::HttpClients;
+use crate::caller::{Caller, Callers};
+use crate::dialog;
+use crate::error::{AppError, AppResult, ErrorKind};
+
+// Cookie jars persisted as Netscape cookies.txt files, the format browsers export and yt-dlp reads.
+// Each plugin gets its own file under `cookies/plugins`, requests without a plugin use `cookies/app.txt`.
+// The file is rewritten shortly after a response sets cookies, readable by the current user only.
+
+const APP_JAR_FILE: &str = "app.txt";
+const PLUGIN_JARS_DIR: &str = "plugins";
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+// Cookies set within this long of each other are saved in one write
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+// Plugin IDs are percent-encoded into file names so they can't escape the cookies dir
+const NAMESPACE_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_');
+
+/// File holding the cookies of `plugin_id` inside `cookies_dir`.
+pub fn jar_path(cookies_dir: &Path, plugin_id: Option<&str>) -> PathBuf {
+    match plugin_id {
+        Some(id) => cookies_dir.join(PLUGIN_JARS_DIR).join(format!(
+            "{}.txt",
+            utf8_percent_encode(id, NAMESPACE_ENCODE_SET)
+        )),
+        None => cookies_dir.join(APP_JAR_FILE),
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn flag(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+/// One cookie, as listed to the frontend and as stored in a cookies.txt line.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CookieInfo {
+    pub domain: String,
+    // False for host-only cookies
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    // Unix timestamp, None for session cookies
+    pub expires: Option<i64>,
+    pub name: String,
+    pub value: String,
+}
+
+impl CookieInfo {
+    fn from_cookie(cookie: &Cookie<'_>) -> Option<Self> {
+        let (domain, include_subdomains) = match &cookie.domain {
+            CookieDomain::HostOnly(domain) => (domain.clone(), false),
+            CookieDomain::Suffix(domain) => (domain.clone(), true),
+            CookieDomain::NotPresent | CookieDomain::Empty => return None,
+        };
+        let expires = match &cookie.expires {
+            CookieExpiration::AtUtc(at) => Some(at.unix_timestamp()),
+            CookieExpiration::SessionEnd => None,
+        };
+
+        Some(Self {
+            domain,
+            include_subdomains,
+            path: String::from(&cookie.path),
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+            expires,
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+        })
+    }
+
+    fn to_netscape_line(&self) -> String {
+        let domain = if self.include_subdomains {
+            format!(".{}", self.domain)
+        } else {
+            self.domain.clone()
+        };
+        format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            if self.http_only { HTTP_ONLY_PREFIX } else { "" },
+            domain,
+            flag(self.include_subdomains),
+            self.path,
+            flag(self.secure),
+            self.expires.unwrap_or(0),
+            self.name,
+            self.value
+        )
+    }
+
+    fn parse_netscape_line(line: &str) -> Option<Self> {
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [domain, include_subdomains, path, secure, expires, name, value] = fields[..] else {
+            return None;
+        };
+        let expires: i64 = expires.trim().parse().ok()?;
+
+        Some(Self {
+            domain: domain.trim_start_matches('.').to_lowercase(),
+            include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE")
+                || domain.starts_with('.'),
+            path: path.to_string(),
+            secure: secure.eq_ignore_ascii_case("TRUE"),
+            http_only,
+            expires: (expires > 0).then_some(expires),
+            name: name.to_string(),
+            value: value.trim_end_matches(['\r', '\n']).to_string(),
+        })
+    }
+
+    // Replays the cookie as a Set-Cookie from its own origin so the store applies its usual rules
+    fn insert_into(&self, store: &mut CookieStore) -> bool {
+        let mut set_cookie = format!("{}={}; Path={}", self.name, self.value, self.path);
+        if self.include_subdomains {
+            set_cookie.push_str(&format!("; Domain={}", self.domain));
+        }
+        if self.secure {
+            set_cookie.push_str("; Secure");
+        }
+        if self.http_only {
+            set_cookie.push_str("; HttpOnly");
+        }
+        if let Some(expires) = self.expires {
+            let max_age = expires - now();
+            if max_age <= 0 {
+                return false;
+            }
+            set_cookie.push_str(&format!("; Max-Age={}", max_age));
+        }
+
+        let scheme = if self.secure { "https" } else { "http" };
+        let Ok(url) = Url::parse(&format!("{}://{}{}", scheme, self.domain, self.path)) else {
+            return false;
+        };
+        store.parse(&set_cookie, &url).is_ok()
+    }
+}
+
+fn list_cookies(store: &RwLock<CookieStore>) -> Vec<CookieInfo> {
+    store
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter_unexpired()
+        .filter_map(CookieInfo::from_cookie)
+        .collect()
+}
+
+fn to_netscape(store: &RwLock<CookieStore>) -> String {
+    let mut contents = format!("{}\n\n", NETSCAPE_HEADER);
+    for cookie in list_cookies(store) {
+        contents.push_str(&cookie.to_netscape_line());
+        contents.push('\n');
+    }
+    contents
+}
+
+// The cookies.txt a jar is mirrored to
+struct JarFile {
+    path: PathBuf,
+    // Set while a delayed save is scheduled
+    save_pending: AtomicBool,
+    // Keeps a delayed save and an explicit one from writing the temp file at the same time
+    write_lock: Mutex<()>,
+}
+
+impl JarFile {
+    // Cookies can hold session tokens, so the file is only readable by the current user
+    fn write(&self, store: &RwLock<CookieStore>) -> AppResult<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("txt.tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&temp_path)?;
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        file.write_all(to_netscape(store).as_bytes())?;
+        drop(file);
+        fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// A cookie store for reqwest that mirrors itself to a cookies.txt file.
+#[derive(Default)]
+pub struct CookieJar {
+    file: Option<Arc<JarFile>>,
+    store: Arc<RwLock<CookieStore>>,
+}
+
+impl CookieJar {
+    /// Opens the jar at `path`, loading any cookies already saved there.
+    pub fn open(path: PathBuf) -> AppResult<Self> {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let jar = Self {
+            file: Some(Arc::new(JarFile {
+                path,
+                save_pending: AtomicBool::new(false),
+                write_lock: Mutex::new(()),
+            })),
+            store: Arc::default(),
+        };
+        jar.insert_netscape(&contents);
+        Ok(jar)
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|file| file.path.as_path())
+    }
+
+    pub fn list(&self) -> Vec<CookieInfo> {
+        list_cookies(&self.store)
+    }
+
+    fn insert_netscape(&self, contents: &str) -> usize {
+        let mut store = self.store.write().unwrap_or_else(|e| e.into_inner());
+        contents
+            .lines()
+            .filter_map(CookieInfo::parse_netscape_line)
+            .filter(|cookie| cookie.insert_into(&mut store))
+            .count()
+    }
+
+    /// Merges cookies from a cookies.txt export into the jar. Returns how many were imported.
+    pub fn import(&self, contents: &str) -> AppResult<usize> {
+        let imported = self.insert_netscape(contents);
+        self.save()?;
+        Ok(imported)
+    }
+
+    pub fn clear(&self) -> AppResult<()> {
+        self.store
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.save()
+    }
+
+    pub fn to_netscape(&self) -> String {
+        to_netscape(&self.store)
+    }
+
+    /// Writes the jar to its file. In-memory jars are left alone.
+    pub fn save(&self) -> AppResult<()> {
+        match &self.file {
+            Some(file) => file.write(&self.store),
+            None => Ok(()),
+        }
+    }
+
+    // Saves the jar on a blocking task after SAVE_DELAY, so a burst of responses setting cookies
+    // is written once and the request that set them doesn't wait on the disk
+    fn save_later(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            if let Err(e) = self.save() {
+                warn!(target: "http", "Failed to save cookies to {:?}: {}", file.path, e);
+            }
+            return;
+        };
+        if file.save_pending.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let (file, store) = (file.clone(), self.store.clone());
+        runtime.spawn(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            // Cleared before writing so cookies set during the write schedule another save
+            file.save_pending.store(false, Ordering::SeqCst);
+            let path = file.path.clone();
+            let result = tokio::task::spawn_blocking(move || file.write(&store)).await;
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    warn!(target: "http", "Failed to save cookies to {:?}: {}", path, e)
+                }
+                Err(e) => warn!(target: "http", "Failed to save cookies to {:?}: {}", path, e),
+            }
+        });
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies: Vec<_> = cookie_headers
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| cookie_store::RawCookie::parse(value.to_string()).ok())
+            .collect();
+        if cookies.is_empty() {
+            return;
+        }
+
+        self.store
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .store_response_cookies(cookies.into_iter(), url);
+        self.save_later();
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self
+            .store
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        if header.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+// Which jar a cookie command works on. The app can manage any jar, a plugin only its own.
+fn jar_owner(
+    callers: &Callers,
+    token: Option<&str>,
+    plugin_id: Option<String>,
+) -> AppResult<Option<String>> {
+    match (callers.resolve(token)?, plugin_id) {
+        (Caller::App, plugin_id) => Ok(plugin_id),
+        (Caller::Plugin(caller), None) => Ok(Some(caller)),
+        (Caller::Plugin(caller), Some(plugin_id)) if caller == plugin_id => Ok(Some(caller)),
+        (Caller::Plugin(caller), Some(plugin_id)) => Err(AppError::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "Plugin {} can't access the cookies of {}",
+                caller, plugin_id
+            ),
+        )),
+    }
+}
+
+#[command]
+pub async fn http_cookies_list(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: Option<String>,
+) -> AppResult<Vec<CookieInfo>> {
+    let plugin_id = jar_owner(&callers, token.as_deref(), plugin_id)?;
+    Ok(clients.cookie_jar(plugin_id.as_deref())?.list())
+}
+
+/// Imports a cookies.txt file, e.g. exported from a browser, into the jar.
+/// The file is picked by the user in a dialog. Returns how many cookies were imported, None if
+/// the dialog was cancelled.
+#[command]
+pub async fn http_cookies_import(
+    app: AppHandle,
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: Option<String>,
+) -> AppResult<Option<usize>> {
+    let plugin_id = jar_owner(&callers, token.as_deref(), plugin_id)?;
+    let Some(path) = dialog::pick_file(&app, "Cookies", &["txt"]).await? else {
+        return Ok(None);
+    };
+    let contents = tokio::fs::read_to_string(&path).await?;
+    let jar = clients.cookie_jar(plugin_id.as_deref())?;
+    let imported = tokio::task::spawn_blocking(move || jar.import(&contents))
+        .await
+        .map_err(|e| AppError::unknown(format!("Cookie import task failed: {}", e)))??;
+    debug!(target: "http", "Imported {} cookies from {:?}", imported, path);
+    Ok(Some(imported))
+}
+
+#[command]
+pub async fn http_cookies_clear(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: Option<String>,
+) -> AppResult<()> {
+    let plugin_id = jar_owner(&callers, token.as_deref(), plugin_id)?;
+    clients.cookie_jar(plugin_id.as_deref())?.clear()
+}
+
+/// Path of the jar's cookies.txt, e.g. for yt-dlp's `--cookies`.
+#[command]
+pub async fn http_cookies_file(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: Option<String>,
+) -> AppResult<String> {
+    let plugin_id = jar_owner(&callers, token.as_deref(), plugin_id)?;
+    let jar = clients.cookie_jar(plugin_id.as_deref())?;
+    jar.save()?;
+    jar.path()
+        .map(|path| path.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::unknown("Cookies are not persisted"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::cookie::CookieStore as _;
+    use tempfile::tempdir;
+
+    const EXPORT: &str = "# Netscape HTTP Cookie File
+# This is a generated file! Do not edit.
+
+.youtube.com\tTRUE\t/\tTRUE\t4102444800\tLOGIN_INFO\tabc123
+#HttpOnly_www.example.com\tFALSE\t/account\tFALSE\t0\tsession\txyz
+.expired.com\tTRUE\t/\tFALSE\t1000\told\tgone
+not a cookie line
+";
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    fn request_cookies(jar: &CookieJar, request_url: &str) -> Option<String> {
+        jar.cookies(&url(request_url))
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    mod import {
+        use super::*;
+
+        #[test]
+        fn loads_netscape_export() {
+            let jar = CookieJar::default();
+
+            let imported = jar.import(EXPORT).unwrap();
+
+            assert_eq!(imported, 2);
+            assert_eq!(
+                request_cookies(&jar, "https://music.youtube.com/watch"),
+                Some("LOGIN_INFO=abc123".to_string())
+            );
+            assert_eq!(request_cookies(&jar, "http://music.youtube.com/"), None);
+            assert_eq!(
+                request_cookies(&jar, "http://www.example.com/account/settings"),
+                Some("session=xyz".to_string())
+            );
+            assert_eq!(
+                request_cookies(&jar, "http://sub.www.example.com/account"),
+                None
+            );
+        }
+
+        #[test]
+        fn lists_imported_cookies() {
+            let jar = CookieJar::default();
+            jar.import(EXPORT).unwrap();
+
+            let mut cookies = jar.list();
+            cookies.sort_by(|a, b| a.name.cmp(&b.name));
+
+            assert_eq!(
+                cookies,
+                vec![
+                    CookieInfo {
+                        domain: "youtube.com".to_string(),
+                        include_subdomains: true,
+                        path: "/".to_string(),
+                        secure: true,
+                        http_only: false,
+                        expires: Some(4102444800),
+                        name: "LOGIN_INFO".to_string(),
+                        value: "abc123".to_string(),
+                    },
+                    CookieInfo {
+                        domain: "www.example.com".to_string(),
+                        include_subdomains: false,
+                        path: "/account".to_string(),
+                        secure: false,
+                        http_only: true,
+                        expires: None,
+                        name: "session".to_string(),
+                        value: "xyz".to_string(),
+                    },
+                ]
+            );
+        }
+    }
+
+    mod persistence {
+        use super::*;
+
+        #[tokio::test]
+        async fn saves_response_cookies_and_reloads_them() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join("app.txt");
+            let jar = CookieJar::open(path.clone()).unwrap();
+            let header = HeaderValue::from_static("token=t1; Path=/; Max-Age=3600");
+
+            jar.set_cookies(
+                &mut std::iter::once(&header),
+                &url("https://api.example.com/login"),
+            );
+            assert!(!path.exists());
+            tokio::time::sleep(SAVE_DELAY * 4).await;
+            let reopened = CookieJar::open(path.clone()).unwrap();
+
+            assert!(fs::read_to_string(&path)
+                .unwrap()
+                .starts_with(NETSCAPE_HEADER));
+            assert_eq!(
+                request_cookies(&reopened, "https://api.example.com/me"),
+                Some("token=t1".to_string())
+            );
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn keeps_the_file_private() {
+            use std::os::unix::fs::PermissionsExt;
+            let temp = tempdir().unwrap();
+            let path = temp.path().join("app.txt");
+            fs::write(&path, "").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            let jar = CookieJar::open(path.clone()).unwrap();
+
+            jar.import(EXPORT).unwrap();
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        #[test]
+        fn clear_empties_the_file() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join("app.txt");
+            let jar = CookieJar::open(path.clone()).unwrap();
+            jar.import(EXPORT).unwrap();
+
+            jar.clear().unwrap();
+
+            assert!(CookieJar::open(path).unwrap().list().is_empty());
+        }
+    }
+
+    mod jar_owner {
+        use super::*;
+
+        #[test]
+        fn confines_plugins_to_their_own_jar() {
+            let callers = Callers::default();
+            let app = callers.claim_app_token().unwrap();
+            let plugin = callers.issue_plugin_token(Some(&app), "lastfm").unwrap();
+
+            assert_eq!(
+                jar_owner(&callers, Some(&app), Some("discogs".to_string())).unwrap(),
+                Some("discogs".to_string())
+            );
+            assert_eq!(jar_owner(&callers, Some(&app), None).unwrap(), None);
+            assert_eq!(
+                jar_owner(&callers, Some(&plugin), None).unwrap(),
+                Some("lastfm".to_string())
+            );
+            assert_eq!(
+                jar_owner(&callers, Some(&plugin), Some("discogs".to_string()))
+                    .unwrap_err()
+                    .kind,
+                ErrorKind::PermissionDenied
+            );
+            assert!(jar_owner(&callers, None, None).is_err());
+        }
+    }
+
+    mod jar_path {
+        use super::*;
+
+        #[test]
+        fn keeps_plugin_ids_inside_the_cookies_dir() {
+            let dir = Path::new("/data/cookies");
+
+            assert_eq!(
+                jar_path(dir, Some("../../etc/passwd")),
+                dir.join("plugins/%2E%2E%2F%2E%2E%2Fetc%2Fpasswd.txt")
+            );
+            assert_eq!(
+                jar_path(dir, Some("lastfm-scrobbler")),
+                dir.join("plugins/lastfm-scrobbler.txt")
+            );
+            assert_eq!(jar_path(dir, None), dir.join("app.txt"));
+        }
+    }
+}
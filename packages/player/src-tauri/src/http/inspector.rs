@@ -0,0 +1,374 @@
+use log::debug;
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{command, AppHandle};
+
+use super::cache::CacheStatus;
+use super::client::HttpClients;
+use crate::caller::Callers;
+use crate::dialog;
+use crate::error::{AppError, AppResult};
+
+// Keeps the most recent HTTP exchanges in memory so they can be inspected from the UI or exported
+// as a HAR file. URLs and headers are redacted before they are recorded; bodies are never kept.
+
+pub const MAX_EXCHANGES: usize = 300;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeSummary {
+    pub id: u64,
+    pub plugin_id: Option<String>,
+    pub method: String,
+    pub url: String,
+    // None when no response arrived
+    pub status: Option<u16>,
+    // RFC 3339
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub request_size: Option<u64>,
+    pub response_size: Option<u64>,
+    pub cache: Option<CacheStatus>,
+    pub streamed: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Exchange {
+    #[serde(flatten)]
+    pub summary: ExchangeSummary,
+    pub request_headers: HashMap<String, String>,
+    pub response_headers: HashMap<String, String>,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl Exchange {
+    /// Starts timing an exchange. `url` and `request_headers` must already be redacted.
+    pub fn start(
+        plugin_id: Option<&str>,
+        method: &str,
+        url: String,
+        request_headers: HashMap<String, String>,
+        request_size: Option<u64>,
+    ) -> Self {
+        Self {
+            summary: ExchangeSummary {
+                id: 0,
+                plugin_id: plugin_id.map(str::to_string),
+                method: method.to_string(),
+                url,
+                status: None,
+                started_at: chrono::Utc::now().to_rfc3339(),
+                duration_ms: 0,
+                request_size,
+                response_size: None,
+                cache: None,
+                streamed: false,
+                error: None,
+            },
+            request_headers,
+            response_headers: HashMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Records the response status and its redacted headers.
+    pub fn respond(&mut self, status: u16, headers: HashMap<String, String>) {
+        self.summary.status = Some(status);
+        self.response_headers = headers;
+    }
+
+    /// Stops the clock, recording the body size or the error the exchange ended with.
+    pub fn finish(&mut self, result: Result<u64, &AppError>) {
+        self.summary.duration_ms = self.started.elapsed().as_millis() as u64;
+        match result {
+            Ok(size) => self.summary.response_size = Some(size),
+            Err(e) => self.summary.error = Some(e.to_string()),
+        }
+    }
+
+    fn to_har_entry(&self) -> serde_json::Value {
+        let summary = &self.summary;
+        let query: Vec<serde_json::Value> = Url::parse(&summary.url)
+            .map(|url| {
+                url.query_pairs()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mime_type = self
+            .response_headers
+            .get("content-type")
+            .cloned()
+            .unwrap_or_default();
+        let response_size = summary.response_size.map(|size| size as i64).unwrap_or(-1);
+
+        json!({
+            "startedDateTime": summary.started_at,
+            "time": summary.duration_ms,
+            "request": {
+                "method": summary.method,
+                "url": summary.url,
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": har_headers(&self.request_headers),
+                "queryString": query,
+                "headersSize": -1,
+                "bodySize": summary.request_size.map(|size| size as i64).unwrap_or(-1),
+            },
+            "response": {
+                "status": summary.status.unwrap_or(0),
+                "statusText": "",
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": har_headers(&self.response_headers),
+                "content": {
+                    "size": response_size,
+                    "mimeType": mime_type,
+                },
+                "redirectURL": self.response_headers.get("location").cloned().unwrap_or_default(),
+                "headersSize": -1,
+                "bodySize": response_size,
+            },
+            "cache": {},
+            "timings": {
+                "send": 0,
+                "wait": summary.duration_ms,
+                "receive": 0,
+            },
+            "_pluginId": summary.plugin_id,
+            "_cache": summary.cache,
+            "_streamed": summary.streamed,
+            "_error": summary.error,
+        })
+    }
+}
+
+// Sorted so exports are stable
+fn har_headers(headers: &HashMap<String, String>) -> Vec<serde_json::Value> {
+    let mut headers: Vec<_> = headers.iter().collect();
+    headers.sort();
+    headers
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct Inspector {
+    exchanges: Mutex<VecDeque<Exchange>>,
+    next_id: AtomicU64,
+}
+
+impl Inspector {
+    /// Adds `exchange` under a new ID, dropping the oldest ones beyond `MAX_EXCHANGES`.
+    pub fn record(&self, mut exchange: Exchange) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        exchange.summary.id = id;
+        let mut exchanges = self.exchanges.lock().unwrap_or_else(|e| e.into_inner());
+        exchanges.push_back(exchange);
+        while exchanges.len() > MAX_EXCHANGES {
+            exchanges.pop_front();
+        }
+        id
+    }
+
+    /// Summaries of the recorded exchanges, oldest first.
+    pub fn list(&self) -> Vec<ExchangeSummary> {
+        self.exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|exchange| exchange.summary.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<Exchange> {
+        self.exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|exchange| exchange.summary.id == id)
+            .cloned()
+    }
+
+    pub fn clear(&self) {
+        self.exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// The recorded exchanges as a HAR 1.2 log.
+    pub fn to_har(&self) -> serde_json::Value {
+        let entries: Vec<serde_json::Value> = self
+            .exchanges
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(Exchange::to_har_entry)
+            .collect();
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": "Nuclear",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "pages": [],
+                "entries": entries,
+            }
+        })
+    }
+}
+
+// The inspector commands are reserved for the app, since the exchanges of every plugin are recorded.
+
+#[command]
+pub async fn http_inspector_list(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+) -> AppResult<Vec<ExchangeSummary>> {
+    callers.require_app(token.as_deref())?;
+    Ok(clients.inspector().list())
+}
+
+#[command]
+pub async fn http_inspector_get(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    id: u64,
+) -> AppResult<Option<Exchange>> {
+    callers.require_app(token.as_deref())?;
+    Ok(clients.inspector().get(id))
+}
+
+#[command]
+pub async fn http_inspector_clear(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
+    debug!(target: "http", "Clearing HTTP inspector");
+    clients.inspector().clear();
+    Ok(())
+}
+
+/// Exports the recorded exchanges as a HAR file, saved where the user picks in a dialog.
+/// Returns the path written, None if the dialog was cancelled.
+#[command]
+pub async fn http_inspector_export_har(
+    app: AppHandle,
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+) -> AppResult<Option<String>> {
+    callers.require_app(token.as_deref())?;
+    let Some(path) = dialog::pick_save_path(&app, "nuclear.har", "HAR", &["har"]).await? else {
+        return Ok(None);
+    };
+    let har = serde_json::to_vec_pretty(&clients.inspector().to_har())?;
+    tokio::fs::write(&path, har).await.map_err(|e| {
+        AppError::io(format!("Failed to write HAR file: {}", e))
+            .with_details(path.display().to_string())
+    })?;
+    debug!(target: "http", "Exported HTTP exchanges to {}", path.display());
+    Ok(Some(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(url: &str) -> Exchange {
+        let mut exchange = Exchange::start(
+            Some("lastfm"),
+            "GET",
+            url.to_string(),
+            HashMap::from([("authorization".to_string(), "[REDACTED]".to_string())]),
+            None,
+        );
+        exchange.respond(
+            200,
+            HashMap::from([("content-type".to_string(), "application/json".to_string())]),
+        );
+        exchange.finish(Ok(42));
+        exchange
+    }
+
+    mod record {
+        use super::*;
+
+        #[test]
+        fn keeps_the_most_recent_exchanges() {
+            let inspector = Inspector::default();
+
+            for i in 0..MAX_EXCHANGES + 5 {
+                inspector.record(exchange(&format!("https://example.com/{}", i)));
+            }
+
+            let list = inspector.list();
+            assert_eq!(list.len(), MAX_EXCHANGES);
+            assert_eq!(list[0].id, 6);
+            assert_eq!(list[0].url, "https://example.com/5");
+            assert_eq!(list.last().unwrap().id, (MAX_EXCHANGES + 5) as u64);
+        }
+    }
+
+    mod get {
+        use super::*;
+
+        #[test]
+        fn returns_the_exchange_with_headers() {
+            let inspector = Inspector::default();
+            let id = inspector.record(exchange("https://example.com/"));
+
+            let found = inspector.get(id).unwrap();
+
+            assert_eq!(found.summary.status, Some(200));
+            assert_eq!(found.summary.response_size, Some(42));
+            assert_eq!(found.request_headers["authorization"], "[REDACTED]");
+            assert!(inspector.get(id + 1).is_none());
+        }
+    }
+
+    mod to_har {
+        use super::*;
+
+        #[test]
+        fn builds_a_har_log() {
+            let inspector = Inspector::default();
+            inspector.record(exchange(
+                "https://example.com/search?q=x&api_key=[REDACTED]",
+            ));
+
+            let har = inspector.to_har();
+
+            assert_eq!(har["log"]["version"], "1.2");
+            let entry = &har["log"]["entries"][0];
+            assert_eq!(entry["request"]["method"], "GET");
+            assert_eq!(
+                entry["request"]["queryString"][1],
+                json!({ "name": "api_key", "value": "[REDACTED]" })
+            );
+            assert_eq!(
+                entry["request"]["headers"][0],
+                json!({ "name": "authorization", "value": "[REDACTED]" })
+            );
+            assert_eq!(entry["response"]["status"], 200);
+            assert_eq!(entry["response"]["content"]["size"], 42);
+            assert_eq!(entry["response"]["content"]["mimeType"], "application/json");
+            assert_eq!(entry["_pluginId"], "lastfm");
+        }
+    }
+}
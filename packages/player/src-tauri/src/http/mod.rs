@@ -0,0 +1,1568 @@
+pub mod cache;
+pub mod client;
+pub mod cookies;
+pub mod inspector;
+pub mod policy;
+pub mod rate_limit;
+pub mod redaction;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use encoding_rs::{Encoding, UTF_8};
+use futures::StreamExt;
+use log::{debug, error, warn};
+use once_cell::sync::Lazy;
+use reqwest::cookie::CookieStore as _;
+use reqwest::{
+    header::HeaderMap, header::HeaderName, header::HeaderValue, header::CONTENT_TYPE,
+    header::USER_AGENT, Client, Method, RequestBuilder, Response, Url,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{command, ipc::Channel, AppHandle, Manager};
+use tokio_util::sync::CancellationToken;
+
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult};
+use cache::{CacheEntry, CacheMode, CacheStatus, HttpCache};
+use client::{HttpClients, RedirectMode};
+use inspector::Exchange;
+use rate_limit::{is_idempotent, is_retryable, retry_delay, worth_retrying, DEFAULT_MAX_RETRIES};
+use redaction::RedactionRules;
+
+/// How a body is represented on the JavaScript side.
+/// `text` and `base64` bodies are strings, `json` bodies are any JSON value.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyEncoding {
+    #[default]
+    Text,
+    Base64,
+    Json,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpRequest {
+    url: String,
+    method: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    body: Option<serde_json::Value>,
+    #[serde(default)]
+    body_encoding: BodyEncoding,
+    #[serde(default)]
+    response_encoding: BodyEncoding,
+    // Selects the plugin's pooled client, cookie jar and network policy. Set from the caller's
+    // token by the commands, never taken from the webview.
+    #[serde(skip)]
+    plugin_id: Option<String>,
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    redirect: RedirectMode,
+    user_agent: Option<String>,
+    // Retries of idempotent requests answered with 429 or 503
+    max_retries: Option<u32>,
+    // Only GET requests made with http_fetch use the cache
+    #[serde(default)]
+    cache: CacheMode,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HttpResponse {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: serde_json::Value,
+    cache: CacheStatus,
+}
+
+/// Messages sent over the channel passed to `http_fetch_stream`, in order:
+/// one `start`, any number of `chunk`s, then either `end` or `error`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(
+    tag = "event",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum HttpStreamEvent {
+    Start {
+        status: u16,
+        headers: HashMap<String, String>,
+    },
+    // Base64 so binary bodies survive the JSON channel
+    Chunk {
+        data: String,
+    },
+    End {
+        bytes: u64,
+    },
+    Error {
+        error: AppError,
+    },
+}
+
+static ACTIVE_STREAMS: Lazy<Mutex<HashMap<String, CancellationToken>>> =
+    Lazy::new(Default::default);
+
+fn encode_request_body(body: &serde_json::Value, encoding: BodyEncoding) -> AppResult<Vec<u8>> {
+    match (encoding, body) {
+        (BodyEncoding::Json, value) => Ok(serde_json::to_vec(value)?),
+        (BodyEncoding::Text, serde_json::Value::String(text)) => Ok(text.clone().into_bytes()),
+        (BodyEncoding::Base64, serde_json::Value::String(encoded)) => STANDARD
+            .decode(encoded)
+            .map_err(|e| AppError::parse(format!("Invalid base64 request body: {}", e))),
+        (encoding, _) => Err(AppError::parse(format!(
+            "Request body must be a string for {:?} encoding",
+            encoding
+        ))),
+    }
+}
+
+// Honours the charset in Content-Type, falling back to UTF-8 like reqwest's Response::text
+fn decode_text(bytes: &[u8], headers: &HashMap<String, String>) -> String {
+    let encoding = headers
+        .get("content-type")
+        .and_then(|content_type| {
+            content_type.split(';').skip(1).find_map(|param| {
+                let (name, value) = param.split_once('=')?;
+                name.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+        })
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+// A JSON response that is empty or doesn't parse, like an HTML error page, comes back as null or
+// as text rather than failing, so the caller still gets the status
+fn decode_response_body(
+    bytes: &[u8],
+    headers: &HashMap<String, String>,
+    encoding: BodyEncoding,
+) -> serde_json::Value {
+    match encoding {
+        BodyEncoding::Text => decode_text(bytes, headers).into(),
+        BodyEncoding::Base64 => STANDARD.encode(bytes).into(),
+        BodyEncoding::Json if bytes.iter().all(u8::is_ascii_whitespace) => serde_json::Value::Null,
+        BodyEncoding::Json => {
+            serde_json::from_slice(bytes).unwrap_or_else(|_| decode_text(bytes, headers).into())
+        }
+    }
+}
+
+fn request_method(request: &HttpRequest) -> Method {
+    request
+        .method
+        .as_ref()
+        .and_then(|m| Method::from_str(m).ok())
+        .unwrap_or(Method::GET)
+}
+
+// Builds the request and logs it with sensitive headers, query params and body keys redacted.
+// Returns the method and redacted URL for logging the outcome.
+fn prepare_request(
+    client: &Client,
+    request: &HttpRequest,
+    redaction: &RedactionRules,
+) -> AppResult<(RequestBuilder, String, String)> {
+    let method = request_method(request);
+
+    let method_str = method.to_string();
+
+    let mut req_builder = client.request(method, &request.url);
+
+    if let Some(timeout_ms) = request.timeout_ms {
+        req_builder = req_builder.timeout(Duration::from_millis(timeout_ms));
+    }
+
+    let mut header_map = HeaderMap::new();
+    if let Some(user_agent) = request.user_agent.as_deref() {
+        let user_agent = HeaderValue::from_str(user_agent)
+            .map_err(|e| AppError::parse(format!("Invalid user agent: {}", e)))?;
+        header_map.insert(USER_AGENT, user_agent);
+    }
+    if let Some(ref headers) = request.headers {
+        for (key, value) in headers {
+            if let (Ok(name), Ok(val)) = (HeaderName::from_str(key), HeaderValue::from_str(value)) {
+                header_map.insert(name, val);
+            }
+        }
+    }
+    if request.body_encoding == BodyEncoding::Json && !header_map.contains_key(CONTENT_TYPE) {
+        header_map.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+    let content_type = header_map
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    req_builder = req_builder.headers(header_map);
+
+    let body = request
+        .body
+        .as_ref()
+        .map(|body| encode_request_body(body, request.body_encoding))
+        .transpose()?;
+
+    let redacted_url = redaction.redact_url(&request.url);
+    let redacted_hdrs = redaction.redact_headers(&request.headers.clone().unwrap_or_default());
+    let body_log = redaction.format_body(body.as_deref(), content_type.as_deref());
+    if let Some(body) = body {
+        req_builder = req_builder.body(body);
+    }
+    debug!(
+        target: "http",
+        "{} {} headers={:?} {}",
+        method_str,
+        redacted_url,
+        redacted_hdrs,
+        body_log
+    );
+
+    Ok((req_builder, method_str, redacted_url))
+}
+
+async fn send_request(
+    req_builder: RequestBuilder,
+    method_str: &str,
+    redacted_url: &str,
+) -> AppResult<Response> {
+    req_builder.send().await.map_err(|e| {
+        let e = e.without_url();
+        error!(target: "http", "{} {} failed: {}", method_str, redacted_url, e);
+        AppError::from(e)
+    })
+}
+
+// Sends the request once the host's rate limit allows it. Idempotent requests answered with
+// 429 or 503 are retried after Retry-After or an exponential backoff; the last response is
+// returned as is once retries run out.
+async fn send_with_retry(
+    clients: &HttpClients,
+    request: &HttpRequest,
+    mut req_builder: RequestBuilder,
+    method_str: &str,
+    redacted_url: &str,
+) -> AppResult<Response> {
+    let plugin_id = request.plugin_id.as_deref();
+    let host = Url::parse(&request.url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    let max_retries = if is_idempotent(&request_method(request)) {
+        request.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    } else {
+        0
+    };
+    let limiter = clients.rate_limiter();
+
+    let mut attempt = 0;
+    loop {
+        limiter.acquire(plugin_id, &host).await;
+        let retry_builder = if attempt < max_retries {
+            req_builder.try_clone()
+        } else {
+            None
+        };
+        let response = send_request(req_builder, method_str, redacted_url).await?;
+        if !is_retryable(response.status()) {
+            return Ok(response);
+        }
+        let delay = retry_delay(attempt, response.headers());
+        limiter.block(plugin_id, &host, delay);
+        let Some(retry_builder) = retry_builder.filter(|_| worth_retrying(delay)) else {
+            return Ok(response);
+        };
+
+        warn!(
+            target: "http",
+            "{} {} -> {}, retrying in {:?}",
+            method_str, redacted_url, response.status().as_u16(), delay
+        );
+        req_builder = retry_builder;
+        attempt += 1;
+    }
+}
+
+fn response_headers(response: &Response) -> HashMap<String, String> {
+    response
+        .headers()
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect()
+}
+
+fn log_response(
+    redaction: &RedactionRules,
+    method_str: &str,
+    redacted_url: &str,
+    status: u16,
+    headers: &HashMap<String, String>,
+    body_log: &str,
+) {
+    let response_hdrs = redaction.redact_headers(headers);
+    match status {
+        500..=599 => {
+            error!(
+                target: "http",
+                "{} {} -> {} headers={:?} {}",
+                method_str, redacted_url, status, response_hdrs, body_log
+            );
+        }
+        400..=499 => {
+            warn!(
+                target: "http",
+                "{} {} -> {} headers={:?} {}",
+                method_str, redacted_url, status, response_hdrs, body_log
+            );
+        }
+        _ => {
+            debug!(
+                target: "http",
+                "{} {} -> {} headers={:?} {}",
+                method_str, redacted_url, status, response_hdrs, body_log
+            );
+        }
+    }
+}
+
+#[command]
+pub async fn http_fetch(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    mut request: HttpRequest,
+) -> AppResult<HttpResponse> {
+    request.plugin_id = caller_plugin_id(&callers, token.as_deref())?;
+    fetch(&clients, &request).await
+}
+
+// Requests are made on behalf of whoever holds the token: the app, or the plugin it was issued to
+fn caller_plugin_id(callers: &Callers, token: Option<&str>) -> AppResult<Option<String>> {
+    Ok(callers.resolve(token)?.plugin_id().map(str::to_string))
+}
+
+// Refuses requests the network policy doesn't allow, before anything is sent or read from the cache.
+async fn authorize(
+    clients: &HttpClients,
+    request: &HttpRequest,
+    redaction: &RedactionRules,
+) -> AppResult<()> {
+    let url =
+        Url::parse(&request.url).map_err(|e| AppError::parse(format!("Invalid URL: {}", e)))?;
+    let checked = clients.authorize(request.plugin_id.as_deref(), &url).await;
+    checked.inspect_err(|e| {
+        warn!(
+            target: "http",
+            "Blocked {} {} for plugin {:?}: {}",
+            request_method(request),
+            redaction.redact_url(&request.url),
+            request.plugin_id,
+            e
+        );
+    })
+}
+
+fn cached_response(
+    request: &HttpRequest,
+    entry: &CacheEntry,
+    cache: CacheStatus,
+    redaction: &RedactionRules,
+) -> (HttpResponse, u64) {
+    debug!(
+        target: "http",
+        "{} {} -> {} [CACHE {:?}] {}",
+        request_method(request),
+        redaction.redact_url(&request.url),
+        entry.status,
+        cache,
+        redaction.format_body(
+            Some(&entry.body),
+            entry.headers.get("content-type").map(String::as_str)
+        )
+    );
+    let response = HttpResponse {
+        status: entry.status,
+        headers: entry.headers.clone(),
+        body: decode_response_body(&entry.body, &entry.headers, request.response_encoding),
+        cache,
+    };
+    (response, entry.body.len() as u64)
+}
+
+// Starts an inspector entry for `request`, redacted with the rules it is sent under
+fn start_exchange(request: &HttpRequest, redaction: &RedactionRules) -> Exchange {
+    let request_size = request
+        .body
+        .as_ref()
+        .and_then(|body| encode_request_body(body, request.body_encoding).ok())
+        .map(|body| body.len() as u64);
+    Exchange::start(
+        request.plugin_id.as_deref(),
+        request_method(request).as_str(),
+        redaction.redact_url(&request.url),
+        redaction.redact_headers(&request.headers.clone().unwrap_or_default()),
+        request_size,
+    )
+}
+
+/// Performs `request` through the cache and records it in the inspector.
+pub async fn fetch(clients: &HttpClients, request: &HttpRequest) -> AppResult<HttpResponse> {
+    let redaction = clients.redaction().rules();
+    let mut exchange = start_exchange(request, &redaction);
+    let result = send_fetch(clients, request, &redaction).await;
+    match &result {
+        Ok((response, size)) => {
+            exchange.respond(response.status, redaction.redact_headers(&response.headers));
+            exchange.summary.cache = Some(response.cache);
+            exchange.finish(Ok(*size));
+        }
+        Err(e) => exchange.finish(Err(e)),
+    }
+    clients.inspector().record(exchange);
+    result.map(|(response, _)| response)
+}
+
+// The request headers as the cache sees them: the caller's, plus the cookies the jar will add,
+// so that responses to requests sent with cookies are recognised and Vary: Cookie matches
+fn cache_request_headers(clients: &HttpClients, request: &HttpRequest) -> HashMap<String, String> {
+    let mut headers = request.headers.clone().unwrap_or_default();
+    let has_cookie = headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case("cookie"));
+    let jar_cookies = Url::parse(&request.url).ok().and_then(|url| {
+        clients
+            .cookie_jar(request.plugin_id.as_deref())
+            .ok()?
+            .cookies(&url)
+    });
+    if let (false, Some(cookies)) = (has_cookie, jar_cookies) {
+        headers.insert(
+            "cookie".to_string(),
+            cookies.to_str().unwrap_or_default().to_string(),
+        );
+    }
+    headers
+}
+
+// Returns the response along with the size of its raw body
+async fn send_fetch(
+    clients: &HttpClients,
+    request: &HttpRequest,
+    redaction: &RedactionRules,
+) -> AppResult<(HttpResponse, u64)> {
+    authorize(clients, request, redaction).await?;
+    let method = request_method(request);
+    let cache = clients.cache();
+    let request_headers = cache_request_headers(clients, request);
+    let cache_key = (method == Method::GET && request.cache != CacheMode::NoStore)
+        .then(|| HttpCache::key(request.plugin_id.as_deref(), &request.url));
+    let cached = match (request.cache, &cache_key) {
+        (CacheMode::Reload, _) | (_, None) => None,
+        (_, Some(key)) => cache.get(key, &request_headers).await,
+    };
+
+    match (request.cache, &cached) {
+        (CacheMode::OnlyIfCached, None) => {
+            return Err(AppError::http_status(504).with_details("Not in cache"));
+        }
+        (CacheMode::ForceCache | CacheMode::OnlyIfCached, Some(entry)) => {
+            return Ok(cached_response(request, entry, CacheStatus::Hit, redaction));
+        }
+        (CacheMode::Default, Some(entry)) if entry.is_fresh() => {
+            return Ok(cached_response(request, entry, CacheStatus::Hit, redaction));
+        }
+        _ => {}
+    }
+
+    let client = clients.get(request.plugin_id.as_deref(), request.redirect)?;
+    let (mut req_builder, method_str, redacted_url) = prepare_request(&client, request, redaction)?;
+
+    // Conditional headers set by the caller are left alone, and so is the 304 they get back
+    let caller_conditional = request_headers.keys().any(|name| {
+        name.eq_ignore_ascii_case("if-none-match") || name.eq_ignore_ascii_case("if-modified-since")
+    });
+    let revalidating = match &cached {
+        Some(entry) if !caller_conditional && !entry.validators().is_empty() => {
+            for (name, value) in entry.validators() {
+                req_builder = req_builder.header(name, value);
+            }
+            true
+        }
+        _ => false,
+    };
+
+    let response =
+        send_with_retry(clients, request, req_builder, &method_str, &redacted_url).await?;
+
+    let status = response.status().as_u16();
+    let headers = response_headers(&response);
+
+    if let (true, Some(key), Some(entry), 304) = (revalidating, &cache_key, &cached, status) {
+        log_response(
+            redaction,
+            &method_str,
+            &redacted_url,
+            status,
+            &headers,
+            "[NOT MODIFIED]",
+        );
+        let entry = cache.revalidated(key, entry, &headers);
+        return Ok(cached_response(
+            request,
+            &entry,
+            CacheStatus::Revalidated,
+            redaction,
+        ));
+    }
+
+    let bytes = response.bytes().await.map_err(|e| {
+        let e = e.without_url();
+        error!(target: "http", "{} {} failed to read body: {}", method_str, redacted_url, e);
+        AppError::from(e)
+    })?;
+
+    log_response(
+        redaction,
+        &method_str,
+        &redacted_url,
+        status,
+        &headers,
+        &redaction.format_body(
+            Some(&bytes),
+            headers.get("content-type").map(String::as_str),
+        ),
+    );
+
+    match &cache_key {
+        // A 304 to the caller's own conditional request says nothing new about the cached entry
+        Some(_) if status == 304 => {}
+        Some(key) => {
+            cache.store(
+                key,
+                &request.url,
+                &request_headers,
+                status,
+                headers.clone(),
+                &bytes,
+            );
+        }
+        // Writes make whatever was cached for the URL outdated
+        None if !method.is_safe() && status < 400 => {
+            cache.remove(&HttpCache::key(request.plugin_id.as_deref(), &request.url));
+        }
+        None => {}
+    }
+
+    let response = HttpResponse {
+        status,
+        body: decode_response_body(&bytes, &headers, request.response_encoding),
+        headers,
+        cache: CacheStatus::Miss,
+    };
+    Ok((response, bytes.len() as u64))
+}
+
+/// Performs `request` and reports the body chunk by chunk as it arrives.
+/// Sends `start` and `chunk` events; the caller reports the outcome. Returns the body size.
+/// The exchange is recorded in the inspector once the stream ends.
+pub async fn stream_request(
+    clients: &HttpClients,
+    request: &HttpRequest,
+    cancel: &CancellationToken,
+    on_event: &mut impl FnMut(HttpStreamEvent),
+) -> AppResult<u64> {
+    let redaction = clients.redaction().rules();
+    let mut exchange = start_exchange(request, &redaction);
+    exchange.summary.streamed = true;
+    let result = send_stream(clients, request, &redaction, cancel, &mut |event| {
+        if let HttpStreamEvent::Start { status, headers } = &event {
+            exchange.respond(*status, redaction.redact_headers(headers));
+        }
+        on_event(event)
+    })
+    .await;
+    exchange.finish(result.as_ref().copied());
+    clients.inspector().record(exchange);
+    result
+}
+
+async fn send_stream(
+    clients: &HttpClients,
+    request: &HttpRequest,
+    redaction: &RedactionRules,
+    cancel: &CancellationToken,
+    on_event: &mut impl FnMut(HttpStreamEvent),
+) -> AppResult<u64> {
+    authorize(clients, request, redaction).await?;
+    let client = clients.get(request.plugin_id.as_deref(), request.redirect)?;
+    let (req_builder, method_str, redacted_url) = prepare_request(&client, request, redaction)?;
+
+    let response = tokio::select! {
+        _ = cancel.cancelled() => return Err(AppError::cancelled()),
+        response = send_with_retry(clients, request, req_builder, &method_str, &redacted_url) => response?,
+    };
+
+    let status = response.status().as_u16();
+    let headers = response_headers(&response);
+    log_response(
+        redaction,
+        &method_str,
+        &redacted_url,
+        status,
+        &headers,
+        "[STREAM]",
+    );
+    on_event(HttpStreamEvent::Start { status, headers });
+
+    let mut stream = response.bytes_stream();
+    let mut bytes = 0u64;
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.cancelled() => {
+                debug!(target: "http", "{} {} cancelled after {} bytes", method_str, redacted_url, bytes);
+                return Err(AppError::cancelled());
+            }
+            chunk = stream.next() => chunk,
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        let chunk = chunk.map_err(|e| {
+            let e = e.without_url();
+            error!(target: "http", "{} {} failed to read body: {}", method_str, redacted_url, e);
+            AppError::from(e)
+        })?;
+        bytes += chunk.len() as u64;
+        on_event(HttpStreamEvent::Chunk {
+            data: STANDARD.encode(&chunk),
+        });
+    }
+
+    debug!(target: "http", "{} {} stream finished [BODY length={}]", method_str, redacted_url, bytes);
+    Ok(bytes)
+}
+
+/// Starts a streaming request and returns its ID straight away.
+/// Events are delivered on `on_event`; pass the ID to `http_cancel` to abort.
+#[command]
+pub async fn http_fetch_stream(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    mut request: HttpRequest,
+    on_event: Channel<HttpStreamEvent>,
+) -> AppResult<String> {
+    request.plugin_id = caller_plugin_id(&callers, token.as_deref())?;
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let cancel = CancellationToken::new();
+    ACTIVE_STREAMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(request_id.clone(), cancel.clone());
+
+    let id = request_id.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut send = |event: HttpStreamEvent| {
+            if let Err(e) = on_event.send(event) {
+                debug!(target: "http", "Failed to send stream event for {}: {}", id, e);
+            }
+        };
+
+        let clients = app_handle.state::<HttpClients>();
+        let result = stream_request(&clients, &request, &cancel, &mut send).await;
+        ACTIVE_STREAMS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&id);
+
+        match result {
+            Ok(bytes) => send(HttpStreamEvent::End { bytes }),
+            Err(error) => send(HttpStreamEvent::Error { error }),
+        }
+    });
+
+    Ok(request_id)
+}
+
+#[command]
+pub fn http_cancel(request_id: String) -> AppResult<()> {
+    match ACTIVE_STREAMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&request_id)
+    {
+        Some(cancel) => {
+            debug!(target: "http", "Cancelling request {}", request_id);
+            cancel.cancel();
+        }
+        None => debug!(target: "http", "No active request with id {}", request_id),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Clients allowed to reach the local test servers. "unknown" declares a host it wasn't
+    // approved for
+    fn local_clients() -> HttpClients {
+        let clients = HttpClients::default();
+        let local = ["127.0.0.1".to_string()];
+        clients
+            .policy()
+            .update(|policy| {
+                policy.private_hosts = local.to_vec();
+                for plugin_id in ["lastfm", "discogs"] {
+                    policy.request_hosts(plugin_id, &local);
+                    policy.approve_hosts(plugin_id, &local);
+                }
+                policy.request_hosts("unknown", &["example.com".to_string()]);
+            })
+            .unwrap();
+        clients
+    }
+
+    mod stream_request {
+        use super::*;
+        use axum::body::Body;
+        use axum::routing::get;
+        use axum::Router;
+        use std::time::Duration;
+
+        const CHUNKS: usize = 4;
+
+        fn payload() -> Vec<u8> {
+            (0..=255u8).cycle().take(CHUNKS * 1024).collect()
+        }
+
+        async fn serve(pacing: Duration) -> String {
+            let stream = move || async move {
+                let chunks: Vec<Vec<u8>> = payload().chunks(1024).map(<[u8]>::to_vec).collect();
+                let stream = futures::stream::iter(chunks).then(move |chunk| async move {
+                    tokio::time::sleep(pacing).await;
+                    Ok::<_, std::io::Error>(chunk)
+                });
+                axum::http::Response::builder()
+                    .header("content-type", "application/octet-stream")
+                    .body(Body::from_stream(stream))
+                    .unwrap()
+            };
+            let router = Router::new().route("/stream", get(stream)).route(
+                "/missing",
+                get(|| async { (axum::http::StatusCode::NOT_FOUND, "gone") }),
+            );
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            format!("http://{}", addr)
+        }
+
+        fn get_request(url: String) -> HttpRequest {
+            HttpRequest {
+                url,
+                ..Default::default()
+            }
+        }
+
+        fn body_of(events: &[HttpStreamEvent]) -> Vec<u8> {
+            events
+                .iter()
+                .filter_map(|event| match event {
+                    HttpStreamEvent::Chunk { data } => Some(STANDARD.decode(data).unwrap()),
+                    _ => None,
+                })
+                .flatten()
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn delivers_binary_body_in_chunks() {
+            let url = serve(Duration::from_millis(10)).await;
+            let clients = local_clients();
+            let mut events = Vec::new();
+
+            let bytes = stream_request(
+                &clients,
+                &get_request(format!("{}/stream", url)),
+                &CancellationToken::new(),
+                &mut |event| events.push(event),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(bytes, payload().len() as u64);
+            let recorded = &clients.inspector().list()[0];
+            assert!(recorded.streamed);
+            assert_eq!(recorded.status, Some(200));
+            assert_eq!(recorded.response_size, Some(bytes));
+            assert!(matches!(
+                &events[0],
+                HttpStreamEvent::Start { status: 200, headers }
+                    if headers["content-type"] == "application/octet-stream"
+            ));
+            assert!(events.len() > 2);
+            assert_eq!(body_of(&events), payload());
+        }
+
+        #[tokio::test]
+        async fn streams_error_statuses_like_fetch() {
+            let url = serve(Duration::ZERO).await;
+            let mut events = Vec::new();
+
+            stream_request(
+                &local_clients(),
+                &get_request(format!("{}/missing", url)),
+                &CancellationToken::new(),
+                &mut |event| events.push(event),
+            )
+            .await
+            .unwrap();
+
+            assert!(matches!(
+                events[0],
+                HttpStreamEvent::Start { status: 404, .. }
+            ));
+            assert_eq!(body_of(&events), b"gone");
+        }
+
+        #[tokio::test]
+        async fn stops_when_cancelled() {
+            let url = serve(Duration::from_millis(200)).await;
+            let cancel = CancellationToken::new();
+            let cancel_after_start = cancel.clone();
+            let mut events = Vec::new();
+
+            let result = stream_request(
+                &local_clients(),
+                &get_request(format!("{}/stream", url)),
+                &cancel,
+                &mut |event| {
+                    if matches!(event, HttpStreamEvent::Start { .. }) {
+                        cancel_after_start.cancel();
+                    }
+                    events.push(event);
+                },
+            )
+            .await;
+
+            assert_eq!(result.unwrap_err().kind, crate::error::ErrorKind::Cancelled);
+            assert!(body_of(&events).len() < payload().len());
+        }
+
+        #[test]
+        fn serializes_events_with_tag() {
+            let event = HttpStreamEvent::End { bytes: 3 };
+
+            assert_eq!(
+                serde_json::to_value(&event).unwrap(),
+                serde_json::json!({ "event": "end", "bytes": 3 })
+            );
+        }
+    }
+
+    mod http_fetch {
+        use super::*;
+        use axum::http::HeaderMap as AxumHeaders;
+        use axum::response::IntoResponse;
+        use axum::routing::{get, post};
+        use axum::Router;
+
+        fn header(headers: &AxumHeaders, name: &str) -> String {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string()
+        }
+
+        async fn serve() -> String {
+            // Rate limited on every other request
+            let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let flaky = move || {
+                let hits = hits.clone();
+                async move {
+                    let hit = hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    if hit % 2 == 0 {
+                        (
+                            axum::http::StatusCode::TOO_MANY_REQUESTS,
+                            [("retry-after", "1")],
+                            format!("hit {}", hit),
+                        )
+                    } else {
+                        (
+                            axum::http::StatusCode::OK,
+                            [("retry-after", "0")],
+                            format!("hit {}", hit),
+                        )
+                    }
+                }
+            };
+            let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let cached = move || {
+                let hits = hits.clone();
+                async move {
+                    let hit = hits.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    ([("cache-control", "max-age=60")], format!("hit {}", hit))
+                }
+            };
+            let etag = |headers: AxumHeaders| async move {
+                if header(&headers, "if-none-match") == "\"v1\"" {
+                    axum::http::StatusCode::NOT_MODIFIED.into_response()
+                } else {
+                    ([("etag", "\"v1\""), ("cache-control", "no-cache")], "album").into_response()
+                }
+            };
+            // Echoes the request body back with the request's content type
+            let echo = |headers: AxumHeaders, body: axum::body::Bytes| async move {
+                let content_type = match header(&headers, "content-type") {
+                    content_type if content_type.is_empty() => "application/octet-stream".into(),
+                    content_type => content_type,
+                };
+                ([("content-type", content_type)], body)
+            };
+            let router = Router::new()
+                .route("/echo", post(echo))
+                .route(
+                    "/login",
+                    get(|| async { [("set-cookie", "session=abc; Path=/")] }),
+                )
+                .route(
+                    "/whoami",
+                    get(|headers: AxumHeaders| async move { header(&headers, "cookie") }),
+                )
+                .route(
+                    "/agent",
+                    get(|headers: AxumHeaders| async move { header(&headers, "user-agent") }),
+                )
+                .route(
+                    "/redirect",
+                    get(|| async { axum::response::Redirect::to("/agent") }),
+                )
+                .route("/flaky", get(flaky.clone()).post(flaky))
+                .route("/cached", get(cached.clone()).post(cached))
+                .route("/etag", get(etag))
+                .route(
+                    "/outage",
+                    get(|| async {
+                        (
+                            axum::http::StatusCode::BAD_GATEWAY,
+                            [("content-type", "text/html")],
+                            "<h1>Bad gateway</h1>",
+                        )
+                    }),
+                )
+                .route(
+                    "/overloaded",
+                    get(|| async {
+                        (
+                            axum::http::StatusCode::SERVICE_UNAVAILABLE,
+                            [("retry-after", "3600")],
+                            "come back later",
+                        )
+                    }),
+                )
+                .route(
+                    "/empty",
+                    get(|| async { axum::http::StatusCode::NO_CONTENT }),
+                )
+                .route(
+                    "/latin1",
+                    get(|| async {
+                        (
+                            [("content-type", "text/plain; charset=ISO-8859-1")],
+                            vec![0x63, 0x61, 0x66, 0xe9],
+                        )
+                    }),
+                )
+                .route(
+                    "/slow",
+                    get(|| async {
+                        tokio::time::sleep(Duration::from_secs(2)).await;
+                        "done"
+                    }),
+                );
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move {
+                axum::serve(listener, router).await.unwrap();
+            });
+            format!("http://{}", addr)
+        }
+
+        fn get_request(url: String) -> HttpRequest {
+            HttpRequest {
+                url,
+                ..Default::default()
+            }
+        }
+
+        fn post_request(
+            url: String,
+            body: serde_json::Value,
+            body_encoding: BodyEncoding,
+            response_encoding: BodyEncoding,
+        ) -> HttpRequest {
+            HttpRequest {
+                url,
+                method: Some("POST".to_string()),
+                body: Some(body),
+                body_encoding,
+                response_encoding,
+                ..Default::default()
+            }
+        }
+
+        #[tokio::test]
+        async fn round_trips_arbitrary_bytes_as_base64() {
+            let url = format!("{}/echo", serve().await);
+            let bytes: Vec<u8> = (0..=255u8).rev().chain(0..=255u8).collect();
+            let encoded = STANDARD.encode(&bytes);
+
+            let response = fetch(
+                &local_clients(),
+                &post_request(
+                    url,
+                    encoded.clone().into(),
+                    BodyEncoding::Base64,
+                    BodyEncoding::Base64,
+                ),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(response.status, 200);
+            assert_eq!(response.body, serde_json::Value::String(encoded));
+        }
+
+        #[tokio::test]
+        async fn sends_and_parses_json() {
+            let url = format!("{}/echo", serve().await);
+            let body = serde_json::json!({ "artist": "Cher", "tracks": [1, 2, 3] });
+
+            let response = fetch(
+                &local_clients(),
+                &post_request(url, body.clone(), BodyEncoding::Json, BodyEncoding::Json),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(response.headers["content-type"], "application/json");
+            assert_eq!(response.body, body);
+        }
+
+        #[tokio::test]
+        async fn defaults_to_text() {
+            let url = format!("{}/echo", serve().await);
+            let request: HttpRequest = serde_json::from_value(serde_json::json!({
+                "url": url,
+                "method": "POST",
+                "body": "hello",
+            }))
+            .unwrap();
+
+            let response = fetch(&local_clients(), &request).await.unwrap();
+
+            assert_eq!(
+                response.body,
+                serde_json::Value::String("hello".to_string())
+            );
+        }
+
+        #[tokio::test]
+        async fn returns_invalid_json_response_as_text() {
+            let url = format!("{}/echo", serve().await);
+
+            let response = fetch(
+                &local_clients(),
+                &post_request(
+                    url,
+                    "not json".into(),
+                    BodyEncoding::Text,
+                    BodyEncoding::Json,
+                ),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(response.status, 200);
+            assert_eq!(response.body, serde_json::json!("not json"));
+        }
+
+        #[tokio::test]
+        async fn keeps_status_of_json_requests_answered_with_errors_or_nothing() {
+            let url = serve().await;
+            let json_request = |path: &str| HttpRequest {
+                url: format!("{}{}", url, path),
+                response_encoding: BodyEncoding::Json,
+                ..Default::default()
+            };
+
+            let outage = fetch(&local_clients(), &json_request("/outage"))
+                .await
+                .unwrap();
+            let empty = fetch(&local_clients(), &json_request("/empty"))
+                .await
+                .unwrap();
+
+            assert_eq!(outage.status, 502);
+            assert_eq!(outage.body, serde_json::json!("<h1>Bad gateway</h1>"));
+            assert_eq!(empty.status, 204);
+            assert_eq!(empty.body, serde_json::Value::Null);
+        }
+
+        #[tokio::test]
+        async fn keeps_cookies_per_plugin() {
+            let url = serve().await;
+            let clients = local_clients();
+            let plugin = |path: &str, plugin_id: &str| HttpRequest {
+                plugin_id: Some(plugin_id.to_string()),
+                ..get_request(format!("{}{}", url, path))
+            };
+
+            fetch(&clients, &plugin("/login", "lastfm")).await.unwrap();
+            let logged_in = fetch(&clients, &plugin("/whoami", "lastfm")).await.unwrap();
+            let other = fetch(&clients, &plugin("/whoami", "discogs"))
+                .await
+                .unwrap();
+
+            assert_eq!(logged_in.body, "session=abc");
+            assert_eq!(other.body, "");
+        }
+
+        #[tokio::test]
+        async fn applies_redirect_mode() {
+            let url = format!("{}/redirect", serve().await);
+            let clients = local_clients();
+            let request = |redirect: RedirectMode| HttpRequest {
+                redirect,
+                ..get_request(url.clone())
+            };
+
+            let followed = fetch(&clients, &request(RedirectMode::Follow))
+                .await
+                .unwrap();
+            let manual = fetch(&clients, &request(RedirectMode::Manual))
+                .await
+                .unwrap();
+            let error = fetch(&clients, &request(RedirectMode::Error)).await;
+
+            assert_eq!(followed.status, 200);
+            assert_eq!(manual.status, 303);
+            assert_eq!(manual.headers["location"], "/agent");
+            assert!(error.is_err());
+        }
+
+        #[tokio::test]
+        async fn sends_user_agent() {
+            let url = serve().await;
+            let request = HttpRequest {
+                user_agent: Some("Nuclear/1.0".to_string()),
+                ..get_request(format!("{}/agent", url))
+            };
+
+            let response = fetch(&local_clients(), &request).await.unwrap();
+
+            assert_eq!(response.body, "Nuclear/1.0");
+        }
+
+        #[tokio::test]
+        async fn rejects_invalid_user_agent() {
+            let url = serve().await;
+            let request = HttpRequest {
+                user_agent: Some("Nuclear\n1.0".to_string()),
+                ..get_request(format!("{}/agent", url))
+            };
+
+            let result = fetch(&local_clients(), &request).await;
+
+            assert_eq!(
+                result.unwrap_err().kind,
+                crate::error::ErrorKind::ParseError
+            );
+        }
+
+        #[tokio::test]
+        async fn times_out() {
+            let url = serve().await;
+            let request = HttpRequest {
+                timeout_ms: Some(100),
+                ..get_request(format!("{}/slow", url))
+            };
+
+            let result = fetch(&local_clients(), &request).await;
+
+            assert_eq!(result.unwrap_err().kind, crate::error::ErrorKind::Timeout);
+        }
+
+        #[tokio::test]
+        async fn retries_after_rate_limit() {
+            let url = serve().await;
+            let started = std::time::Instant::now();
+
+            let response = fetch(&local_clients(), &get_request(format!("{}/flaky", url)))
+                .await
+                .unwrap();
+
+            assert_eq!(response.status, 200);
+            assert_eq!(response.body, "hit 1");
+            assert!(started.elapsed() >= Duration::from_millis(900));
+        }
+
+        #[tokio::test]
+        async fn holds_back_host_for_long_retry_after_without_retrying() {
+            let url = serve().await;
+            let clients = local_clients();
+            let started = std::time::Instant::now();
+
+            let response = fetch(&clients, &get_request(format!("{}/overloaded", url)))
+                .await
+                .unwrap();
+
+            assert_eq!(response.status, 503);
+            assert!(started.elapsed() < Duration::from_secs(5));
+            let states = clients.rate_limiter().states();
+            assert_eq!(states.len(), 1);
+            assert!(states[0].blocked_for_ms > 3_500_000);
+        }
+
+        #[tokio::test]
+        async fn does_not_retry_post() {
+            let url = serve().await;
+
+            let response = fetch(
+                &local_clients(),
+                &post_request(
+                    format!("{}/flaky", url),
+                    "".into(),
+                    BodyEncoding::Text,
+                    BodyEncoding::Text,
+                ),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(response.status, 429);
+            assert_eq!(response.body, "hit 0");
+        }
+
+        #[tokio::test]
+        async fn returns_last_response_when_retries_run_out() {
+            let url = serve().await;
+            let request = HttpRequest {
+                max_retries: Some(0),
+                ..get_request(format!("{}/flaky", url))
+            };
+
+            let response = fetch(&local_clients(), &request).await.unwrap();
+
+            assert_eq!(response.status, 429);
+        }
+
+        #[tokio::test]
+        async fn decodes_text_with_charset() {
+            let url = serve().await;
+
+            let response = fetch(&local_clients(), &get_request(format!("{}/latin1", url)))
+                .await
+                .unwrap();
+
+            assert_eq!(response.body, "café");
+        }
+
+        #[tokio::test]
+        async fn serves_fresh_responses_from_cache() {
+            let url = format!("{}/cached", serve().await);
+            let clients = local_clients();
+
+            let first = fetch(&clients, &get_request(url.clone())).await.unwrap();
+            let second = fetch(&clients, &get_request(url)).await.unwrap();
+
+            assert_eq!(first.cache, CacheStatus::Miss);
+            assert_eq!(second.cache, CacheStatus::Hit);
+            assert_eq!(second.body, "hit 0");
+        }
+
+        #[tokio::test]
+        async fn revalidates_with_etag() {
+            let url = format!("{}/etag", serve().await);
+            let clients = local_clients();
+
+            fetch(&clients, &get_request(url.clone())).await.unwrap();
+            let response = fetch(&clients, &get_request(url)).await.unwrap();
+
+            assert_eq!(response.cache, CacheStatus::Revalidated);
+            assert_eq!(response.status, 200);
+            assert_eq!(response.body, "album");
+        }
+
+        #[tokio::test]
+        async fn keeps_entry_when_the_callers_own_conditional_request_gets_304() {
+            let url = format!("{}/etag", serve().await);
+            let clients = local_clients();
+            fetch(&clients, &get_request(url.clone())).await.unwrap();
+
+            let response = fetch(
+                &clients,
+                &HttpRequest {
+                    headers: Some(HashMap::from([(
+                        "If-None-Match".to_string(),
+                        "\"v1\"".to_string(),
+                    )])),
+                    ..get_request(url.clone())
+                },
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(response.status, 304);
+            assert!(clients
+                .cache()
+                .get(&HttpCache::key(None, &url), &HashMap::new())
+                .await
+                .is_some());
+        }
+
+        #[tokio::test]
+        async fn does_not_cache_responses_to_requests_sent_with_cookies() {
+            let url = serve().await;
+            let clients = local_clients();
+            fetch(&clients, &get_request(format!("{}/login", url)))
+                .await
+                .unwrap();
+
+            let first = fetch(&clients, &get_request(format!("{}/cached", url)))
+                .await
+                .unwrap();
+            let second = fetch(&clients, &get_request(format!("{}/cached", url)))
+                .await
+                .unwrap();
+
+            assert_eq!(first.body, "hit 0");
+            assert_eq!(second.body, "hit 1");
+            assert_eq!(second.cache, CacheStatus::Miss);
+        }
+
+        #[tokio::test]
+        async fn applies_cache_mode() {
+            let url = format!("{}/cached", serve().await);
+            let clients = local_clients();
+            let request = |cache: CacheMode| HttpRequest {
+                cache,
+                ..get_request(url.clone())
+            };
+
+            let not_cached = fetch(&clients, &request(CacheMode::OnlyIfCached)).await;
+            let no_store = fetch(&clients, &request(CacheMode::NoStore)).await.unwrap();
+            let reload = fetch(&clients, &request(CacheMode::Reload)).await.unwrap();
+            let cached = fetch(&clients, &request(CacheMode::OnlyIfCached))
+                .await
+                .unwrap();
+
+            assert_eq!(
+                not_cached.unwrap_err().kind,
+                crate::error::ErrorKind::HttpStatus { code: 504 }
+            );
+            assert_eq!(no_store.body, "hit 0");
+            assert_eq!(reload.cache, CacheStatus::Miss);
+            assert_eq!(cached.body, "hit 1");
+        }
+
+        #[tokio::test]
+        async fn invalidates_cache_on_writes() {
+            let url = format!("{}/cached", serve().await);
+            let clients = local_clients();
+
+            fetch(&clients, &get_request(url.clone())).await.unwrap();
+            fetch(
+                &clients,
+                &post_request(
+                    url.clone(),
+                    "".into(),
+                    BodyEncoding::Text,
+                    BodyEncoding::Text,
+                ),
+            )
+            .await
+            .unwrap();
+            let response = fetch(&clients, &get_request(url)).await.unwrap();
+
+            assert_eq!(response.cache, CacheStatus::Miss);
+            assert_eq!(response.body, "hit 2");
+        }
+
+        #[tokio::test]
+        async fn enforces_network_policy() {
+            let url = format!("{}/agent", serve().await);
+            let clients = local_clients();
+            let request = |plugin_id: &str| HttpRequest {
+                plugin_id: Some(plugin_id.to_string()),
+                ..get_request(url.clone())
+            };
+
+            let approved = fetch(&clients, &request("lastfm")).await;
+            let unapproved = fetch(&clients, &request("unknown")).await;
+            let loopback = fetch(&HttpClients::default(), &get_request(url.clone())).await;
+
+            assert!(approved.is_ok());
+            assert_eq!(
+                unapproved.unwrap_err().kind,
+                crate::error::ErrorKind::PermissionDenied
+            );
+            assert_eq!(
+                loopback.unwrap_err().kind,
+                crate::error::ErrorKind::PermissionDenied
+            );
+        }
+
+        #[tokio::test]
+        async fn goes_through_the_configured_proxy() {
+            let url = serve().await;
+            // Stands in for a forwarding proxy, answering with what it was asked for
+            let proxy =
+                Router::new().fallback(|uri: axum::http::Uri, headers: AxumHeaders| async move {
+                    format!(
+                        "proxied {} {}",
+                        uri,
+                        header(&headers, "proxy-authorization")
+                    )
+                });
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                axum::serve(listener, proxy).await.unwrap();
+            });
+            let clients = local_clients();
+            clients
+                .set_network(crate::network::NetworkSettings {
+                    // A private host name, reachable because it is the proxy
+                    proxy_url: Some(format!("http://localhost:{}", port)),
+                    proxy_username: Some("me".to_string()),
+                    proxy_password: Some("secret".to_string()),
+                    no_proxy: vec!["127.0.0.1".to_string()],
+                    ..Default::default()
+                })
+                .unwrap();
+
+            let proxied = fetch(&clients, &get_request("http://music.example/track".into()))
+                .await
+                .unwrap();
+            let direct = fetch(&clients, &get_request(format!("{}/agent", url)))
+                .await
+                .unwrap();
+
+            assert_eq!(
+                proxied.body,
+                format!(
+                    "proxied http://music.example/track Basic {}",
+                    STANDARD.encode("me:secret")
+                )
+            );
+            assert_eq!(direct.body, "");
+        }
+
+        #[tokio::test]
+        async fn records_exchanges_in_inspector() {
+            let url = serve().await;
+            let clients = local_clients();
+            let request = HttpRequest {
+                plugin_id: Some("lastfm".to_string()),
+                headers: Some(HashMap::from([(
+                    "Authorization".to_string(),
+                    "Bearer secret".to_string(),
+                )])),
+                ..get_request(format!("{}/cached?api_key=secret", url))
+            };
+
+            fetch(&clients, &request).await.unwrap();
+            fetch(&clients, &request).await.unwrap();
+            let unapproved = HttpRequest {
+                plugin_id: Some("unknown".to_string()),
+                ..get_request(format!("{}/cached", url))
+            };
+            fetch(&clients, &unapproved).await.unwrap_err();
+
+            let list = clients.inspector().list();
+            assert_eq!(list.len(), 3);
+            assert_eq!(list[0].plugin_id.as_deref(), Some("lastfm"));
+            assert_eq!(list[0].status, Some(200));
+            assert_eq!(list[0].response_size, Some(5));
+            assert_eq!(list[0].cache, Some(CacheStatus::Miss));
+            assert!(!list[0].url.contains("secret"));
+            // Not cached, the request carries credentials
+            assert_eq!(list[1].cache, Some(CacheStatus::Miss));
+            assert_eq!(list[2].status, None);
+            assert!(list[2].error.is_some());
+            let exchange = clients.inspector().get(list[0].id).unwrap();
+            assert_eq!(exchange.request_headers["Authorization"], "[REDACTED]");
+            assert_eq!(exchange.response_headers["cache-control"], "max-age=60");
+        }
+
+        #[tokio::test]
+        async fn keeps_urls_out_of_errors() {
+            let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!(
+                "http://{}/track?api_key=secret",
+                closed.local_addr().unwrap()
+            );
+            drop(closed);
+            let clients = local_clients();
+
+            let error = fetch(&clients, &get_request(url)).await.unwrap_err();
+
+            assert_eq!(error.kind, crate::error::ErrorKind::Network);
+            assert!(!error.message.contains("secret"));
+            let recorded = clients.inspector().list()[0].error.clone().unwrap();
+            assert!(!recorded.contains("secret"));
+        }
+
+        #[test]
+        fn takes_the_plugin_from_the_caller_token_only() {
+            let callers = Callers::default();
+            let app = callers.claim_app_token().unwrap();
+            let plugin = callers.issue_plugin_token(Some(&app), "lastfm").unwrap();
+
+            let request: HttpRequest = serde_json::from_value(serde_json::json!({
+                "url": "https://example.com",
+                "pluginId": "discogs",
+            }))
+            .unwrap();
+
+            assert_eq!(request.plugin_id, None);
+            assert_eq!(
+                caller_plugin_id(&callers, Some(&plugin)).unwrap(),
+                Some("lastfm".to_string())
+            );
+            assert_eq!(caller_plugin_id(&callers, Some(&app)).unwrap(), None);
+            assert_eq!(
+                caller_plugin_id(&callers, None).unwrap_err().kind,
+                crate::error::ErrorKind::PermissionDenied
+            );
+        }
+
+        #[test]
+        fn rejects_invalid_base64_request_body() {
+            let result = encode_request_body(&"not base64!".into(), BodyEncoding::Base64);
+
+            assert_eq!(
+                result.unwrap_err().kind,
+                crate::error::ErrorKind::ParseError
+            );
+        }
+
+        #[test]
+        fn rejects_non_string_text_body() {
+            let result = encode_request_body(&serde_json::json!({ "a": 1 }), BodyEncoding::Text);
+
+            assert_eq!(
+                result.unwrap_err().kind,
+                crate::error::ErrorKind::ParseError
+            );
+        }
+    }
+}
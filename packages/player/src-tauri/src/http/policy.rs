@@ -0,0 +1,839 @@
+use log::{debug, error, info, warn};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tauri::command;
+
+use super::client::HttpClients;
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult, ErrorKind};
+
+// Network permissions for http_fetch. Plugins declare the hosts they need in package.json
+// (`nuclear.hosts`) and can only reach the ones the user approved. Plugins that declare none,
+// including ones written before hosts could be declared, may reach any public host. Loading a
+// plugin records the hosts it declares as requested, the app lists the pending ones for the user
+// to approve, with private and loopback ones left out unless the user picks them.
+// Changing the policy is reserved for the app. Loopback, private
+// and link-local addresses are refused unless a host is explicitly allowed, both when a URL names
+// them directly and when a host name resolves to them, so plugins can't probe the LAN or local
+// services such as the MCP server. Requests without a plugin ID come from the app itself and
+// only get the address check. Behind a proxy, which resolves names itself, the target host is
+// resolved here as well so the check still applies, and redirects are only followed to hosts
+// that were resolved that way.
+
+pub const POLICY_FILE: &str = "network-policy.json";
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PluginNetworkPolicy {
+    // Declared in the plugin's manifest
+    pub requested_hosts: Vec<String>,
+    pub approved_hosts: Vec<String>,
+}
+
+/// A requested host the user hasn't approved yet. Private ones give the plugin access to the
+/// local network once approved.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PendingHost {
+    pub host: String,
+    pub private: bool,
+}
+
+/// Host patterns are either exact host names or IP addresses, `*.domain` for any subdomain,
+/// or `*` for any public host.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NetworkPolicy {
+    pub plugins: BTreeMap<String, PluginNetworkPolicy>,
+    // Private or loopback hosts every request may reach, e.g. a media server on the LAN
+    pub private_hosts: Vec<String>,
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim()
+        .trim_end_matches('.')
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase()
+}
+
+fn normalize_hosts(hosts: &[String]) -> Vec<String> {
+    let mut hosts: Vec<String> = hosts
+        .iter()
+        .map(|host| normalize_host(host))
+        .filter(|host| !host.is_empty())
+        .collect();
+    hosts.sort();
+    hosts.dedup();
+    hosts
+}
+
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = normalize_host(pattern);
+    let host = normalize_host(host);
+    match pattern.strip_prefix("*.") {
+        _ if pattern == "*" => true,
+        Some(domain) => host.ends_with(&format!(".{}", domain)),
+        None => pattern == host,
+    }
+}
+
+// IPv4-mapped IPv6 addresses are checked as the IPv4 address they carry
+pub fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(ip),
+            None => is_private_v6(ip),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        || a == 0
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+}
+
+fn is_local_name(host: &str) -> bool {
+    let host = normalize_host(host);
+    host == "localhost" || host.ends_with(".localhost")
+}
+
+// Whether approving `pattern` lets a plugin reach private addresses by name alone. Host names
+// that resolve to private addresses only show up once resolved
+fn is_private_pattern(pattern: &str) -> bool {
+    let host = normalize_host(pattern);
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_private(ip),
+        Err(_) => is_local_name(&host),
+    }
+}
+
+fn denied(message: impl Into<String>, host: &str) -> AppError {
+    AppError::new(ErrorKind::PermissionDenied, message).with_details(host.to_string())
+}
+
+impl NetworkPolicy {
+    pub fn load(path: &Path) -> AppResult<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| AppError::parse(format!("Invalid network policy {:?}: {}", path, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Records the hosts a plugin declares. Approvals are kept, including ones the manifest no
+    /// longer lists, since users can approve hosts of their own such as a server on their LAN.
+    pub fn request_hosts(&mut self, plugin_id: &str, hosts: &[String]) {
+        self.plugins
+            .entry(plugin_id.to_string())
+            .or_default()
+            .requested_hosts = normalize_hosts(hosts);
+    }
+
+    pub fn approve_hosts(&mut self, plugin_id: &str, hosts: &[String]) {
+        let plugin = self.plugins.entry(plugin_id.to_string()).or_default();
+        plugin.approved_hosts.extend(normalize_hosts(hosts));
+        plugin.approved_hosts = normalize_hosts(&plugin.approved_hosts);
+    }
+
+    pub fn revoke_hosts(&mut self, plugin_id: &str, hosts: &[String]) {
+        let hosts = normalize_hosts(hosts);
+        if let Some(plugin) = self.plugins.get_mut(plugin_id) {
+            plugin.approved_hosts.retain(|host| !hosts.contains(host));
+        }
+    }
+
+    pub fn pending_hosts(&self, plugin_id: &str) -> Vec<PendingHost> {
+        let Some(plugin) = self.plugins.get(plugin_id) else {
+            return Vec::new();
+        };
+        plugin
+            .requested_hosts
+            .iter()
+            .filter(|host| !plugin.approved_hosts.contains(host))
+            .map(|host| PendingHost {
+                host: host.clone(),
+                private: is_private_pattern(host),
+            })
+            .collect()
+    }
+
+    fn declares_hosts(&self, plugin_id: &str) -> bool {
+        self.plugins
+            .get(plugin_id)
+            .is_some_and(|plugin| !plugin.requested_hosts.is_empty())
+    }
+
+    fn approved_hosts(&self, plugin_id: &str) -> &[String] {
+        self.plugins
+            .get(plugin_id)
+            .map(|plugin| plugin.approved_hosts.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Whether `host` may be reached on a private address. Needs a pattern naming it, so
+    /// approving `*` doesn't open up the LAN.
+    pub fn allows_private(&self, plugin_id: Option<&str>, host: &str) -> bool {
+        let explicit = |pattern: &String| pattern.trim() != "*" && host_matches(pattern, host);
+        self.private_hosts.iter().any(explicit)
+            || plugin_id.is_some_and(|id| self.approved_hosts(id).iter().any(explicit))
+    }
+
+    /// Checks whether `plugin_id` may request `url`. Only plugins that declare hosts are held to
+    /// the approved ones. Host names resolving to private addresses are caught later by
+    /// `PolicyResolver`.
+    pub fn check(&self, plugin_id: Option<&str>, url: &Url) -> AppResult<()> {
+        let host = url.host_str().unwrap_or_default();
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(denied(
+                format!("Scheme {} is not allowed", url.scheme()),
+                host,
+            ));
+        }
+
+        if let Some(plugin_id) = plugin_id.filter(|id| self.declares_hosts(id)) {
+            if !self
+                .approved_hosts(plugin_id)
+                .iter()
+                .any(|pattern| host_matches(pattern, host))
+            {
+                return Err(denied(
+                    format!("Plugin {} is not allowed to reach {}", plugin_id, host),
+                    host,
+                ));
+            }
+        }
+
+        if is_private_pattern(host) && !self.allows_private(plugin_id, host) {
+            return Err(denied(
+                format!("{} is a private or loopback address", host),
+                host,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Refuses `host` if any of `addrs` is private and the policy doesn't allow it. Unlike
+    /// `PolicyResolver`, which keeps the public addresses, this is for requests that can't be
+    /// held to the addresses checked, such as those sent through a proxy.
+    pub fn check_addrs(
+        &self,
+        plugin_id: Option<&str>,
+        host: &str,
+        mut addrs: impl Iterator<Item = SocketAddr>,
+    ) -> AppResult<()> {
+        if addrs.any(|addr| is_private(addr.ip())) && !self.allows_private(plugin_id, host) {
+            return Err(denied(
+                format!("{} resolves to a private or loopback address", host),
+                host,
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Host and port to look `url` up with
+fn lookup_target(url: &Url) -> Option<(String, u16)> {
+    let host = normalize_host(url.host_str()?);
+    Some((host, url.port_or_known_default().unwrap_or(0)))
+}
+
+/// Network policy shared by the HTTP clients, persisted in `path` whenever it changes.
+/// Without a path it only lives in memory.
+#[derive(Default)]
+pub struct NetworkPolicyStore {
+    path: Option<PathBuf>,
+    policy: RwLock<NetworkPolicy>,
+}
+
+impl NetworkPolicyStore {
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let policy = match &path {
+            Some(path) => NetworkPolicy::load(path).unwrap_or_else(|e| {
+                error!(target: "http", "Failed to load network policy, using defaults: {}", e);
+                NetworkPolicy::default()
+            }),
+            None => NetworkPolicy::default(),
+        };
+        Self {
+            path,
+            policy: RwLock::new(policy),
+        }
+    }
+
+    pub fn get(&self) -> NetworkPolicy {
+        self.policy
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn update(&self, update: impl FnOnce(&mut NetworkPolicy)) -> AppResult<NetworkPolicy> {
+        let mut policy = self.policy.write().unwrap_or_else(|e| e.into_inner());
+        update(&mut policy);
+        if let Some(path) = &self.path {
+            policy.save(path)?;
+        }
+        Ok(policy.clone())
+    }
+
+    pub fn check(&self, plugin_id: Option<&str>, url: &Url) -> AppResult<()> {
+        self.policy
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .check(plugin_id, url)
+    }
+
+    pub fn allows_private(&self, plugin_id: Option<&str>, host: &str) -> bool {
+        self.policy
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .allows_private(plugin_id, host)
+    }
+
+    /// Resolves the host of `url` and checks its addresses, for requests that don't go through
+    /// `PolicyResolver`: those sent through a proxy, and yt-dlp's. Names that don't resolve here
+    /// are left to whoever sends the request.
+    pub async fn check_resolved(&self, plugin_id: Option<&str>, url: &Url) -> AppResult<()> {
+        let Some((host, port)) = lookup_target(url) else {
+            return Ok(());
+        };
+        let resolved = tokio::net::lookup_host((host.as_str(), port)).await;
+        match resolved {
+            Ok(addrs) => self
+                .policy
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .check_addrs(plugin_id, &host, addrs),
+            Err(e) => {
+                debug!(target: "http", "Could not resolve {} to check it: {}", host, e);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Stands in for `check_resolved` on redirects behind a proxy. Redirects are checked synchronously,
+/// which rules out a lookup, so they're only followed to IP addresses, which `check` covers, and to
+/// hosts already resolved for an earlier request in the chain. Anything else is refused.
+pub fn check_proxied_redirect(url: &Url, previous: &[Url]) -> AppResult<()> {
+    let Some(host) = url.host_str().map(normalize_host) else {
+        return Ok(());
+    };
+    let checked = previous
+        .iter()
+        .filter_map(Url::host_str)
+        .any(|previous| normalize_host(previous) == host);
+    if checked || host.parse::<IpAddr>().is_ok() {
+        return Ok(());
+    }
+    Err(denied(
+        format!("Can't check the redirect to {} behind a proxy", host),
+        &host,
+    ))
+}
+
+/// DNS resolver for a plugin's clients that drops private addresses the policy doesn't allow,
+/// so a public-looking host name can't point requests at the local network.
+pub struct PolicyResolver {
+    pub policy: Arc<NetworkPolicyStore>,
+    pub plugin_id: Option<String>,
+    // The configured proxy is reachable even on a private address
+    pub proxy_host: Option<String>,
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        let plugin_id = self.plugin_id.clone();
+        let is_proxy = self
+            .proxy_host
+            .as_deref()
+            .is_some_and(|proxy_host| proxy_host.eq_ignore_ascii_case(name.as_str()));
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if is_proxy || policy.allows_private(plugin_id.as_deref(), &host) {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+
+            let public: Vec<SocketAddr> = addrs
+                .into_iter()
+                .filter(|addr| !is_private(addr.ip()))
+                .collect();
+            if public.is_empty() {
+                warn!(
+                    target: "http",
+                    "Blocked {} for plugin {:?}: resolves to a private address",
+                    host, plugin_id
+                );
+                return Err(format!("{} resolves to a private or loopback address", host).into());
+            }
+            Ok(Box::new(public.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[command]
+pub async fn http_policy(clients: tauri::State<'_, HttpClients>) -> AppResult<NetworkPolicy> {
+    Ok(clients.policy().get())
+}
+
+/// Hosts a plugin requested that are waiting for the user's approval.
+#[command]
+pub async fn http_policy_pending_hosts(
+    clients: tauri::State<'_, HttpClients>,
+    plugin_id: String,
+) -> AppResult<Vec<PendingHost>> {
+    Ok(clients.policy().get().pending_hosts(&plugin_id))
+}
+
+/// Records the hosts declared in a plugin's manifest, called when the plugin loads.
+#[command]
+pub async fn http_policy_request_hosts(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: String,
+    hosts: Vec<String>,
+) -> AppResult<NetworkPolicy> {
+    callers.require_app(token.as_deref())?;
+    clients
+        .policy()
+        .update(|policy| policy.request_hosts(&plugin_id, &hosts))
+}
+
+/// Approves hosts for a plugin once the user allowed them.
+#[command]
+pub async fn http_policy_approve_hosts(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: String,
+    hosts: Vec<String>,
+) -> AppResult<NetworkPolicy> {
+    callers.require_app(token.as_deref())?;
+    info!(target: "http", "Approved hosts {:?} for plugin {}", hosts, plugin_id);
+    clients
+        .policy()
+        .update(|policy| policy.approve_hosts(&plugin_id, &hosts))
+}
+
+#[command]
+pub async fn http_policy_revoke_hosts(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: String,
+    hosts: Vec<String>,
+) -> AppResult<NetworkPolicy> {
+    callers.require_app(token.as_deref())?;
+    info!(target: "http", "Revoked hosts {:?} for plugin {}", hosts, plugin_id);
+    clients
+        .policy()
+        .update(|policy| policy.revoke_hosts(&plugin_id, &hosts))
+}
+
+#[command]
+pub async fn http_policy_set_private_hosts(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    hosts: Vec<String>,
+) -> AppResult<NetworkPolicy> {
+    callers.require_app(token.as_deref())?;
+    clients
+        .policy()
+        .update(|policy| policy.private_hosts = normalize_hosts(&hosts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    fn hosts(hosts: &[&str]) -> Vec<String> {
+        hosts.iter().map(|host| host.to_string()).collect()
+    }
+
+    mod host_matches {
+        use super::*;
+
+        #[test]
+        fn matches_exact_hosts_case_insensitively() {
+            assert!(host_matches("API.example.com", "api.example.com."));
+            assert!(!host_matches("api.example.com", "example.com"));
+        }
+
+        #[test]
+        fn matches_subdomains_of_wildcards() {
+            assert!(host_matches("*.example.com", "api.example.com"));
+            assert!(!host_matches("*.example.com", "example.com"));
+            assert!(!host_matches("*.example.com", "badexample.com"));
+        }
+    }
+
+    mod is_private {
+        use super::*;
+
+        #[test]
+        fn flags_local_ranges() {
+            for ip in [
+                "127.0.0.1",
+                "10.1.2.3",
+                "172.16.0.1",
+                "192.168.1.1",
+                "169.254.169.254",
+                "100.64.0.1",
+                "0.0.0.0",
+                "::1",
+                "fd00::1",
+                "fe80::1",
+                "::ffff:127.0.0.1",
+            ] {
+                assert!(is_private(ip.parse().unwrap()), "{}", ip);
+            }
+        }
+
+        #[test]
+        fn allows_public_addresses() {
+            for ip in ["1.1.1.1", "172.32.0.1", "2606:4700::1111"] {
+                assert!(!is_private(ip.parse().unwrap()), "{}", ip);
+            }
+        }
+    }
+
+    mod check {
+        use super::*;
+
+        #[test]
+        fn requires_approval_for_plugins() {
+            let mut policy = NetworkPolicy::default();
+            policy.request_hosts("lastfm", &hosts(&["ws.audioscrobbler.com"]));
+
+            let pending = policy.check(Some("lastfm"), &url("https://ws.audioscrobbler.com/2.0"));
+            policy.approve_hosts("lastfm", &hosts(&["ws.audioscrobbler.com"]));
+            let approved = policy.check(Some("lastfm"), &url("https://ws.audioscrobbler.com/2.0"));
+            let other = policy.check(Some("lastfm"), &url("https://example.com"));
+
+            assert_eq!(pending.unwrap_err().kind, ErrorKind::PermissionDenied);
+            assert!(approved.is_ok());
+            assert_eq!(other.unwrap_err().details.as_deref(), Some("example.com"));
+        }
+
+        #[test]
+        fn allows_public_hosts_for_plugins_that_declare_none() {
+            let mut policy = NetworkPolicy::default();
+            policy.request_hosts("legacy", &[]);
+
+            assert!(policy
+                .check(Some("legacy"), &url("https://example.com"))
+                .is_ok());
+            assert!(policy
+                .check(Some("unknown"), &url("https://example.com"))
+                .is_ok());
+            assert!(policy
+                .check(Some("legacy"), &url("http://127.0.0.1:8800/mcp"))
+                .is_err());
+        }
+
+        #[test]
+        fn blocks_private_addresses() {
+            let mut policy = NetworkPolicy::default();
+            policy.approve_hosts("lastfm", &hosts(&["*"]));
+
+            for target in [
+                "http://127.0.0.1:8800/mcp",
+                "http://localhost:3000",
+                "http://[::1]/",
+                "http://192.168.1.10",
+            ] {
+                assert!(
+                    policy.check(Some("lastfm"), &url(target)).is_err(),
+                    "{}",
+                    target
+                );
+                assert!(policy.check(None, &url(target)).is_err(), "{}", target);
+            }
+            assert!(policy
+                .check(Some("lastfm"), &url("https://example.com"))
+                .is_ok());
+        }
+
+        #[test]
+        fn allows_explicitly_approved_private_hosts() {
+            let mut policy = NetworkPolicy::default();
+            policy.approve_hosts("jellyfin", &hosts(&["192.168.1.10"]));
+            policy.private_hosts = hosts(&["nas.local"]);
+
+            assert!(policy
+                .check(Some("jellyfin"), &url("http://192.168.1.10:8096"))
+                .is_ok());
+            assert!(policy.check(None, &url("http://192.168.1.10")).is_err());
+            assert!(policy.check(None, &url("http://nas.local")).is_ok());
+        }
+
+        #[test]
+        fn rejects_other_schemes() {
+            let policy = NetworkPolicy::default();
+
+            assert!(policy.check(None, &url("file:///etc/passwd")).is_err());
+        }
+    }
+
+    mod pending_hosts {
+        use super::*;
+
+        #[test]
+        fn lists_requested_hosts_not_yet_approved() {
+            let mut policy = NetworkPolicy::default();
+            policy.request_hosts(
+                "jellyfin",
+                &hosts(&["api.example.com", "*.example.org", "localhost"]),
+            );
+            policy.approve_hosts("jellyfin", &hosts(&["*.example.org"]));
+
+            assert_eq!(
+                policy.pending_hosts("jellyfin"),
+                vec![
+                    PendingHost {
+                        host: "api.example.com".to_string(),
+                        private: false,
+                    },
+                    PendingHost {
+                        host: "localhost".to_string(),
+                        private: true,
+                    },
+                ]
+            );
+            assert!(policy.pending_hosts("unknown").is_empty());
+        }
+
+        #[test]
+        fn flags_private_and_loopback_patterns() {
+            for host in ["127.0.0.1", "192.168.1.10", "[::1]", "*.localhost"] {
+                assert!(is_private_pattern(host), "{}", host);
+            }
+            for host in ["example.com", "*", "1.1.1.1"] {
+                assert!(!is_private_pattern(host), "{}", host);
+            }
+        }
+    }
+
+    mod check_addrs {
+        use super::*;
+
+        fn addrs(addrs: &[&str]) -> std::vec::IntoIter<SocketAddr> {
+            addrs
+                .iter()
+                .map(|addr| addr.parse().unwrap())
+                .collect::<Vec<_>>()
+                .into_iter()
+        }
+
+        #[test]
+        fn refuses_hosts_with_any_private_address() {
+            let mut policy = NetworkPolicy::default();
+            policy.approve_hosts("lastfm", &hosts(&["*"]));
+
+            let mixed = policy.check_addrs(
+                Some("lastfm"),
+                "music.example",
+                addrs(&["93.184.216.34:443", "10.0.0.5:443"]),
+            );
+            let public = policy.check_addrs(
+                Some("lastfm"),
+                "music.example",
+                addrs(&["93.184.216.34:443"]),
+            );
+
+            assert_eq!(mixed.unwrap_err().kind, ErrorKind::PermissionDenied);
+            assert!(public.is_ok());
+        }
+
+        #[test]
+        fn allows_explicitly_approved_private_hosts() {
+            let policy = NetworkPolicy {
+                private_hosts: hosts(&["nas.home.lan"]),
+                ..Default::default()
+            };
+
+            assert!(policy
+                .check_addrs(None, "nas.home.lan", addrs(&["192.168.1.10:80"]))
+                .is_ok());
+        }
+    }
+
+    mod store {
+        use super::*;
+
+        #[test]
+        fn persists_updates() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join(POLICY_FILE);
+            let store = NetworkPolicyStore::open(Some(path.clone()));
+
+            store
+                .update(|policy| policy.approve_hosts("lastfm", &hosts(&["Last.fm", "last.fm"])))
+                .unwrap();
+
+            let reopened = NetworkPolicyStore::open(Some(path));
+            assert_eq!(
+                reopened.get().plugins["lastfm"].approved_hosts,
+                vec!["last.fm".to_string()]
+            );
+        }
+
+        #[test]
+        fn revokes_hosts() {
+            let store = NetworkPolicyStore::default();
+
+            let policy = store
+                .update(|policy| {
+                    policy.approve_hosts("lastfm", &hosts(&["last.fm", "*.last.fm"]));
+                    policy.revoke_hosts("lastfm", &hosts(&["last.fm"]));
+                })
+                .unwrap();
+
+            assert_eq!(
+                policy.plugins["lastfm"].approved_hosts,
+                vec!["*.last.fm".to_string()]
+            );
+        }
+
+        #[tokio::test]
+        async fn checks_the_resolved_addresses() {
+            let store = NetworkPolicyStore::default();
+            store
+                .update(|policy| policy.approve_hosts("lastfm", &hosts(&["*"])))
+                .unwrap();
+
+            let loopback = store
+                .check_resolved(Some("lastfm"), &url("http://localhost:4000"))
+                .await;
+            let unresolved = store
+                .check_resolved(Some("lastfm"), &url("http://music.invalid"))
+                .await;
+
+            assert!(loopback.is_err());
+            assert!(unresolved.is_ok());
+        }
+    }
+
+    mod check_proxied_redirect {
+        use super::*;
+
+        #[test]
+        fn follows_hosts_already_checked_and_addresses() {
+            let previous = [url("https://last.fm/track"), url("https://www.last.fm/")];
+
+            assert!(check_proxied_redirect(&url("https://WWW.last.fm/music"), &previous).is_ok());
+            assert!(check_proxied_redirect(&url("https://93.184.216.34/"), &previous).is_ok());
+            assert!(check_proxied_redirect(&url("https://[2606:2800::1]/"), &previous).is_ok());
+        }
+
+        #[test]
+        fn refuses_hosts_it_cannot_resolve() {
+            let previous = [url("https://last.fm/track")];
+
+            let error =
+                check_proxied_redirect(&url("https://nas.home.lan/"), &previous).unwrap_err();
+
+            assert_eq!(error.kind, ErrorKind::PermissionDenied);
+            assert_eq!(error.details.as_deref(), Some("nas.home.lan"));
+        }
+    }
+
+    mod resolver {
+        use super::*;
+
+        #[tokio::test]
+        async fn drops_private_addresses() {
+            let resolver = PolicyResolver {
+                policy: Arc::new(NetworkPolicyStore::default()),
+                plugin_id: Some("lastfm".to_string()),
+                proxy_host: None,
+            };
+
+            let result = resolver.resolve("localhost".parse().unwrap()).await;
+
+            assert!(result.is_err());
+        }
+
+        #[tokio::test]
+        async fn keeps_allowed_private_hosts() {
+            let store = NetworkPolicyStore::default();
+            store
+                .update(|policy| policy.private_hosts = hosts(&["localhost"]))
+                .unwrap();
+            let resolver = PolicyResolver {
+                policy: Arc::new(store),
+                plugin_id: None,
+                proxy_host: None,
+            };
+
+            let addrs: Vec<_> = resolver
+                .resolve("localhost".parse().unwrap())
+                .await
+                .unwrap()
+                .collect();
+
+            assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+            assert!(!addrs.is_empty());
+        }
+
+        #[tokio::test]
+        async fn keeps_the_proxy_host() {
+            let resolver = PolicyResolver {
+                policy: Arc::new(NetworkPolicyStore::default()),
+                plugin_id: Some("lastfm".to_string()),
+                proxy_host: Some("localhost".to_string()),
+            };
+
+            let result = resolver.resolve("localhost".parse().unwrap()).await;
+
+            assert!(result.is_ok());
+        }
+    }
+}
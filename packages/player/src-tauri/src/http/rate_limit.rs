@@ -0,0 +1,458 @@
+use log::debug;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::command;
+
+use super::client::HttpClients;
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult};
+
+// Per-host token buckets for http_fetch, so plugins stay within the rate limits of the APIs they call.
+// Buckets are kept per host and shared by every caller, since servers limit the client as a whole.
+// The app can override the limit of a host for one plugin, which gives that plugin a bucket of its
+// own. Hosts without a limit are only held back while a server has asked us to wait with Retry-After.
+// Buckets that are back to full and not blocked are dropped, as a new one would be no different.
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// Longer Retry-After waits are not worth holding a request for; the response is returned instead,
+// while later requests to the host still wait it out
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+// MusicBrainz bans clients that go over one request per second
+const BUILT_IN_LIMITS: &[(&str, RateLimit)] = &[(
+    "musicbrainz.org",
+    RateLimit {
+        requests_per_second: 1.0,
+        burst: 1,
+    },
+)];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitState {
+    pub plugin_id: Option<String>,
+    pub host: String,
+    pub limit: Option<RateLimit>,
+    pub available_tokens: Option<f64>,
+    // How long requests are held back because of a Retry-After
+    pub blocked_for_ms: u64,
+}
+
+struct Bucket {
+    limit: Option<RateLimit>,
+    tokens: f64,
+    last_refill: Instant,
+    blocked_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(limit: Option<RateLimit>) -> Self {
+        Self {
+            limit,
+            tokens: limit.map(|limit| limit.burst as f64).unwrap_or_default(),
+            last_refill: Instant::now(),
+            blocked_until: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(limit) = self.limit {
+            let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+            self.tokens =
+                (self.tokens + elapsed * limit.requests_per_second).min(limit.burst as f64);
+        }
+        self.last_refill = now;
+    }
+
+    // Takes a token, or returns how long to wait before trying again
+    fn try_acquire(&mut self, now: Instant) -> Option<Duration> {
+        if let Some(blocked_until) = self.blocked_until.filter(|until| *until > now) {
+            return Some(blocked_until - now);
+        }
+        let limit = self.limit?;
+
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limit.requests_per_second,
+            ))
+        }
+    }
+
+    fn is_idle(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        let blocked = self.blocked_until.is_some_and(|until| until > now);
+        let full = self
+            .limit
+            .map_or(true, |limit| self.tokens >= limit.burst as f64);
+        !blocked && full
+    }
+}
+
+type BucketKey = (Option<String>, String);
+
+#[derive(Default)]
+pub struct RateLimiter {
+    overrides: Mutex<HashMap<BucketKey, Option<RateLimit>>>,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+}
+
+fn built_in_limit(host: &str) -> Option<RateLimit> {
+    BUILT_IN_LIMITS
+        .iter()
+        .find(|(domain, _)| host == *domain || host.ends_with(&format!(".{}", domain)))
+        .map(|(_, limit)| *limit)
+}
+
+fn key(plugin_id: Option<&str>, host: &str) -> BucketKey {
+    (plugin_id.map(str::to_string), host.to_lowercase())
+}
+
+impl RateLimiter {
+    // The plugin's own bucket if the app overrode its limit for the host, the host's shared one
+    // otherwise. Returns the bucket's key and limit.
+    fn bucket_for(&self, plugin_id: Option<&str>, host: &str) -> (BucketKey, Option<RateLimit>) {
+        let overrides = self.overrides.lock().unwrap_or_else(|e| e.into_inner());
+        let plugin_key = key(plugin_id, host);
+        if let Some(limit) = overrides.get(&plugin_key) {
+            return (plugin_key, *limit);
+        }
+        let host_key = key(None, host);
+        let limit = match overrides.get(&host_key) {
+            Some(limit) => *limit,
+            None => built_in_limit(&host_key.1),
+        };
+        (host_key, limit)
+    }
+
+    fn with_bucket<T>(
+        &self,
+        plugin_id: Option<&str>,
+        host: &str,
+        f: impl FnOnce(&mut Bucket, Instant) -> T,
+    ) -> T {
+        let (key, limit) = self.bucket_for(plugin_id, host);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets.retain(|_, bucket| !bucket.is_idle(now));
+        f(
+            buckets.entry(key).or_insert_with(|| Bucket::new(limit)),
+            now,
+        )
+    }
+
+    /// Overrides the limit for `host` when called by `plugin_id`, or for every caller without an
+    /// override of its own when `plugin_id` is None. A `None` limit removes any limit, including a
+    /// built-in one.
+    pub fn set_limit(&self, plugin_id: Option<&str>, host: &str, limit: Option<RateLimit>) {
+        let key = key(plugin_id, host);
+        self.buckets
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&key);
+        self.overrides
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, limit);
+    }
+
+    /// Waits until a request to `host` is allowed.
+    pub async fn acquire(&self, plugin_id: Option<&str>, host: &str) {
+        loop {
+            let wait = self.with_bucket(plugin_id, host, |bucket, now| bucket.try_acquire(now));
+            match wait {
+                Some(wait) => {
+                    debug!(target: "http", "Rate limited, waiting {:?} for {}", wait, host);
+                    tokio::time::sleep(wait).await;
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Holds back requests to `host` for `delay`, e.g. after a Retry-After.
+    pub fn block(&self, plugin_id: Option<&str>, host: &str, delay: Duration) {
+        self.with_bucket(plugin_id, host, |bucket, now| {
+            bucket.blocked_until = bucket.blocked_until.max(Some(now + delay));
+        });
+    }
+
+    pub fn states(&self) -> Vec<RateLimitState> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let mut states: Vec<RateLimitState> = buckets
+            .iter_mut()
+            .map(|((plugin_id, host), bucket)| {
+                bucket.refill(now);
+                RateLimitState {
+                    plugin_id: plugin_id.clone(),
+                    host: host.clone(),
+                    limit: bucket.limit,
+                    available_tokens: bucket.limit.map(|_| bucket.tokens),
+                    blocked_for_ms: bucket
+                        .blocked_until
+                        .map(|until| until.saturating_duration_since(now).as_millis() as u64)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+        states.sort_by(|a, b| (&a.host, &a.plugin_id).cmp(&(&b.host, &b.plugin_id)));
+        states
+    }
+}
+
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
+pub fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    )
+}
+
+/// Parses Retry-After, given either in seconds or as an HTTP date.
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.signed_duration_since(chrono::Utc::now());
+    Some(delay.to_std().unwrap_or_default())
+}
+
+/// How long to hold back requests to the host after retry number `attempt` (starting at 0)
+/// was answered with `headers`.
+pub fn retry_delay(attempt: u32, headers: &HeaderMap) -> Duration {
+    parse_retry_after(headers).unwrap_or_else(|| {
+        BACKOFF_BASE
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF)
+    })
+}
+
+/// Whether a request is worth holding for `delay` to retry it.
+pub fn worth_retrying(delay: Duration) -> bool {
+    delay <= MAX_RETRY_AFTER
+}
+
+#[command]
+pub async fn http_rate_limits(
+    clients: tauri::State<'_, HttpClients>,
+) -> AppResult<Vec<RateLimitState>> {
+    Ok(clients.rate_limiter().states())
+}
+
+#[command]
+pub async fn http_set_rate_limit(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    plugin_id: Option<String>,
+    host: String,
+    limit: Option<RateLimit>,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
+    if let Some(limit) = limit {
+        let rate = limit.requests_per_second;
+        if !rate.is_finite() || rate <= 0.0 || limit.burst == 0 {
+            return Err(AppError::parse(
+                "Rate limit needs a positive rate and burst",
+            ));
+        }
+    }
+    clients
+        .rate_limiter()
+        .set_limit(plugin_id.as_deref(), &host, limit);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(retry_after: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(retry_after).unwrap());
+        headers
+    }
+
+    mod acquire {
+        use super::*;
+
+        #[tokio::test]
+        async fn spaces_requests_beyond_the_burst() {
+            let limiter = RateLimiter::default();
+            limiter.set_limit(
+                Some("plugin"),
+                "api.example.com",
+                Some(RateLimit {
+                    requests_per_second: 10.0,
+                    burst: 2,
+                }),
+            );
+            let started = Instant::now();
+
+            for _ in 0..4 {
+                limiter.acquire(Some("plugin"), "api.example.com").await;
+            }
+
+            assert!(started.elapsed() >= Duration::from_millis(180));
+        }
+
+        #[tokio::test]
+        async fn does_not_wait_for_unlimited_hosts() {
+            let limiter = RateLimiter::default();
+            let started = Instant::now();
+
+            for _ in 0..20 {
+                limiter.acquire(None, "api.example.com").await;
+            }
+
+            assert!(started.elapsed() < Duration::from_millis(50));
+        }
+
+        #[tokio::test]
+        async fn shares_built_in_limits_between_plugins() {
+            let limiter = RateLimiter::default();
+            limiter.acquire(Some("first"), "musicbrainz.org").await;
+            let started = Instant::now();
+
+            limiter.acquire(Some("second"), "musicbrainz.org").await;
+
+            assert!(started.elapsed() >= Duration::from_millis(900));
+        }
+
+        #[tokio::test]
+        async fn gives_plugins_with_an_override_their_own_bucket() {
+            let limiter = RateLimiter::default();
+            limiter.set_limit(
+                Some("mirror"),
+                "musicbrainz.org",
+                Some(RateLimit {
+                    requests_per_second: 100.0,
+                    burst: 10,
+                }),
+            );
+            limiter.acquire(None, "musicbrainz.org").await;
+            let started = Instant::now();
+
+            limiter.acquire(Some("mirror"), "musicbrainz.org").await;
+
+            assert!(started.elapsed() < Duration::from_millis(50));
+        }
+
+        #[tokio::test]
+        async fn waits_while_blocked() {
+            let limiter = RateLimiter::default();
+            limiter.block(None, "api.example.com", Duration::from_millis(150));
+            let started = Instant::now();
+
+            limiter.acquire(None, "api.example.com").await;
+
+            assert!(started.elapsed() >= Duration::from_millis(140));
+        }
+    }
+
+    mod states {
+        use super::*;
+
+        #[tokio::test]
+        async fn reports_built_in_limits() {
+            let limiter = RateLimiter::default();
+
+            limiter.acquire(Some("mb"), "MusicBrainz.org").await;
+
+            let states = limiter.states();
+            assert_eq!(states.len(), 1);
+            assert_eq!(states[0].plugin_id, None);
+            assert_eq!(states[0].host, "musicbrainz.org");
+            assert_eq!(
+                states[0].limit,
+                Some(RateLimit {
+                    requests_per_second: 1.0,
+                    burst: 1,
+                })
+            );
+            assert!(states[0].available_tokens.unwrap() < 1.0);
+            assert_eq!(states[0].blocked_for_ms, 0);
+        }
+
+        #[tokio::test]
+        async fn drops_idle_buckets() {
+            let limiter = RateLimiter::default();
+            limiter.set_limit(
+                None,
+                "api.example.com",
+                Some(RateLimit {
+                    requests_per_second: 20.0,
+                    burst: 1,
+                }),
+            );
+            limiter.acquire(None, "api.example.com").await;
+            limiter.acquire(None, "other.example.com").await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+
+            limiter.acquire(None, "musicbrainz.org").await;
+
+            let hosts: Vec<_> = limiter.states().into_iter().map(|s| s.host).collect();
+            assert_eq!(hosts, vec!["musicbrainz.org"]);
+        }
+    }
+
+    mod retry_delay {
+        use super::*;
+
+        #[test]
+        fn uses_retry_after_seconds() {
+            assert_eq!(retry_delay(0, &headers("2")), Duration::from_secs(2));
+        }
+
+        #[test]
+        fn uses_retry_after_date() {
+            let at = chrono::Utc::now() + chrono::Duration::seconds(10);
+            let delay = retry_delay(0, &headers(&at.to_rfc2822()));
+
+            assert!(delay > Duration::from_secs(8) && delay <= Duration::from_secs(10));
+        }
+
+        #[test]
+        fn keeps_long_retry_after_but_does_not_retry() {
+            let delay = retry_delay(0, &headers("3600"));
+
+            assert_eq!(delay, Duration::from_secs(3600));
+            assert!(!worth_retrying(delay));
+        }
+
+        #[test]
+        fn backs_off_exponentially() {
+            let none = HeaderMap::new();
+
+            assert_eq!(retry_delay(0, &none), Duration::from_millis(500));
+            assert_eq!(retry_delay(2, &none), Duration::from_secs(2));
+            assert_eq!(retry_delay(10, &none), MAX_BACKOFF);
+        }
+    }
+}
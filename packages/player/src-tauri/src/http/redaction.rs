@@ -0,0 +1,602 @@
+use log::{error, info};
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tauri::command;
+
+use super::client::HttpClients;
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult};
+
+// Redaction of sensitive values in HTTP logs. Built-in header and query param lists are always
+// applied; users can add headers, params and JSON body key paths on top. Bodies are only logged
+// by length unless `logBodies` is switched on, in which case JSON and form bodies are logged with
+// sensitive keys masked at any depth.
+
+pub const REDACTION_FILE: &str = "http-redaction.json";
+
+const REDACTED: &str = "[REDACTED]";
+// Longer bodies are cut off in the log
+const MAX_LOGGED_BODY_CHARS: usize = 4096;
+
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+const REDACTED_QUERY_PARAMS: &[&str] = &[
+    "api_key",
+    "api-key",
+    "apikey",
+    "api_sig",
+    "api-sig",
+    "apisig",
+    "token",
+    "access_token",
+    "refresh_token",
+    "auth_token",
+    "secret",
+    "password",
+    "passwd",
+    "sig",
+    "signature",
+];
+
+/// Extra redaction on top of the built-in lists. Names are matched case-insensitively.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RedactionRules {
+    pub headers: Vec<String>,
+    pub query_params: Vec<String>,
+    // A bare key such as `email` is masked at any depth, a dotted path such as `user.email` or
+    // `*.credentials.pin` only from the root. Array elements share their parent's path.
+    pub body_keys: Vec<String>,
+    // Logs JSON and form bodies with sensitive values masked instead of just their length
+    pub log_bodies: bool,
+}
+
+fn contains_ignore_case<S: AsRef<str>>(names: &[S], name: &str) -> bool {
+    names
+        .iter()
+        .any(|candidate| candidate.as_ref().eq_ignore_ascii_case(name))
+}
+
+fn path_matches(pattern: &[&str], path: &[&str]) -> bool {
+    pattern.len() == path.len()
+        && pattern
+            .iter()
+            .zip(path)
+            .all(|(expected, key)| *expected == "*" || expected.eq_ignore_ascii_case(key))
+}
+
+fn truncate(text: String) -> String {
+    match text.char_indices().nth(MAX_LOGGED_BODY_CHARS) {
+        Some((end, _)) => format!("{}...[TRUNCATED]", &text[..end]),
+        None => text,
+    }
+}
+
+impl RedactionRules {
+    pub fn load(path: &Path) -> AppResult<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| AppError::parse(format!("Invalid redaction rules {:?}: {}", path, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    fn is_sensitive_header(&self, name: &str) -> bool {
+        contains_ignore_case(REDACTED_HEADERS, name) || contains_ignore_case(&self.headers, name)
+    }
+
+    fn is_sensitive_param(&self, name: &str) -> bool {
+        let decoded = percent_decode_str(name).decode_utf8_lossy();
+        contains_ignore_case(REDACTED_QUERY_PARAMS, &decoded)
+            || contains_ignore_case(&self.query_params, &decoded)
+    }
+
+    // Sensitive params and bare body keys are masked wherever they appear in a body
+    fn is_sensitive_key(&self, path: &[&str]) -> bool {
+        let Some(key) = path.last() else {
+            return false;
+        };
+        self.is_sensitive_param(key)
+            || self.body_keys.iter().any(|pattern| {
+                let pattern: Vec<&str> = pattern.split('.').collect();
+                match pattern.as_slice() {
+                    [bare] => bare.eq_ignore_ascii_case(key),
+                    pattern => path_matches(pattern, path),
+                }
+            })
+    }
+
+    fn redact_query(&self, query: &str) -> String {
+        query
+            .split('&')
+            .map(|param| {
+                if let Some(eq_pos) = param.find('=') {
+                    let (name, _) = param.split_at(eq_pos);
+                    if self.is_sensitive_param(name) {
+                        format!("{}={}", name, REDACTED)
+                    } else {
+                        param.to_string()
+                    }
+                } else {
+                    param.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("&")
+    }
+
+    pub fn redact_url(&self, url: &str) -> String {
+        let Some(query_start) = url.find('?') else {
+            return url.to_string();
+        };
+
+        let (base, query_with_marker) = url.split_at(query_start);
+        let query = &query_with_marker[1..];
+
+        if query.is_empty() {
+            return url.to_string();
+        }
+
+        format!("{}?{}", base, self.redact_query(query))
+    }
+
+    pub fn redact_headers(&self, headers: &HashMap<String, String>) -> HashMap<String, String> {
+        headers
+            .iter()
+            .map(|(key, value)| {
+                let redacted_value = if self.is_sensitive_header(key) {
+                    REDACTED.to_string()
+                } else {
+                    value.clone()
+                };
+                (key.clone(), redacted_value)
+            })
+            .collect()
+    }
+
+    fn redact_json(&self, value: &mut serde_json::Value, path: &mut Vec<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    path.push(key.clone());
+                    let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+                    if self.is_sensitive_key(&segments) {
+                        *value = REDACTED.into();
+                    } else {
+                        self.redact_json(value, path);
+                    }
+                    path.pop();
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.redact_json(item, path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// JSON or form body with sensitive values masked, or None for other content types.
+    pub fn redact_body(&self, body: &[u8], content_type: Option<&str>) -> Option<String> {
+        let mime = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|mime| mime.trim().to_lowercase());
+        match mime.as_deref() {
+            Some("application/x-www-form-urlencoded") => {
+                Some(self.redact_query(std::str::from_utf8(body).ok()?))
+            }
+            Some(mime) if !(mime.ends_with("/json") || mime.ends_with("+json")) => None,
+            // Bodies without a content type are logged if they are JSON
+            _ => {
+                let mut value: serde_json::Value = serde_json::from_slice(body).ok()?;
+                self.redact_json(&mut value, &mut Vec::new());
+                Some(value.to_string())
+            }
+        }
+    }
+
+    pub fn format_body(&self, body: Option<&[u8]>, content_type: Option<&str>) -> String {
+        let Some(body) = body else {
+            return "[NO BODY]".to_string();
+        };
+        let redacted = self
+            .log_bodies
+            .then(|| self.redact_body(body, content_type))
+            .flatten();
+        match redacted {
+            Some(redacted) => format!("[BODY length={}] {}", body.len(), truncate(redacted)),
+            None => format!("[BODY length={}]", body.len()),
+        }
+    }
+}
+
+/// Redaction rules used by the HTTP clients, persisted in `path` whenever they change.
+/// Without a path they only live in memory.
+#[derive(Default)]
+pub struct RedactionStore {
+    path: Option<PathBuf>,
+    rules: RwLock<RedactionRules>,
+}
+
+impl RedactionStore {
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let rules = match &path {
+            Some(path) => RedactionRules::load(path).unwrap_or_else(|e| {
+                error!(target: "http", "Failed to load redaction rules, using defaults: {}", e);
+                RedactionRules::default()
+            }),
+            None => RedactionRules::default(),
+        };
+        Self {
+            path,
+            rules: RwLock::new(rules),
+        }
+    }
+
+    pub fn rules(&self) -> RedactionRules {
+        self.rules.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set(&self, rules: RedactionRules) -> AppResult<()> {
+        let mut current = self.rules.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(path) = &self.path {
+            rules.save(path)?;
+        }
+        *current = rules;
+        Ok(())
+    }
+}
+
+#[command]
+pub async fn http_redaction_rules(
+    clients: tauri::State<'_, HttpClients>,
+) -> AppResult<RedactionRules> {
+    Ok(clients.redaction().rules())
+}
+
+/// Reserved for the app, so a plugin can't turn on body logging or unmask its own traffic.
+#[command]
+pub async fn http_set_redaction_rules(
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    rules: RedactionRules,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
+    info!(
+        target: "http",
+        "Updating redaction rules, body logging {}",
+        if rules.log_bodies { "on" } else { "off" }
+    );
+    clients.redaction().set(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact_headers(headers: &HashMap<String, String>) -> HashMap<String, String> {
+        RedactionRules::default().redact_headers(headers)
+    }
+
+    fn redact_url(url: &str) -> String {
+        RedactionRules::default().redact_url(url)
+    }
+
+    fn format_body_for_log(body: Option<&[u8]>) -> String {
+        RedactionRules::default().format_body(body, None)
+    }
+
+    fn debug_rules(body_keys: &[&str]) -> RedactionRules {
+        RedactionRules {
+            body_keys: body_keys.iter().map(|key| key.to_string()).collect(),
+            log_bodies: true,
+            ..Default::default()
+        }
+    }
+
+    mod redact_headers {
+        use super::*;
+
+        #[test]
+        fn redacts_authorization_header() {
+            let mut headers = HashMap::new();
+            headers.insert(
+                "authorization".to_string(),
+                "Bearer secret-token".to_string(),
+            );
+            headers.insert("content-type".to_string(), "application/json".to_string());
+
+            let redacted = redact_headers(&headers);
+
+            assert_eq!(redacted.get("authorization").unwrap(), "[REDACTED]");
+            assert_eq!(redacted.get("content-type").unwrap(), "application/json");
+        }
+
+        #[test]
+        fn redacts_x_api_key_header() {
+            let mut headers = HashMap::new();
+            headers.insert("x-api-key".to_string(), "my-secret-api-key".to_string());
+
+            let redacted = redact_headers(&headers);
+
+            assert_eq!(redacted.get("x-api-key").unwrap(), "[REDACTED]");
+        }
+
+        #[test]
+        fn is_case_insensitive() {
+            let mut headers = HashMap::new();
+            headers.insert("Authorization".to_string(), "Bearer secret".to_string());
+            headers.insert("COOKIE".to_string(), "session=xyz".to_string());
+            headers.insert("X-Api-Key".to_string(), "key123".to_string());
+
+            let redacted = redact_headers(&headers);
+
+            assert_eq!(redacted.get("Authorization").unwrap(), "[REDACTED]");
+            assert_eq!(redacted.get("COOKIE").unwrap(), "[REDACTED]");
+            assert_eq!(redacted.get("X-Api-Key").unwrap(), "[REDACTED]");
+        }
+
+        #[test]
+        fn preserves_safe_headers() {
+            let mut headers = HashMap::new();
+            headers.insert("content-type".to_string(), "application/json".to_string());
+            headers.insert("user-agent".to_string(), "Nuclear/1.0".to_string());
+            headers.insert("accept".to_string(), "*/*".to_string());
+
+            let redacted = redact_headers(&headers);
+
+            assert_eq!(redacted.get("content-type").unwrap(), "application/json");
+            assert_eq!(redacted.get("user-agent").unwrap(), "Nuclear/1.0");
+            assert_eq!(redacted.get("accept").unwrap(), "*/*");
+        }
+    }
+
+    mod redact_url {
+        use super::*;
+
+        #[test]
+        fn redacts_api_key_param() {
+            let url = "https://ws.audioscrobbler.com/2.0/?method=artist.search&artist=cher&api_key=abc123&format=json";
+            let redacted = redact_url(url);
+            assert_eq!(
+                redacted,
+                "https://ws.audioscrobbler.com/2.0/?method=artist.search&artist=cher&api_key=[REDACTED]&format=json"
+            );
+        }
+
+        #[test]
+        fn redacts_multiple_sensitive_params() {
+            let url = "https://api.example.com?api_key=key123&secret=shhh&query=music&token=tok456";
+            let redacted = redact_url(url);
+            assert_eq!(
+                redacted,
+                "https://api.example.com?api_key=[REDACTED]&secret=[REDACTED]&query=music&token=[REDACTED]"
+            );
+        }
+
+        #[test]
+        fn is_case_insensitive() {
+            let url = "https://api.example.com?API_KEY=key123&Token=tok&SECRET=shh";
+            let redacted = redact_url(url);
+            assert_eq!(
+                redacted,
+                "https://api.example.com?API_KEY=[REDACTED]&Token=[REDACTED]&SECRET=[REDACTED]"
+            );
+        }
+
+        #[test]
+        fn preserves_safe_params() {
+            let url = "https://api.example.com/search?query=hello&limit=10&offset=0";
+            let redacted = redact_url(url);
+            assert_eq!(
+                redacted,
+                "https://api.example.com/search?query=hello&limit=10&offset=0"
+            );
+        }
+
+        #[test]
+        fn handles_url_without_query_params() {
+            let url = "https://api.example.com/users/123";
+            let redacted = redact_url(url);
+            assert_eq!(redacted, "https://api.example.com/users/123");
+        }
+
+        #[test]
+        fn handles_malformed_url_gracefully() {
+            let url = "not a valid url";
+            let redacted = redact_url(url);
+            assert_eq!(redacted, "not a valid url");
+        }
+
+        #[test]
+        fn redacts_url_encoded_param_names() {
+            // api%5Fkey is URL-encoded api_key
+            let url = "https://api.example.com?api%5Fkey=secret123&query=test";
+            let redacted = redact_url(url);
+            assert_eq!(
+                redacted,
+                "https://api.example.com?api%5Fkey=[REDACTED]&query=test"
+            );
+        }
+    }
+
+    mod format_body_for_log {
+        use super::*;
+
+        #[test]
+        fn formats_body_with_length() {
+            let body = "some request body content";
+            let formatted = format_body_for_log(Some(body.as_bytes()));
+            assert_eq!(formatted, "[BODY length=25]");
+        }
+
+        #[test]
+        fn formats_empty_body() {
+            let formatted = format_body_for_log(Some(b""));
+            assert_eq!(formatted, "[BODY length=0]");
+        }
+
+        #[test]
+        fn formats_none_as_no_body() {
+            let formatted = format_body_for_log(None);
+            assert_eq!(formatted, "[NO BODY]");
+        }
+
+        #[test]
+        fn never_includes_body_content() {
+            let body = r#"{"password": "super_secret", "api_key": "abc123"}"#;
+            let formatted = format_body_for_log(Some(body.as_bytes()));
+            assert!(!formatted.contains("password"));
+            assert!(!formatted.contains("super_secret"));
+            assert!(!formatted.contains("api_key"));
+            assert!(!formatted.contains("abc123"));
+            assert_eq!(formatted, "[BODY length=49]");
+        }
+    }
+
+    mod configured_rules {
+        use super::*;
+
+        #[test]
+        fn redacts_extra_headers_and_params() {
+            let rules = RedactionRules {
+                headers: vec!["X-Session".to_string()],
+                query_params: vec!["user".to_string()],
+                ..Default::default()
+            };
+            let headers = HashMap::from([("x-session".to_string(), "abc".to_string())]);
+
+            assert_eq!(rules.redact_headers(&headers)["x-session"], "[REDACTED]");
+            assert_eq!(
+                rules.redact_url("https://example.com?user=me&page=2"),
+                "https://example.com?user=[REDACTED]&page=2"
+            );
+        }
+    }
+
+    mod redact_body {
+        use super::*;
+
+        #[test]
+        fn masks_sensitive_keys_in_nested_json() {
+            let body = serde_json::json!({
+                "user": { "name": "cher", "password": "hunter2" },
+                "sessions": [{ "token": "t1", "device": "phone" }],
+                "auth": { "api_key": { "value": "k" } },
+            });
+
+            let redacted = debug_rules(&[])
+                .redact_body(body.to_string().as_bytes(), Some("application/json"))
+                .unwrap();
+
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&redacted).unwrap(),
+                serde_json::json!({
+                    "user": { "name": "cher", "password": "[REDACTED]" },
+                    "sessions": [{ "token": "[REDACTED]", "device": "phone" }],
+                    "auth": { "api_key": "[REDACTED]" },
+                })
+            );
+        }
+
+        #[test]
+        fn applies_configured_key_paths() {
+            let body = serde_json::json!({
+                "email": "root@example.com",
+                "profile": { "email": "nested@example.com", "pin": "1234" },
+                "device": { "pin": "0000" },
+                "items": [{ "meta": { "pin": "9999" } }],
+            });
+
+            let redacted = debug_rules(&["email", "profile.pin", "*.meta.pin"])
+                .redact_body(body.to_string().as_bytes(), Some("application/json"))
+                .unwrap();
+
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&redacted).unwrap(),
+                serde_json::json!({
+                    "email": "[REDACTED]",
+                    "profile": { "email": "[REDACTED]", "pin": "[REDACTED]" },
+                    "device": { "pin": "0000" },
+                    "items": [{ "meta": { "pin": "[REDACTED]" } }],
+                })
+            );
+        }
+
+        #[test]
+        fn masks_form_encoded_fields() {
+            let body = b"username=cher&password=hunter2&api%5Fsig=abc&remember=1";
+
+            let redacted = debug_rules(&[])
+                .redact_body(
+                    body,
+                    Some("application/x-www-form-urlencoded; charset=UTF-8"),
+                )
+                .unwrap();
+
+            assert_eq!(
+                redacted,
+                "username=cher&password=[REDACTED]&api%5Fsig=[REDACTED]&remember=1"
+            );
+        }
+
+        #[test]
+        fn skips_other_content_types() {
+            assert_eq!(
+                debug_rules(&[]).redact_body(b"{\"a\":1}", Some("text/html")),
+                None
+            );
+            assert_eq!(debug_rules(&[]).redact_body(&[0xff, 0xd8], None), None);
+        }
+    }
+
+    mod format_body {
+        use super::*;
+
+        #[test]
+        fn logs_redacted_body_in_debug_mode() {
+            let body = br#"{"password":"super_secret","artist":"Cher"}"#;
+
+            let formatted = debug_rules(&[]).format_body(Some(body), Some("application/json"));
+
+            assert_eq!(
+                formatted,
+                r#"[BODY length=43] {"artist":"Cher","password":"[REDACTED]"}"#
+            );
+        }
+
+        #[test]
+        fn truncates_long_bodies() {
+            let body = serde_json::json!({ "text": "a".repeat(10_000) }).to_string();
+
+            let formatted = debug_rules(&[]).format_body(Some(body.as_bytes()), None);
+
+            assert!(formatted.ends_with("...[TRUNCATED]"));
+            assert!(formatted.len() < 4200);
+        }
+    }
+}
@@ -1,11 +1,21 @@
+pub mod audio_cache;
+pub mod caller;
 pub mod commands;
+mod dialog;
+pub mod download;
+pub mod error;
 pub mod http;
 pub mod logging;
 pub mod mcp;
+pub mod network;
+pub mod plugins;
 mod setup;
 pub mod stream_proxy;
 pub mod ytdlp;
 
+use tauri::webview::PageLoadEvent;
+use tauri::Manager;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let is_flatpak = std::env::var("FLATPAK_ID").is_ok();
@@ -23,25 +33,75 @@ pub fn run() {
     }
 
     builder
+        .manage(caller::Callers::default())
+        .on_page_load(|webview, payload| {
+            // Tokens live as long as the page that claimed them, a reload claims them again
+            if matches!(payload.event(), PageLoadEvent::Started) {
+                webview.state::<caller::Callers>().reset();
+            }
+        })
         .register_asynchronous_uri_scheme_protocol("nuclear-stream", |ctx, request, responder| {
             stream_proxy::handle_stream_request(ctx.app_handle(), request, responder);
         })
         .invoke_handler(tauri::generate_handler![
+            audio_cache::audio_cache_stats,
+            audio_cache::audio_cache_set_max_bytes,
+            audio_cache::audio_cache_clear,
+            caller::caller_claim_app_token,
+            caller::caller_issue_plugin_token,
+            caller::caller_revoke_plugin_token,
             commands::is_flatpak,
             commands::copy_dir_recursive,
-            commands::extract_zip,
-            commands::download_file,
+            download::download_file,
+            download::cancel_download,
             http::http_fetch,
+            http::http_fetch_stream,
+            http::http_cancel,
+            http::cookies::http_cookies_list,
+            http::cookies::http_cookies_import,
+            http::cookies::http_cookies_clear,
+            http::cookies::http_cookies_file,
+            http::rate_limit::http_rate_limits,
+            http::rate_limit::http_set_rate_limit,
+            http::cache::http_cache_clear,
+            http::policy::http_policy,
+            http::policy::http_policy_pending_hosts,
+            http::policy::http_policy_request_hosts,
+            http::policy::http_policy_approve_hosts,
+            http::policy::http_policy_revoke_hosts,
+            http::policy::http_policy_set_private_hosts,
+            http::redaction::http_redaction_rules,
+            http::redaction::http_set_redaction_rules,
+            http::inspector::http_inspector_list,
+            http::inspector::http_inspector_get,
+            http::inspector::http_inspector_clear,
+            http::inspector::http_inspector_export_har,
+            plugins::install_plugin_archive,
+            plugins::signature::plugin_trust_store,
+            plugins::signature::plugin_trust_key,
+            plugins::signature::plugin_untrust_key,
+            plugins::signature::plugin_set_require_signatures,
+            plugins::signature::verify_plugin_archive,
             ytdlp::ytdlp_search,
             ytdlp::ytdlp_get_stream,
+            ytdlp::ytdlp_resolve_url,
+            ytdlp::manager::ytdlp_binary_info,
+            ytdlp::manager::ytdlp_set_binary_path,
+            ytdlp::manager::ytdlp_set_cookies_file,
+            ytdlp::manager::ytdlp_update,
             logging::get_startup_logs,
+            network::network_settings,
+            network::network_set_settings,
             mcp::mcp_start,
             mcp::mcp_stop,
             mcp::mcp_respond
         ])
         .setup(|app| {
             logging::mark_startup_complete();
+            audio_cache::init_audio_cache(app.handle().clone());
+            http::client::init_http_clients(app.handle().clone());
             mcp::init_mcp(app.handle().clone());
+            ytdlp::manager::init_ytdlp_manager(app.handle().clone());
             Ok(())
         })
         .run(tauri::generate_context!())
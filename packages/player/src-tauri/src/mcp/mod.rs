@@ -13,6 +13,8 @@ use tokio::sync::{oneshot, Mutex};
 use tokio_util::sync::CancellationToken;
 use tools::NuclearMcpServer;
 
+use crate::error::{AppError, AppResult};
+
 const MCP_PORT_START: u16 = 8800;
 const MCP_PORT_END: u16 = 8809;
 
@@ -109,7 +111,7 @@ pub fn init_mcp(app_handle: AppHandle) {
 }
 
 #[tauri::command]
-pub async fn mcp_start(state: tauri::State<'_, McpState>) -> Result<u16, String> {
+pub async fn mcp_start(state: tauri::State<'_, McpState>) -> AppResult<u16> {
     let mut guard = state.running.lock().await;
     if let Some(server) = guard.as_ref() {
         log::info!("MCP server already running on port {}", server.port);
@@ -131,13 +133,15 @@ pub async fn mcp_start(state: tauri::State<'_, McpState>) -> Result<u16, String>
             });
             Ok(port)
         }
-        Ok(Err(message)) => Err(message),
-        Err(_) => Err("MCP server task exited before reporting ready".into()),
+        Ok(Err(message)) => Err(AppError::io(message)),
+        Err(_) => Err(AppError::unknown(
+            "MCP server task exited before reporting ready",
+        )),
     }
 }
 
 #[tauri::command]
-pub async fn mcp_stop(state: tauri::State<'_, McpState>) -> Result<(), String> {
+pub async fn mcp_stop(state: tauri::State<'_, McpState>) -> AppResult<()> {
     let mut guard = state.running.lock().await;
     if let Some(server) = guard.take() {
         log::info!("Stopping MCP server");
@@ -153,7 +157,7 @@ pub async fn mcp_stop(state: tauri::State<'_, McpState>) -> Result<(), String> {
 pub async fn mcp_respond(
     state: tauri::State<'_, McpState>,
     response: McpBridgeResponse,
-) -> Result<(), String> {
+) -> AppResult<()> {
     state.bridge.handle_response(response).await;
     Ok(())
 }
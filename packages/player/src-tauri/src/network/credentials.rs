@@ -0,0 +1,73 @@
+use std::sync::Mutex;
+
+use crate::error::{AppError, AppResult};
+
+// Where the proxy password is kept. It stays out of network.json, which any process running as the
+// user can read, and lives in the OS keyring instead: the Keychain on macOS, the Credential Manager
+// on Windows and the Secret Service on Linux.
+
+const KEYRING_SERVICE: &str = "com.nuclearplayer";
+const PROXY_PASSWORD: &str = "proxy-password";
+
+pub trait SecretStore: Send + Sync {
+    fn get(&self) -> AppResult<Option<String>>;
+    /// Stores `secret`, or forgets it when None.
+    fn set(&self, secret: Option<&str>) -> AppResult<()>;
+}
+
+pub struct KeyringSecret {
+    user: &'static str,
+}
+
+impl KeyringSecret {
+    pub fn proxy_password() -> Self {
+        Self {
+            user: PROXY_PASSWORD,
+        }
+    }
+
+    fn entry(&self) -> AppResult<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, self.user).map_err(keyring_error)
+    }
+}
+
+fn keyring_error(e: keyring::Error) -> AppError {
+    AppError::unknown(format!("Keyring error: {}", e))
+}
+
+impl SecretStore for KeyringSecret {
+    fn get(&self) -> AppResult<Option<String>> {
+        match self.entry()?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    fn set(&self, secret: Option<&str>) -> AppResult<()> {
+        let entry = self.entry()?;
+        let result = match secret {
+            Some(secret) => entry.set_password(secret),
+            None => entry.delete_credential(),
+        };
+        match result {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+}
+
+/// Keeps the secret in memory only, for stores without a settings file.
+#[derive(Default)]
+pub struct MemorySecret(Mutex<Option<String>>);
+
+impl SecretStore for MemorySecret {
+    fn get(&self) -> AppResult<Option<String>> {
+        Ok(self.0.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    fn set(&self, secret: Option<&str>) -> AppResult<()> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = secret.map(str::to_string);
+        Ok(())
+    }
+}
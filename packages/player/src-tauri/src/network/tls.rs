@@ -0,0 +1,464 @@
+use log::{debug, warn};
+use once_cell::sync::Lazy;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::error::{AppError, AppResult};
+use crate::http::policy::host_matches;
+
+// TLS options for self-hosted servers: extra CA certificates on top of the system roots,
+// certificate pins per host, and hosts whose certificates are not checked at all.
+// With any of them set, clients use a rustls config with a verifier that applies them per host;
+// otherwise they keep reqwest's default TLS.
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TlsSettings {
+    // PEM files with CA certificates trusted on top of the system roots
+    pub ca_files: Vec<PathBuf>,
+    // Host pattern to the hex SHA-256 fingerprint of the certificate it must present. A pinned
+    // host is trusted on the pin alone, so self-signed certificates work.
+    pub pinned_certificates: BTreeMap<String, String>,
+    // Host patterns whose certificates are accepted without any checks. `*` and wildcards over a
+    // single label, like `*.com`, are refused.
+    pub insecure_hosts: Vec<String>,
+}
+
+// The config only changes with the settings, and loading the system roots isn't free
+static CLIENT_CONFIG: Lazy<Mutex<Option<(TlsSettings, ClientConfig)>>> = Lazy::new(Mutex::default);
+
+/// Parses a hex SHA-256 fingerprint, ignoring case, colons and spaces.
+pub fn parse_fingerprint(fingerprint: &str) -> AppResult<Vec<u8>> {
+    let digits: String = fingerprint
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    match hex::decode(&digits) {
+        Ok(bytes) if bytes.len() == 32 => Ok(bytes),
+        _ => Err(AppError::parse(format!(
+            "Invalid SHA-256 fingerprint: {}",
+            fingerprint
+        ))),
+    }
+}
+
+// Whether `pattern` is too broad to skip certificate checks for
+fn is_too_broad(pattern: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('.');
+    match pattern.strip_prefix("*.") {
+        _ if pattern == "*" => true,
+        Some(domain) => !domain.contains('.'),
+        None => false,
+    }
+}
+
+// Pins on exact host names win over wildcards, and longer wildcards over shorter ones
+fn pin_specificity(pattern: &str) -> (bool, usize) {
+    (!pattern.trim().starts_with('*'), pattern.len())
+}
+
+impl TlsSettings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn ca_certificates(&self) -> AppResult<Vec<CertificateDer<'static>>> {
+        let mut certificates = Vec::new();
+        for path in &self.ca_files {
+            let pem = fs::read(path)
+                .map_err(|e| AppError::io(format!("Failed to read CA file {:?}: {}", path, e)))?;
+            let parsed = CertificateDer::pem_slice_iter(&pem)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| AppError::parse(format!("Invalid CA file {:?}: {}", path, e)))?;
+            if parsed.is_empty() {
+                return Err(AppError::parse(format!(
+                    "CA file {:?} has no certificates",
+                    path
+                )));
+            }
+            certificates.extend(parsed);
+        }
+        Ok(certificates)
+    }
+
+    fn verifier(&self) -> AppResult<HostVerifier> {
+        if let Some(pattern) = self.insecure_hosts.iter().find(|host| is_too_broad(host)) {
+            return Err(AppError::parse(format!(
+                "Insecure host {} is too broad, name a host or a domain like *.home.lan",
+                pattern
+            )));
+        }
+        let provider = Arc::new(ring::default_provider());
+        let mut roots = RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs();
+        for error in &native.errors {
+            debug!("Skipping system certificate: {}", error);
+        }
+        roots.add_parsable_certificates(native.certs);
+        for certificate in self.ca_certificates()? {
+            roots
+                .add(certificate)
+                .map_err(|e| AppError::parse(format!("Invalid CA certificate: {}", e)))?;
+        }
+        let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|e| AppError::unknown(format!("No usable CA certificates: {}", e)))?;
+
+        let pins = self
+            .pinned_certificates
+            .iter()
+            .map(|(host, fingerprint)| Ok((host.clone(), parse_fingerprint(fingerprint)?)))
+            .collect::<AppResult<_>>()?;
+        for host in &self.insecure_hosts {
+            warn!(
+                "TLS certificate checks are DISABLED for {}. Anyone on the network path can impersonate it.",
+                host
+            );
+        }
+
+        Ok(HostVerifier {
+            webpki,
+            pins,
+            insecure_hosts: self.insecure_hosts.clone(),
+            provider,
+        })
+    }
+
+    /// A rustls config applying these settings, for reqwest's `use_preconfigured_tls`.
+    pub fn client_config(&self) -> AppResult<ClientConfig> {
+        let mut cached = CLIENT_CONFIG.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((settings, config)) = cached.as_ref() {
+            if settings == self {
+                return Ok(config.clone());
+            }
+        }
+
+        let verifier = self.verifier()?;
+        let mut config = ClientConfig::builder_with_provider(verifier.provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| AppError::unknown(format!("Failed to set up TLS: {}", e)))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        *cached = Some((self.clone(), config.clone()));
+        Ok(config)
+    }
+}
+
+#[derive(Debug)]
+struct HostVerifier {
+    webpki: Arc<WebPkiServerVerifier>,
+    pins: Vec<(String, Vec<u8>)>,
+    insecure_hosts: Vec<String>,
+    provider: Arc<CryptoProvider>,
+}
+
+fn server_host(server_name: &ServerName<'_>) -> String {
+    match server_name {
+        ServerName::DnsName(name) => name.as_ref().to_string(),
+        ServerName::IpAddress(ip) => IpAddr::from(*ip).to_string(),
+        _ => String::new(),
+    }
+}
+
+impl ServerCertVerifier for HostVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let host = server_host(server_name);
+
+        if let Some((_, pin)) = self
+            .pins
+            .iter()
+            .filter(|(pattern, _)| host_matches(pattern, &host))
+            .max_by_key(|(pattern, _)| pin_specificity(pattern))
+        {
+            let fingerprint = Sha256::digest(end_entity.as_ref());
+            if fingerprint.as_slice() == pin.as_slice() {
+                return Ok(ServerCertVerified::assertion());
+            }
+            warn!(
+                "Certificate presented by {} does not match its pin, got sha256 {}",
+                host,
+                hex::encode(fingerprint)
+            );
+            return Err(rustls::Error::General(format!(
+                "Certificate for {} does not match the pinned fingerprint",
+                host
+            )));
+        }
+
+        if self
+            .insecure_hosts
+            .iter()
+            .any(|pattern| host_matches(pattern, &host))
+        {
+            warn!("Accepting the certificate of {} WITHOUT verification", host);
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        self.webpki
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    // Generated with openssl, valid until 2126
+    const CA: &str = "-----BEGIN CERTIFICATE-----
+MIIBlDCCATmgAwIBAgIUXJ8dZrJq7zDXCFvSms4ej0FySFQwCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLSG9tZSBMYWIgQ0EwIBcNMjYxMDE4MTE1MTQ5WhgPMjEyNjA5
+MjQxMTUxNDlaMBYxFDASBgNVBAMMC0hvbWUgTGFiIENBMFkwEwYHKoZIzj0CAQYI
+KoZIzj0DAQcDQgAEYqpcZIjrO8bhgQzIawR6jezqJhX7xzO1mZJDq5jcRyOMCAqI
+3TbXMQVH5aw6KKhskRV0bvUBPFu2xUId3e51VKNjMGEwHQYDVR0OBBYEFHJlBBKB
+23IaZfWTGOx4FVFpVe/WMB8GA1UdIwQYMBaAFHJlBBKB23IaZfWTGOx4FVFpVe/W
+MA8GA1UdEwEB/wQFMAMBAf8wDgYDVR0PAQH/BAQDAgEGMAoGCCqGSM49BAMCA0kA
+MEYCIQC4H2zhkUK0aYgHIenl7QUJP7pWM6H+T3pga9qYvgdeoAIhAK3HiP+lMgw9
+Pp+UZloRziawduqLdNpTmMOoN4LhxEnc
+-----END CERTIFICATE-----
+";
+
+    // media.home.lan, issued by CA
+    const MEDIA: &str = "-----BEGIN CERTIFICATE-----
+MIIBwjCCAWigAwIBAgIUKiNxToUtc3ch2CLjF72kAbUQu38wCgYIKoZIzj0EAwIw
+FjEUMBIGA1UEAwwLSG9tZSBMYWIgQ0EwIBcNMjYxMDE4MTE1MTQ5WhgPMjEyNjA5
+MjQxMTUxNDlaMBkxFzAVBgNVBAMMDm1lZGlhLmhvbWUubGFuMFkwEwYHKoZIzj0C
+AQYIKoZIzj0DAQcDQgAEql9GSdiX5/zFxWCZ3SrAYU4e5rJFc+hqXCioQbE4WrB3
+7+ePlKHM1daUi0462sXu4Ux1i7Nr8t0+5mixgm1nyqOBjjCBizAJBgNVHRMEAjAA
+MBkGA1UdEQQSMBCCDm1lZGlhLmhvbWUubGFuMBMGA1UdJQQMMAoGCCsGAQUFBwMB
+MA4GA1UdDwEB/wQEAwIHgDAdBgNVHQ4EFgQUdF3lNsshVJ1CjQxtv4Unzb8gkbgw
+HwYDVR0jBBgwFoAUcmUEEoHbchpl9ZMY7HgVUWlV79YwCgYIKoZIzj0EAwIDSAAw
+RQIgQOWCH3oHtOPoLErUTaJXbc/Fo3sJavxbA+E0WbbHes8CIQCdgFlbjObbVKSG
+kPM6F3Es7Me+MmtjbjA94E7eYT7CJg==
+-----END CERTIFICATE-----
+";
+
+    // Self-signed jellyfin.home.lan
+    const SELF_SIGNED: &str = "-----BEGIN CERTIFICATE-----
+MIIBvTCCAWOgAwIBAgIUGs3CWasHTxA7JPyO09IZ5Uhem9MwCgYIKoZIzj0EAwIw
+HDEaMBgGA1UEAwwRamVsbHlmaW4uaG9tZS5sYW4wIBcNMjYxMDE4MTE1MTQ5WhgP
+MjEyNjA5MjQxMTUxNDlaMBwxGjAYBgNVBAMMEWplbGx5ZmluLmhvbWUubGFuMFkw
+EwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEisi/AbRINFV/8jSrb9uiWf6DeLkFZviq
+M0DhnAz3DNIkHVilKgSqAK48a+FIzxGhITyeUmOLmPgXA0MW8x4lGaOBgDB+MB0G
+A1UdDgQWBBQ9j8Lm0rYSxOK+f/mh7RIJ/APjwzAfBgNVHSMEGDAWgBQ9j8Lm0rYS
+xOK+f/mh7RIJ/APjwzAJBgNVHRMEAjAAMBwGA1UdEQQVMBOCEWplbGx5ZmluLmhv
+bWUubGFuMBMGA1UdJQQMMAoGCCsGAQUFBwMBMAoGCCqGSM49BAMCA0gAMEUCIQCb
+HuIItngTDL8TcqQ8NE4bqLi2u4UwsvOgdD+Kk0nniQIgNVZO6VS3VIMDgGZqIbjT
+G/dC58uZdo94qw6CbEu6p/A=
+-----END CERTIFICATE-----
+";
+
+    const SELF_SIGNED_SHA256: &str =
+        "55:A3:7C:16:45:DB:D0:EC:7A:B7:05:23:F9:3B:72:B8:74:DC:15:B7:46:16:E9:5D:AA:D1:14:25:9E:0D:F3:95";
+
+    fn der(pem: &str) -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(pem.as_bytes()).unwrap()
+    }
+
+    fn verify(settings: &TlsSettings, pem: &str, host: &str) -> Result<(), rustls::Error> {
+        settings
+            .verifier()
+            .unwrap()
+            .verify_server_cert(
+                &der(pem),
+                &[],
+                &ServerName::try_from(host.to_string()).unwrap(),
+                &[],
+                UnixTime::now(),
+            )
+            .map(|_| ())
+    }
+
+    // Trusts the test CA, so verification works without system roots
+    fn with_ca(dir: &std::path::Path) -> TlsSettings {
+        let path = dir.join("ca.pem");
+        fs::write(&path, CA).unwrap();
+        TlsSettings {
+            ca_files: vec![path],
+            ..Default::default()
+        }
+    }
+
+    mod verify_server_cert {
+        use super::*;
+
+        #[test]
+        fn trusts_certificates_from_extra_cas() {
+            let temp = tempdir().unwrap();
+            let settings = with_ca(temp.path());
+
+            assert!(verify(&settings, MEDIA, "media.home.lan").is_ok());
+            assert!(verify(&settings, MEDIA, "other.home.lan").is_err());
+            assert!(verify(&settings, SELF_SIGNED, "jellyfin.home.lan").is_err());
+        }
+
+        #[test]
+        fn trusts_pinned_certificates_only() {
+            let temp = tempdir().unwrap();
+            let mut settings = with_ca(temp.path());
+            settings.pinned_certificates = BTreeMap::from([
+                (
+                    "jellyfin.home.lan".to_string(),
+                    SELF_SIGNED_SHA256.to_string(),
+                ),
+                ("media.home.lan".to_string(), "00".repeat(32)),
+            ]);
+
+            assert!(verify(&settings, SELF_SIGNED, "jellyfin.home.lan").is_ok());
+            // A pin overrides the CA, so a valid certificate with another fingerprint fails
+            assert!(verify(&settings, MEDIA, "media.home.lan").is_err());
+        }
+
+        #[test]
+        fn accepts_anything_from_insecure_hosts() {
+            let temp = tempdir().unwrap();
+            let mut settings = with_ca(temp.path());
+            settings.insecure_hosts = vec!["*.home.lan".to_string()];
+
+            assert!(verify(&settings, SELF_SIGNED, "jellyfin.home.lan").is_ok());
+            assert!(verify(&settings, SELF_SIGNED, "jellyfin.example.com").is_err());
+        }
+
+        #[test]
+        fn prefers_the_most_specific_pin() {
+            let temp = tempdir().unwrap();
+            let mut settings = with_ca(temp.path());
+            settings.pinned_certificates = BTreeMap::from([
+                ("*.lan".to_string(), "00".repeat(32)),
+                ("*.home.lan".to_string(), "11".repeat(32)),
+                (
+                    "jellyfin.home.lan".to_string(),
+                    SELF_SIGNED_SHA256.to_string(),
+                ),
+            ]);
+
+            assert!(verify(&settings, SELF_SIGNED, "jellyfin.home.lan").is_ok());
+
+            settings.pinned_certificates.remove("jellyfin.home.lan");
+            settings
+                .pinned_certificates
+                .insert("*.home.lan".to_string(), SELF_SIGNED_SHA256.to_string());
+
+            assert!(verify(&settings, SELF_SIGNED, "jellyfin.home.lan").is_ok());
+        }
+    }
+
+    mod verifier {
+        use super::*;
+
+        #[test]
+        fn refuses_too_broad_insecure_hosts() {
+            for pattern in ["*", "*.com", "*.lan."] {
+                let settings = TlsSettings {
+                    insecure_hosts: vec![pattern.to_string()],
+                    ..Default::default()
+                };
+
+                assert!(settings.verifier().is_err(), "{}", pattern);
+            }
+            let settings = TlsSettings {
+                insecure_hosts: vec!["*.home.lan".to_string(), "navidrome".to_string()],
+                ..Default::default()
+            };
+
+            assert!(settings.verifier().is_ok());
+        }
+    }
+
+    mod ca_certificates {
+        use super::*;
+
+        #[test]
+        fn reads_every_certificate_in_each_file() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join("bundle.pem");
+            fs::write(&path, format!("{}{}", CA, MEDIA)).unwrap();
+            let settings = TlsSettings {
+                ca_files: vec![path],
+                ..Default::default()
+            };
+
+            assert_eq!(settings.ca_certificates().unwrap().len(), 2);
+        }
+
+        #[test]
+        fn rejects_files_without_certificates() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join("ca.pem");
+            fs::write(&path, "not a certificate").unwrap();
+            let settings = TlsSettings {
+                ca_files: vec![path],
+                ..Default::default()
+            };
+
+            assert!(settings.ca_certificates().is_err());
+        }
+    }
+
+    mod parse_fingerprint {
+        use super::*;
+
+        #[test]
+        fn accepts_colons_and_any_case() {
+            assert_eq!(
+                parse_fingerprint(SELF_SIGNED_SHA256).unwrap(),
+                parse_fingerprint(&SELF_SIGNED_SHA256.replace(':', "").to_lowercase()).unwrap()
+            );
+            assert!(parse_fingerprint("abcd").is_err());
+        }
+    }
+}
@@ -0,0 +1,577 @@
+pub mod signature;
+
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tauri::{command, AppHandle, Manager};
+
+use crate::caller::Callers;
+use crate::commands::{extract_archive, ExtractLimits};
+use crate::error::{AppError, AppResult, ErrorKind};
+use signature::{signature_path_for, SignatureStatus, TrustStore};
+
+// Installs plugin archives into `plugins/<name>/<version>` in the app data dir.
+// Each version gets its own directory, so an update leaves the previously active version in place
+// and the app can point back at it if the new one fails to load.
+// Archives are extracted and validated in `plugins/.staging`, then renamed into place. Whatever
+// was installed at the target before, i.e. the same version, is moved to `plugins/.backup` first
+// and restored if the swap fails.
+// The archive signature is checked against the trust store before anything is extracted.
+
+const PLUGINS_DIR_NAME: &str = "plugins";
+const STAGING_DIR_NAME: &str = ".staging";
+const BACKUP_DIR_NAME: &str = ".backup";
+const MANIFEST_FILE: &str = "package.json";
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledPlugin {
+    pub path: String,
+    pub manifest: serde_json::Value,
+    pub signature: SignatureStatus,
+}
+
+pub struct PluginInstaller {
+    plugins_dir: PathBuf,
+    trust: TrustStore,
+}
+
+fn invalid_manifest(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorKind::ParseError, message)
+}
+
+// Plugin names and versions become directories, so they may not escape the plugins dir or
+// collide with the hidden staging and backup dirs.
+// Scoped names like `@scope/plugin` are allowed and produce a nested directory.
+fn is_safe_relative_path(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('.')
+        && Path::new(value)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn required_field<'a>(manifest: &'a serde_json::Value, field: &str) -> AppResult<&'a str> {
+    let value = manifest
+        .get(field)
+        .and_then(serde_json::Value::as_str)
+        .map(str::trim)
+        .unwrap_or_default();
+
+    if value.is_empty() {
+        return Err(invalid_manifest(format!(
+            "Invalid {}: missing \"{}\"",
+            MANIFEST_FILE, field
+        )));
+    }
+    if !is_safe_relative_path(value) {
+        return Err(invalid_manifest(format!(
+            "Invalid {}: \"{}\" is not a valid {}",
+            MANIFEST_FILE, value, field
+        )));
+    }
+    Ok(value)
+}
+
+// Archives either have package.json at the root or wrap everything in a single directory.
+fn find_package_root(extracted: &Path) -> AppResult<PathBuf> {
+    if extracted.join(MANIFEST_FILE).is_file() {
+        return Ok(extracted.to_path_buf());
+    }
+
+    let entries: Vec<PathBuf> = fs::read_dir(extracted)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    if let [only] = entries.as_slice() {
+        if only.is_dir() && only.join(MANIFEST_FILE).is_file() {
+            return Ok(only.clone());
+        }
+    }
+
+    Err(invalid_manifest(format!(
+        "Archive does not contain a {}",
+        MANIFEST_FILE
+    )))
+}
+
+fn read_manifest(package_root: &Path) -> AppResult<serde_json::Value> {
+    let contents = fs::read_to_string(package_root.join(MANIFEST_FILE))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| invalid_manifest(format!("Invalid {}: {}", MANIFEST_FILE, e)))
+}
+
+// Moves `source` to `target`, parking anything already at `target` in `backup`.
+// If the final rename fails the previous install is moved back.
+fn swap_into_place(source: &Path, target: &Path, backup: &Path) -> AppResult<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let had_previous = target.exists();
+    if had_previous {
+        if backup.exists() {
+            fs::remove_dir_all(backup)?;
+        }
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(target, backup)?;
+    }
+
+    if let Err(e) = fs::rename(source, target) {
+        if had_previous {
+            if let Err(restore_error) = fs::rename(backup, target) {
+                error!(
+                    "[Plugins] Failed to restore {:?} from backup: {}",
+                    target, restore_error
+                );
+            }
+        }
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+impl PluginInstaller {
+    pub fn new(plugins_dir: PathBuf, trust: TrustStore) -> Self {
+        Self { plugins_dir, trust }
+    }
+
+    /// Installs `archive_path`, which must contain the plugin named `expected_name`.
+    /// The signature defaults to `<archive>.minisig` next to the archive.
+    pub fn install(
+        &self,
+        archive_path: &Path,
+        expected_name: &str,
+        signature_path: Option<&Path>,
+        limits: &ExtractLimits,
+    ) -> AppResult<InstalledPlugin> {
+        let signature_path = signature_path
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| signature_path_for(archive_path));
+        let signature = self.trust.verify(archive_path, &signature_path)?;
+        self.trust.enforce(&signature)?;
+
+        let staging = self
+            .plugins_dir
+            .join(STAGING_DIR_NAME)
+            .join(uuid::Uuid::new_v4().to_string());
+
+        let result =
+            self.install_from_staging(archive_path, expected_name, &staging, limits, signature);
+
+        if staging.exists() {
+            if let Err(e) = fs::remove_dir_all(&staging) {
+                warn!(
+                    "[Plugins] Failed to clean up staging dir {:?}: {}",
+                    staging, e
+                );
+            }
+        }
+        result
+    }
+
+    fn install_from_staging(
+        &self,
+        archive_path: &Path,
+        expected_name: &str,
+        staging: &Path,
+        limits: &ExtractLimits,
+        signature: SignatureStatus,
+    ) -> AppResult<InstalledPlugin> {
+        extract_archive(archive_path, staging, limits)?;
+
+        let package_root = find_package_root(staging)?;
+        let manifest = read_manifest(&package_root)?;
+        let name = required_field(&manifest, "name")?;
+        let version = required_field(&manifest, "version")?;
+        // Keeps a listing for one plugin from replacing another
+        if name != expected_name {
+            return Err(AppError::new(
+                ErrorKind::VerificationFailed,
+                format!("Archive contains {} instead of {}", name, expected_name),
+            ));
+        }
+
+        let target = self.plugins_dir.join(name).join(version);
+        let backup = self
+            .plugins_dir
+            .join(BACKUP_DIR_NAME)
+            .join(name)
+            .join(version);
+        debug!("[Plugins] Installing {}@{} to {:?}", name, version, target);
+
+        swap_into_place(&package_root, &target, &backup)?;
+        info!("[Plugins] Installed {}@{} to {:?}", name, version, target);
+
+        Ok(InstalledPlugin {
+            path: target.to_string_lossy().into_owned(),
+            manifest,
+            signature,
+        })
+    }
+}
+
+/// Reserved for the app, since installed plugins run with its privileges.
+#[command]
+pub async fn install_plugin_archive(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    archive_path: PathBuf,
+    expected_name: String,
+    signature_path: Option<PathBuf>,
+) -> AppResult<InstalledPlugin> {
+    callers.require_app(token.as_deref())?;
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(e.to_string()))?;
+    let trust = TrustStore::load(&app_data_dir.join(signature::TRUST_STORE_FILE))?;
+    let installer = PluginInstaller::new(app_data_dir.join(PLUGINS_DIR_NAME), trust);
+
+    tauri::async_runtime::spawn_blocking(move || {
+        installer.install(
+            &archive_path,
+            &expected_name,
+            signature_path.as_deref(),
+            &ExtractLimits::default(),
+        )
+    })
+    .await
+    .map_err(|e| AppError::unknown(format!("Plugin install task failed: {}", e)))?
+    .map_err(|e| {
+        error!("[Plugins] install_plugin_archive failed: {}", e);
+        e
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::{tempdir, TempDir};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const MANIFEST: &str = r#"{"name":"test-plugin","version":"1.0.0","main":"index.js"}"#;
+
+    fn create_archive(dir: &Path, files: &[(&str, &str)]) -> PathBuf {
+        let path = dir.join(format!("{}.zip", uuid::Uuid::new_v4()));
+        let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+        let options: FileOptions<'_, ()> = FileOptions::default();
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    fn installer() -> (TempDir, PluginInstaller) {
+        let temp = tempdir().unwrap();
+        let installer = PluginInstaller::new(temp.path().join("plugins"), TrustStore::default());
+        (temp, installer)
+    }
+
+    fn staging_is_empty(temp: &TempDir) -> bool {
+        let staging = temp.path().join("plugins").join(STAGING_DIR_NAME);
+        !staging.exists() || fs::read_dir(staging).unwrap().count() == 0
+    }
+
+    mod install {
+        use super::*;
+
+        #[test]
+        fn installs_to_name_and_version_dir() {
+            let (temp, installer) = installer();
+            let archive = create_archive(
+                temp.path(),
+                &[
+                    ("package.json", MANIFEST),
+                    ("index.js", "export default {}"),
+                ],
+            );
+
+            let installed = installer
+                .install(&archive, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+
+            let target = temp.path().join("plugins/test-plugin/1.0.0");
+            assert_eq!(installed.path, target.to_string_lossy());
+            assert_eq!(installed.manifest["name"], "test-plugin");
+            assert_eq!(
+                fs::read_to_string(target.join("index.js")).unwrap(),
+                "export default {}"
+            );
+            assert!(staging_is_empty(&temp));
+        }
+
+        #[test]
+        fn accepts_single_top_level_directory() {
+            let (temp, installer) = installer();
+            let archive = create_archive(
+                temp.path(),
+                &[
+                    ("test-plugin/package.json", MANIFEST),
+                    ("test-plugin/index.js", "code"),
+                ],
+            );
+
+            installer
+                .install(&archive, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+
+            assert!(temp
+                .path()
+                .join("plugins/test-plugin/1.0.0/index.js")
+                .is_file());
+        }
+
+        #[test]
+        fn rejects_archive_without_manifest() {
+            let (temp, installer) = installer();
+            let archive = create_archive(temp.path(), &[("index.js", "code")]);
+
+            let result =
+                installer.install(&archive, "test-plugin", None, &ExtractLimits::default());
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::ParseError);
+            assert!(error.message.contains("package.json"));
+            assert!(staging_is_empty(&temp));
+        }
+
+        #[test]
+        fn rejects_manifest_without_version() {
+            let (temp, installer) = installer();
+            let archive = create_archive(
+                temp.path(),
+                &[("package.json", r#"{"name":"test-plugin"}"#)],
+            );
+
+            let result =
+                installer.install(&archive, "test-plugin", None, &ExtractLimits::default());
+
+            assert!(result.unwrap_err().message.contains("\"version\""));
+            assert!(!temp.path().join("plugins/test-plugin").exists());
+            assert!(staging_is_empty(&temp));
+        }
+
+        #[test]
+        fn rejects_names_that_escape_plugins_dir() {
+            let (temp, installer) = installer();
+            let archive = create_archive(
+                temp.path(),
+                &[("package.json", r#"{"name":"../evil","version":"1.0.0"}"#)],
+            );
+
+            let result =
+                installer.install(&archive, "test-plugin", None, &ExtractLimits::default());
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::ParseError);
+            assert!(!temp.path().join("evil").exists());
+        }
+
+        #[test]
+        fn replaces_existing_install_and_keeps_backup() {
+            let (temp, installer) = installer();
+            let first = create_archive(
+                temp.path(),
+                &[("package.json", MANIFEST), ("index.js", "old")],
+            );
+            let second = create_archive(
+                temp.path(),
+                &[("package.json", MANIFEST), ("index.js", "new")],
+            );
+
+            installer
+                .install(&first, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+            installer
+                .install(&second, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+
+            let plugins = temp.path().join("plugins");
+            assert_eq!(
+                fs::read_to_string(plugins.join("test-plugin/1.0.0/index.js")).unwrap(),
+                "new"
+            );
+            assert_eq!(
+                fs::read_to_string(plugins.join(".backup/test-plugin/1.0.0/index.js")).unwrap(),
+                "old"
+            );
+        }
+
+        #[test]
+        fn keeps_the_previous_version_on_update() {
+            let (temp, installer) = installer();
+            let first = create_archive(
+                temp.path(),
+                &[("package.json", MANIFEST), ("index.js", "old")],
+            );
+            let second = create_archive(
+                temp.path(),
+                &[
+                    (
+                        "package.json",
+                        r#"{"name":"test-plugin","version":"2.0.0","main":"index.js"}"#,
+                    ),
+                    ("index.js", "new"),
+                ],
+            );
+
+            installer
+                .install(&first, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+            let installed = installer
+                .install(&second, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+
+            let plugin = temp.path().join("plugins/test-plugin");
+            assert_eq!(installed.path, plugin.join("2.0.0").to_string_lossy());
+            assert_eq!(
+                fs::read_to_string(plugin.join("1.0.0/index.js")).unwrap(),
+                "old"
+            );
+        }
+
+        #[test]
+        fn rejects_an_archive_of_another_plugin() {
+            let (temp, installer) = installer();
+            let archive = create_archive(temp.path(), &[("package.json", MANIFEST)]);
+
+            let result =
+                installer.install(&archive, "other-plugin", None, &ExtractLimits::default());
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::VerificationFailed);
+            assert!(error.message.contains("test-plugin"));
+            assert!(!temp.path().join("plugins/test-plugin").exists());
+            assert!(staging_is_empty(&temp));
+        }
+
+        #[test]
+        fn failed_update_leaves_existing_install_untouched() {
+            let (temp, installer) = installer();
+            let good = create_archive(
+                temp.path(),
+                &[("package.json", MANIFEST), ("index.js", "old")],
+            );
+            let broken = temp.path().join("broken.zip");
+            fs::write(&broken, b"not a zip").unwrap();
+
+            installer
+                .install(&good, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+            let result = installer.install(&broken, "test-plugin", None, &ExtractLimits::default());
+
+            assert!(result.is_err());
+            assert_eq!(
+                fs::read_to_string(temp.path().join("plugins/test-plugin/1.0.0/index.js")).unwrap(),
+                "old"
+            );
+            assert!(staging_is_empty(&temp));
+        }
+    }
+
+    mod signatures {
+        use super::*;
+        use crate::plugins::signature::test_vectors;
+
+        #[test]
+        fn reports_unsigned_archive_when_signatures_are_optional() {
+            let (temp, installer) = installer();
+            let archive = create_archive(temp.path(), &[("package.json", MANIFEST)]);
+
+            let installed = installer
+                .install(&archive, "test-plugin", None, &ExtractLimits::default())
+                .unwrap();
+
+            assert_eq!(installed.signature, SignatureStatus::Unsigned);
+        }
+
+        #[test]
+        fn rejects_unsigned_archive_before_extracting() {
+            let temp = tempdir().unwrap();
+            let trust = TrustStore {
+                require_signatures: true,
+                ..TrustStore::default()
+            };
+            let installer = PluginInstaller::new(temp.path().join("plugins"), trust);
+            let archive = create_archive(temp.path(), &[("package.json", MANIFEST)]);
+
+            let result =
+                installer.install(&archive, "test-plugin", None, &ExtractLimits::default());
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::Unsigned);
+            assert!(!temp.path().join("plugins").exists());
+        }
+
+        #[test]
+        fn rejects_signature_that_does_not_match() {
+            let temp = tempdir().unwrap();
+            let mut trust = TrustStore::default();
+            trust.add_key(test_vectors::PUBLIC_KEY, None).unwrap();
+            let installer = PluginInstaller::new(temp.path().join("plugins"), trust);
+            let archive = create_archive(temp.path(), &[("package.json", MANIFEST)]);
+            // A valid signature from the trusted key, but over a different payload
+            let signature = temp.path().join("custom.minisig");
+            fs::write(&signature, test_vectors::SIGNATURE).unwrap();
+
+            let result = installer.install(
+                &archive,
+                "test-plugin",
+                Some(&signature),
+                &ExtractLimits::default(),
+            );
+
+            let error = result.unwrap_err();
+            assert_eq!(error.kind, ErrorKind::BadSignature);
+            assert!(error.message.contains("does not match the archive"));
+            assert!(!temp.path().join("plugins/test-plugin").exists());
+        }
+    }
+
+    mod swap_into_place {
+        use super::*;
+
+        #[test]
+        fn restores_previous_install_when_rename_fails() {
+            let temp = tempdir().unwrap();
+            let target = temp.path().join("plugin/1.0.0");
+            let backup = temp.path().join(".backup/plugin/1.0.0");
+            fs::create_dir_all(&target).unwrap();
+            fs::write(target.join("index.js"), "old").unwrap();
+
+            let result = swap_into_place(&temp.path().join("missing"), &target, &backup);
+
+            assert!(result.is_err());
+            assert_eq!(fs::read_to_string(target.join("index.js")).unwrap(), "old");
+            assert!(!backup.exists());
+        }
+    }
+
+    mod is_safe_relative_path {
+        use super::*;
+
+        #[test]
+        fn allows_plain_and_scoped_names() {
+            assert!(is_safe_relative_path("plugin"));
+            assert!(is_safe_relative_path("@scope/plugin"));
+            assert!(is_safe_relative_path("1.0.0-beta.1"));
+        }
+
+        #[test]
+        fn rejects_traversal_and_absolute_paths() {
+            assert!(!is_safe_relative_path(""));
+            assert!(!is_safe_relative_path(".."));
+            assert!(!is_safe_relative_path("a/../../b"));
+            assert!(!is_safe_relative_path("/etc"));
+            assert!(!is_safe_relative_path("./plugin"));
+            assert!(!is_safe_relative_path(".staging"));
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{info, warn};
+use minisign_verify::{PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{command, AppHandle, Manager};
+
+use crate::caller::Callers;
+use crate::error::{AppError, AppResult, ErrorKind};
+
+// Minisign signature checks for plugin archives.
+// Signatures are detached `<archive>.minisig` files. The trust store is a JSON file in the app data
+// dir listing trusted public keys and whether unsigned or untrusted plugins may be installed at all.
+// Managed machines can ship a store with `requireSignatures` set and only their own key.
+
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+pub const TRUST_STORE_FILE: &str = "plugin-trusted-keys.json";
+
+// Serializes read-modify-write cycles of the trust store file across commands
+static TRUST_STORE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedKey {
+    pub key_id: String,
+    pub public_key: String,
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TrustStore {
+    pub require_signatures: bool,
+    pub keys: Vec<TrustedKey>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(
+    tag = "status",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum SignatureStatus {
+    Verified { key_id: String },
+    Unsigned,
+    // Validly formed signature from a key that is not in the trust store
+    Untrusted { key_id: String },
+}
+
+fn bad_signature(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorKind::BadSignature, message)
+}
+
+// Key IDs are stored little-endian after the two algorithm bytes, minisign prints them as hex.
+fn key_id_from_base64(encoded: &str) -> Option<String> {
+    let bytes = STANDARD.decode(encoded.trim()).ok()?;
+    let key_id: [u8; 8] = bytes.get(2..10)?.try_into().ok()?;
+    Some(format!("{:016X}", u64::from_le_bytes(key_id)))
+}
+
+/// Path of the detached signature for `archive_path`.
+pub fn signature_path_for(archive_path: &Path) -> PathBuf {
+    let mut file_name = archive_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(SIGNATURE_EXTENSION);
+    archive_path.with_file_name(file_name)
+}
+
+impl TrustStore {
+    pub fn load(path: &Path) -> AppResult<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| AppError::parse(format!("Invalid trust store {:?}: {}", path, e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Adds a key given either as the bare base64 line or the full contents of a `minisign.pub` file.
+    pub fn add_key(&mut self, public_key: &str, comment: Option<String>) -> AppResult<TrustedKey> {
+        let encoded = public_key
+            .lines()
+            .map(str::trim)
+            .rfind(|line| !line.is_empty() && !line.starts_with("untrusted comment:"))
+            .unwrap_or_default();
+        PublicKey::from_base64(encoded)
+            .map_err(|e| AppError::parse(format!("Invalid minisign public key: {}", e)))?;
+        let key_id = key_id_from_base64(encoded)
+            .ok_or_else(|| AppError::parse("Invalid minisign public key"))?;
+
+        let key = TrustedKey {
+            key_id: key_id.clone(),
+            public_key: encoded.to_string(),
+            comment,
+        };
+        self.keys.retain(|existing| existing.key_id != key_id);
+        self.keys.push(key.clone());
+        info!("[Plugins] Trusted signing key {}", key_id);
+        Ok(key)
+    }
+
+    pub fn remove_key(&mut self, key_id: &str) -> bool {
+        let before = self.keys.len();
+        self.keys
+            .retain(|key| !key.key_id.eq_ignore_ascii_case(key_id));
+        before != self.keys.len()
+    }
+
+    /// Checks `archive_path` against its detached signature. A signature that is present but does
+    /// not match the archive is always an error, the other outcomes are left to `enforce`.
+    pub fn verify(&self, archive_path: &Path, signature_path: &Path) -> AppResult<SignatureStatus> {
+        let signature_text = match fs::read_to_string(signature_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(SignatureStatus::Unsigned)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let signature = Signature::decode(&signature_text)
+            .map_err(|e| bad_signature(format!("Malformed signature: {}", e)))?;
+        let key_id = signature_text
+            .lines()
+            .nth(1)
+            .and_then(key_id_from_base64)
+            .ok_or_else(|| bad_signature("Malformed signature"))?;
+
+        let Some(trusted) = self.keys.iter().find(|key| key.key_id == key_id) else {
+            return Ok(SignatureStatus::Untrusted { key_id });
+        };
+
+        let public_key = PublicKey::from_base64(&trusted.public_key)
+            .map_err(|e| AppError::parse(format!("Invalid trusted key {}: {}", key_id, e)))?;
+        let mut verifier = public_key
+            .verify_stream(&signature)
+            .map_err(|e| bad_signature(format!("Unsupported signature: {}", e)))?;
+
+        let mut file = File::open(archive_path)?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            verifier.update(&buffer[..read]);
+        }
+
+        verifier.finalize().map_err(|_| {
+            bad_signature(format!(
+                "Signature from key {} does not match the archive",
+                key_id
+            ))
+        })?;
+
+        Ok(SignatureStatus::Verified { key_id })
+    }
+
+    /// Rejects unsigned and untrusted archives when signatures are required.
+    pub fn enforce(&self, status: &SignatureStatus) -> AppResult<()> {
+        match status {
+            SignatureStatus::Verified { .. } => Ok(()),
+            SignatureStatus::Unsigned if self.require_signatures => Err(AppError::new(
+                ErrorKind::Unsigned,
+                "Plugin archive is not signed",
+            )),
+            SignatureStatus::Untrusted { key_id } if self.require_signatures => Err(AppError::new(
+                ErrorKind::UnknownSigningKey,
+                format!("Plugin archive is signed by unknown key {}", key_id),
+            )),
+            status => {
+                warn!(
+                    "[Plugins] Installing plugin without a trusted signature: {:?}",
+                    status
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+fn trust_store_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    Ok(app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(e.to_string()))?
+        .join(TRUST_STORE_FILE))
+}
+
+fn update_trust_store<T>(
+    app_handle: &AppHandle,
+    update: impl FnOnce(&mut TrustStore) -> AppResult<T>,
+) -> AppResult<TrustStore> {
+    let path = trust_store_path(app_handle)?;
+    let _guard = TRUST_STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = TrustStore::load(&path)?;
+    update(&mut store)?;
+    store.save(&path)?;
+    Ok(store)
+}
+
+#[command]
+pub async fn plugin_trust_store(app_handle: AppHandle) -> AppResult<TrustStore> {
+    TrustStore::load(&trust_store_path(&app_handle)?)
+}
+
+// Changing what is trusted is reserved for the app's settings UI, plugins can only read the store.
+#[command]
+pub async fn plugin_trust_key(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    public_key: String,
+    comment: Option<String>,
+) -> AppResult<TrustStore> {
+    callers.require_app(token.as_deref())?;
+    update_trust_store(&app_handle, |store| store.add_key(&public_key, comment))
+}
+
+#[command]
+pub async fn plugin_untrust_key(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    key_id: String,
+) -> AppResult<TrustStore> {
+    callers.require_app(token.as_deref())?;
+    update_trust_store(&app_handle, |store| {
+        if store.remove_key(&key_id) {
+            info!("[Plugins] Removed trusted signing key {}", key_id);
+        }
+        Ok(())
+    })
+}
+
+#[command]
+pub async fn plugin_set_require_signatures(
+    app_handle: AppHandle,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    required: bool,
+) -> AppResult<TrustStore> {
+    callers.require_app(token.as_deref())?;
+    update_trust_store(&app_handle, |store| {
+        store.require_signatures = required;
+        Ok(())
+    })
+}
+
+/// Checks an archive without installing it. Mismatching signatures are returned as errors,
+/// unsigned and untrusted archives are reported through the status.
+#[command]
+pub async fn verify_plugin_archive(
+    app_handle: AppHandle,
+    archive_path: PathBuf,
+    signature_path: Option<PathBuf>,
+) -> AppResult<SignatureStatus> {
+    let store = TrustStore::load(&trust_store_path(&app_handle)?)?;
+    let signature_path = signature_path.unwrap_or_else(|| signature_path_for(&archive_path));
+
+    tauri::async_runtime::spawn_blocking(move || store.verify(&archive_path, &signature_path))
+        .await
+        .map_err(|e| AppError::unknown(format!("Signature check task failed: {}", e)))?
+}
+
+// Test vector from minisign-verify: a prehashed signature of the bytes "test".
+#[cfg(test)]
+pub(crate) mod test_vectors {
+    pub const PUBLIC_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+    pub const KEY_ID: &str = "E7620F1842B4E81F";
+    pub const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+    pub const OTHER_PUBLIC_KEY: &str = "RWQBAgMEBQYHCAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_vectors::*;
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+
+    fn archive(contents: &[u8], signed: bool) -> (TempDir, PathBuf) {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("plugin.zip");
+        fs::write(&path, contents).unwrap();
+        if signed {
+            fs::write(signature_path_for(&path), SIGNATURE).unwrap();
+        }
+        (temp, path)
+    }
+
+    fn trusting(public_key: &str) -> TrustStore {
+        let mut store = TrustStore::default();
+        store.add_key(public_key, None).unwrap();
+        store
+    }
+
+    mod verify {
+        use super::*;
+
+        #[test]
+        fn verifies_signature_from_trusted_key() {
+            let (_temp, path) = archive(b"test", true);
+
+            let status = trusting(PUBLIC_KEY)
+                .verify(&path, &signature_path_for(&path))
+                .unwrap();
+
+            assert_eq!(
+                status,
+                SignatureStatus::Verified {
+                    key_id: KEY_ID.to_string()
+                }
+            );
+        }
+
+        #[test]
+        fn reports_unsigned_archive() {
+            let (_temp, path) = archive(b"test", false);
+
+            let status = trusting(PUBLIC_KEY)
+                .verify(&path, &signature_path_for(&path))
+                .unwrap();
+
+            assert_eq!(status, SignatureStatus::Unsigned);
+        }
+
+        #[test]
+        fn reports_unknown_key() {
+            let (_temp, path) = archive(b"test", true);
+
+            let status = trusting(OTHER_PUBLIC_KEY)
+                .verify(&path, &signature_path_for(&path))
+                .unwrap();
+
+            assert_eq!(
+                status,
+                SignatureStatus::Untrusted {
+                    key_id: KEY_ID.to_string()
+                }
+            );
+        }
+
+        #[test]
+        fn rejects_tampered_archive() {
+            let (_temp, path) = archive(b"Test", true);
+
+            let result = trusting(PUBLIC_KEY).verify(&path, &signature_path_for(&path));
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::BadSignature);
+        }
+
+        #[test]
+        fn rejects_malformed_signature() {
+            let (_temp, path) = archive(b"test", false);
+            fs::write(signature_path_for(&path), "not a signature").unwrap();
+
+            let result = trusting(PUBLIC_KEY).verify(&path, &signature_path_for(&path));
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::BadSignature);
+        }
+    }
+
+    mod enforce {
+        use super::*;
+
+        fn untrusted() -> SignatureStatus {
+            SignatureStatus::Untrusted {
+                key_id: KEY_ID.to_string(),
+            }
+        }
+
+        #[test]
+        fn allows_anything_when_signatures_are_optional() {
+            let store = TrustStore::default();
+
+            assert!(store.enforce(&SignatureStatus::Unsigned).is_ok());
+            assert!(store.enforce(&untrusted()).is_ok());
+        }
+
+        #[test]
+        fn rejects_unsigned_and_untrusted_when_required() {
+            let store = TrustStore {
+                require_signatures: true,
+                ..trusting(PUBLIC_KEY)
+            };
+
+            assert_eq!(
+                store.enforce(&SignatureStatus::Unsigned).unwrap_err().kind,
+                ErrorKind::Unsigned
+            );
+            assert_eq!(
+                store.enforce(&untrusted()).unwrap_err().kind,
+                ErrorKind::UnknownSigningKey
+            );
+            assert!(store
+                .enforce(&SignatureStatus::Verified {
+                    key_id: KEY_ID.to_string()
+                })
+                .is_ok());
+        }
+    }
+
+    mod trust_store {
+        use super::*;
+
+        #[test]
+        fn accepts_minisign_pub_file_contents() {
+            let mut store = TrustStore::default();
+
+            let key = store
+                .add_key(
+                    &format!(
+                        "untrusted comment: minisign public key {}\n{}\n",
+                        KEY_ID, PUBLIC_KEY
+                    ),
+                    Some("Nuclear team".to_string()),
+                )
+                .unwrap();
+
+            assert_eq!(key.key_id, KEY_ID);
+            assert_eq!(key.public_key, PUBLIC_KEY);
+        }
+
+        #[test]
+        fn rejects_invalid_keys() {
+            let result = TrustStore::default().add_key("nonsense", None);
+
+            assert_eq!(result.unwrap_err().kind, ErrorKind::ParseError);
+        }
+
+        #[test]
+        fn round_trips_through_disk() {
+            let temp = tempdir().unwrap();
+            let path = temp.path().join(TRUST_STORE_FILE);
+            let store = TrustStore {
+                require_signatures: true,
+                ..trusting(PUBLIC_KEY)
+            };
+
+            store.save(&path).unwrap();
+
+            assert_eq!(TrustStore::load(&path).unwrap(), store);
+        }
+
+        #[test]
+        fn defaults_when_missing() {
+            let temp = tempdir().unwrap();
+
+            let store = TrustStore::load(&temp.path().join(TRUST_STORE_FILE)).unwrap();
+
+            assert_eq!(store, TrustStore::default());
+        }
+
+        #[test]
+        fn removes_keys_by_id() {
+            let mut store = trusting(PUBLIC_KEY);
+
+            assert!(store.remove_key(&KEY_ID.to_lowercase()));
+            assert!(store.keys.is_empty());
+            assert!(!store.remove_key(KEY_ID));
+        }
+    }
+}
@@ -1,12 +1,20 @@
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use http::{header, HeaderMap, HeaderName, Response, StatusCode};
-use log::{debug, error, warn};
+use futures::future::{BoxFuture, Shared};
+use futures::{FutureExt, StreamExt};
+use http::{header, HeaderMap, Response, StatusCode};
+use log::{debug, error, info, warn};
 use once_cell::sync::Lazy;
+use percent_encoding::percent_decode_str;
 use reqwest::Client;
-use std::sync::Arc;
-use tauri::{AppHandle, Runtime, UriSchemeResponder};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, Runtime, UriSchemeResponder};
 use tokio::runtime::Runtime as TokioRuntime;
 
+use crate::audio_cache::{AudioCache, CachedRange};
+use crate::network::{self, NetworkSettings};
+use crate::ytdlp::{self, YtdlpFormatPreference};
+
 // The purpose of this module is to bypass CORS restrictions in Tauri by streaming audio through a fake local URI scheme.
 // Audio gets streamed from the original URL to this Rust module, which then passes it back to the Tauri frontend with replaced CORS headers.
 
@@ -15,25 +23,37 @@ const CORS_ALLOW_METHODS: &str = "Access-Control-Allow-Methods";
 const CORS_ALLOW_HEADERS: &str = "Access-Control-Allow-Headers";
 const CORS_EXPOSE_HEADERS: &str = "Access-Control-Expose-Headers";
 
-const FORWARD_HEADERS: &[HeaderName] = &[
-    header::CONTENT_TYPE,
-    header::CONTENT_LENGTH,
-    header::CONTENT_RANGE,
-    header::ACCEPT_RANGES,
-];
+// Upper bound on how much of the upstream body is held in memory per request.
+// Larger ranges get a shorter 206 response and the media element asks for the rest.
+const MAX_RANGE_BYTES: u64 = 2 * 1024 * 1024;
 
 // User agent matching yt-dlp's client.
 // Required for YouTube URLs - they validate the UA matches what generated the signed URL.
 const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/138.0.0.0 Safari/537.36";
 
-static HTTP_CLIENT: Lazy<Arc<Client>> = Lazy::new(|| {
-    Arc::new(
-        Client::builder()
-            .user_agent(USER_AGENT)
-            .build()
-            .expect("Failed to create HTTP client"),
-    )
-});
+// Built from the network settings it was created with, and rebuilt when they change
+static HTTP_CLIENT: Lazy<Mutex<Option<(NetworkSettings, Client)>>> = Lazy::new(Mutex::default);
+
+fn http_client(network: NetworkSettings) -> Result<Client, (StatusCode, String)> {
+    let mut cached = HTTP_CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((settings, client)) = cached.as_ref() {
+        if *settings == network {
+            return Ok(client.clone());
+        }
+    }
+
+    let client = network
+        .client_builder()
+        .and_then(|builder| Ok(builder.user_agent(USER_AGENT).build()?))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to create HTTP client: {}", e),
+            )
+        })?;
+    *cached = Some((network, client.clone()));
+    Ok(client)
+}
 
 static RUNTIME: Lazy<TokioRuntime> =
     Lazy::new(|| TokioRuntime::new().expect("Failed to create tokio runtime"));
@@ -73,20 +93,595 @@ fn decode_stream_url(encoded: &str) -> Result<String, (StatusCode, String)> {
     Ok(url)
 }
 
-fn forward_headers(
-    mut builder: http::response::Builder,
-    source: &HeaderMap,
-) -> http::response::Builder {
-    for name in FORWARD_HEADERS {
-        if let Some(value) = source.get(name) {
-            builder = builder.header(name, value.to_str().unwrap_or(""));
+/// A `bytes=start-[end]` range as sent by the media element.
+#[derive(Debug, PartialEq)]
+struct ByteRange {
+    start: u64,
+    end: Option<u64>,
+}
+
+/// Parses the incoming Range header. Multi-part ranges are served as their first part.
+/// Returns `None` for suffix ranges, see `parse_suffix_range`.
+fn parse_range(header: &str) -> Option<ByteRange> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let spec = spec.split(',').next()?.trim();
+
+    let (start, end) = spec.split_once('-')?;
+    let start = start.trim().parse::<u64>().ok()?;
+    let end = match end.trim() {
+        "" => None,
+        end => Some(end.parse::<u64>().ok()?),
+    };
+
+    if end.is_some_and(|end| end < start) {
+        return None;
+    }
+
+    Some(ByteRange { start, end })
+}
+
+/// The length of a `bytes=-length` range, the last `length` bytes of the track.
+fn parse_suffix_range(header: &str) -> Option<u64> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    let length = spec.split(',').next()?.trim().strip_prefix('-')?;
+    length.trim().parse().ok().filter(|length| *length > 0)
+}
+
+/// Clamps a requested range to at most `MAX_RANGE_BYTES`, returning the inclusive end offset.
+fn window_end(range: &ByteRange) -> u64 {
+    let max_end = range.start.saturating_add(MAX_RANGE_BYTES - 1);
+    range.end.map_or(max_end, |end| end.min(max_end))
+}
+
+/// Parses an upstream `Content-Range: bytes start-end/total` header into `(start, total)`.
+fn parse_content_range(headers: &HeaderMap) -> Option<(u64, Option<u64>)> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.parse().ok()?, total.parse().ok()))
+}
+
+/// The total size from an upstream 416's `Content-Range: bytes */total` header.
+fn parse_unsatisfied_range(headers: &HeaderMap) -> Option<u64> {
+    let value = headers.get(header::CONTENT_RANGE)?.to_str().ok()?;
+    value.trim().strip_prefix("bytes */")?.parse().ok()
+}
+
+/// Reads the upstream body chunk by chunk, discarding the first `skip` bytes and stopping
+/// once `limit` bytes have been collected. The upstream connection is dropped at that point,
+/// so no more than `limit` bytes plus one chunk are ever held.
+async fn read_window<S, B, E>(mut stream: S, skip: u64, limit: u64) -> Result<Vec<u8>, E>
+where
+    S: futures::Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    let mut body = Vec::with_capacity(limit as usize);
+    let mut to_skip = skip;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let mut chunk = chunk.as_ref();
+
+        if to_skip > 0 {
+            let skipped = to_skip.min(chunk.len() as u64);
+            to_skip -= skipped;
+            chunk = &chunk[skipped as usize..];
+        }
+
+        let remaining = (limit - body.len() as u64) as usize;
+        body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+
+        if body.len() as u64 >= limit {
+            break;
+        }
+    }
+
+    Ok(body)
+}
+
+fn cors_response_builder(status: StatusCode) -> http::response::Builder {
+    Response::builder()
+        .status(status.as_u16())
+        .header(CORS_ALLOW_ORIGIN, "*")
+        .header(CORS_ALLOW_METHODS, "GET, HEAD, OPTIONS")
+        .header(CORS_ALLOW_HEADERS, "Range, Content-Type")
+        .header(
+            CORS_EXPOSE_HEADERS,
+            "Content-Length, Content-Range, Accept-Ranges",
+        )
+}
+
+/// A slice of a track, fetched from upstream or read back from the audio cache.
+struct Window {
+    start: u64,
+    total: Option<u64>,
+    content_type: Option<String>,
+    body: Vec<u8>,
+}
+
+fn window_response(window: Window) -> Response<Vec<u8>> {
+    let mut builder = cors_response_builder(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_LENGTH, window.body.len())
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    if let Some(content_type) = &window.content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+
+    if !window.body.is_empty() {
+        let end = window.start + window.body.len() as u64 - 1;
+        let total = window.total.unwrap_or(end + 1);
+        builder = builder.header(
+            header::CONTENT_RANGE,
+            format!("bytes {}-{}/{}", window.start, end, total),
+        );
+    }
+
+    builder.body(window.body).unwrap()
+}
+
+/// A 416 for a range starting at or past the end of the track.
+fn unsatisfiable_response(total: Option<u64>) -> Response<Vec<u8>> {
+    let mut builder = cors_response_builder(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(header::ACCEPT_RANGES, "bytes");
+    if let Some(total) = total {
+        builder = builder.header(header::CONTENT_RANGE, format!("bytes */{}", total));
+    }
+    builder.body(Vec::new()).unwrap()
+}
+
+/// What a yt-dlp stream URL was resolved from, so an expired URL is resolved again the same way.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackedStream {
+    // The page yt-dlp resolved the stream from
+    pub page_url: String,
+    pub format: YtdlpFormatPreference,
+}
+
+/// Resolves a fresh stream URL for a page whose signed stream URL has expired.
+pub trait StreamResolver: Send + Sync {
+    fn resolve(&self, stream: &TrackedStream) -> Result<String, String>;
+}
+
+// Caps each map so it can't grow without bound over a long session.
+const MAX_TRACKED_STREAMS: usize = 1000;
+
+/// A string-keyed map holding at most `MAX_TRACKED_STREAMS` entries, dropping the least
+/// recently used one when full so streams that are still playing stay tracked.
+struct RecentMap<V> {
+    entries: HashMap<String, (V, u64)>,
+    clock: u64,
+}
+
+impl<V> Default for RecentMap<V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            clock: 0,
         }
     }
-    builder
+}
+
+impl<V> RecentMap<V> {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn get(&mut self, key: &str) -> Option<&V> {
+        let clock = self.tick();
+        let (value, last_used) = self.entries.get_mut(key)?;
+        *last_used = clock;
+        Some(value)
+    }
+
+    fn insert(&mut self, key: String, value: V) {
+        let clock = self.tick();
+        if !self.entries.contains_key(&key) && self.entries.len() >= MAX_TRACKED_STREAMS {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (value, clock));
+    }
+}
+
+type PendingRefresh = Shared<BoxFuture<'static, Result<String, String>>>;
+
+// Signed yt-dlp URLs expire after a few hours. Remembering which page each URL was resolved from
+// lets the proxy re-resolve it transparently, and later requests for the old URL go to the fresh one.
+// Concurrent requests for the same expired URL share a single re-resolution.
+#[derive(Default)]
+struct YtdlpStreams {
+    tracked: RecentMap<TrackedStream>,
+    refreshed: RecentMap<String>,
+    pending: HashMap<String, PendingRefresh>,
+}
+
+static YTDLP_STREAMS: Lazy<Mutex<YtdlpStreams>> = Lazy::new(Mutex::default);
+
+fn ytdlp_streams() -> std::sync::MutexGuard<'static, YtdlpStreams> {
+    YTDLP_STREAMS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn register_ytdlp_stream(stream_url: &str, page_url: &str, format: &YtdlpFormatPreference) {
+    ytdlp_streams().tracked.insert(
+        stream_url.to_string(),
+        TrackedStream {
+            page_url: page_url.to_string(),
+            format: format.clone(),
+        },
+    );
+}
+
+struct Refresh {
+    stream: TrackedStream,
+    resolver: Arc<dyn StreamResolver>,
+}
+
+/// The upstream side of a proxied stream.
+struct Upstream {
+    // The URL encoded in the nuclear-stream request, which stays the same after re-resolution.
+    requested_url: String,
+    url: String,
+    refresh: Option<Refresh>,
+}
+
+impl Upstream {
+    fn new(requested_url: String, resolver: Arc<dyn StreamResolver>) -> Self {
+        let mut streams = ytdlp_streams();
+        let url = streams
+            .refreshed
+            .get(&requested_url)
+            .cloned()
+            .unwrap_or_else(|| requested_url.clone());
+        let refresh = streams.tracked.get(&url).map(|stream| Refresh {
+            stream: stream.clone(),
+            resolver,
+        });
+
+        Self {
+            requested_url,
+            url,
+            refresh,
+        }
+    }
+
+    async fn refresh(&self, refresh: &Refresh) -> Result<String, (StatusCode, String)> {
+        let pending = {
+            let mut streams = ytdlp_streams();
+            // Another request may already have replaced the URL this one started with
+            if let Some(fresh_url) = streams.refreshed.get(&self.requested_url) {
+                if *fresh_url != self.url {
+                    return Ok(fresh_url.clone());
+                }
+            }
+
+            streams
+                .pending
+                .entry(self.url.clone())
+                .or_insert_with(|| {
+                    let resolver = refresh.resolver.clone();
+                    let stream = refresh.stream.clone();
+                    async move {
+                        tokio::task::spawn_blocking(move || resolver.resolve(&stream))
+                            .await
+                            .map_err(|e| e.to_string())
+                            .and_then(|result| result)
+                    }
+                    .boxed()
+                    .shared()
+                })
+                .clone()
+        };
+
+        let result = pending.await;
+
+        let mut streams = ytdlp_streams();
+        streams.pending.remove(&self.url);
+        let fresh_url = result.map_err(|e| {
+            error!(
+                "[StreamProxy] Failed to re-resolve {}: {}",
+                refresh.stream.page_url, e
+            );
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to re-resolve expired stream: {}", e),
+            )
+        })?;
+
+        streams
+            .tracked
+            .insert(fresh_url.clone(), refresh.stream.clone());
+        streams
+            .refreshed
+            .insert(self.requested_url.clone(), fresh_url.clone());
+
+        Ok(fresh_url)
+    }
+}
+
+async fn send_request(
+    client: &Client,
+    url: &str,
+    range: Option<&str>,
+) -> Result<reqwest::Response, (StatusCode, String)> {
+    let mut req_builder = client.get(url);
+
+    if let Some(range) = range {
+        req_builder = req_builder.header(header::RANGE, range);
+    }
+
+    req_builder.send().await.map_err(|e| {
+        error!("[StreamProxy] Request failed: {}", e);
+        (StatusCode::BAD_GATEWAY, format!("Failed to fetch: {}", e))
+    })
+}
+
+/// Sends the request upstream. If a yt-dlp URL has expired, it is re-resolved and the same
+/// range is requested again from the fresh URL.
+async fn send_upstream(
+    client: &Client,
+    upstream: &Upstream,
+    range: Option<&str>,
+) -> Result<reqwest::Response, (StatusCode, String)> {
+    let mut response = send_request(client, &upstream.url, range).await?;
+    let mut status = response.status();
+
+    if let (StatusCode::FORBIDDEN | StatusCode::GONE, Some(refresh)) = (status, &upstream.refresh) {
+        info!(
+            "[StreamProxy] Stream for {} expired ({}), re-resolving",
+            refresh.stream.page_url, status
+        );
+        let fresh_url = upstream.refresh(refresh).await?;
+        response = send_request(client, &fresh_url, range).await?;
+        status = response.status();
+    }
+
+    // A 416 is answered as such, not as an upstream failure
+    if !status.is_success() && status != StatusCode::RANGE_NOT_SATISFIABLE {
+        warn!("[StreamProxy] Upstream returned: {}", status);
+        return Err((
+            StatusCode::BAD_GATEWAY,
+            format!("Upstream returned error: {}", status),
+        ));
+    }
+
+    Ok(response)
+}
+
+fn read_error(e: reqwest::Error) -> (StatusCode, String) {
+    error!("[StreamProxy] Failed to read body: {}", e);
+    (
+        StatusCode::BAD_GATEWAY,
+        format!("Failed to read response: {}", e),
+    )
+}
+
+/// The part of the track a window is fetched for.
+enum WindowRange {
+    // Inclusive `start..=end`
+    From { start: u64, end: u64 },
+    // The last `length` bytes, of which at most `MAX_RANGE_BYTES` are read
+    Suffix { length: u64 },
+}
+
+impl WindowRange {
+    fn header(&self) -> String {
+        match self {
+            WindowRange::From { start, end } => format!("bytes={}-{}", start, end),
+            WindowRange::Suffix { length } => format!("bytes=-{}", length),
+        }
+    }
+
+    fn limit(&self) -> u64 {
+        match self {
+            WindowRange::From { start, end } => end - start + 1,
+            WindowRange::Suffix { length } => (*length).min(MAX_RANGE_BYTES),
+        }
+    }
+
+    // Where the window starts in a body of `total` bytes sent from offset 0
+    fn start(&self, total: Option<u64>) -> u64 {
+        match self {
+            WindowRange::From { start, .. } => *start,
+            WindowRange::Suffix { length } => {
+                total.map_or(0, |total| total.saturating_sub(*length))
+            }
+        }
+    }
+}
+
+/// Fetches `range` from upstream, reading no more than the window even when upstream ignores
+/// the Range header and sends the whole file from offset 0.
+async fn fetch_window(
+    client: &Client,
+    upstream: &Upstream,
+    range: &WindowRange,
+) -> Result<Window, (StatusCode, String)> {
+    let response = send_upstream(client, upstream, Some(&range.header())).await?;
+
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
+    let (body_start, total, skip) = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            let (body_start, total) =
+                parse_content_range(response.headers()).unwrap_or((range.start(None), None));
+            (body_start, total, 0)
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            return Ok(Window {
+                start: range.start(None),
+                total: parse_unsatisfied_range(response.headers()),
+                content_type,
+                body: Vec::new(),
+            });
+        }
+        _ => {
+            let total = response.content_length();
+            let start = range.start(total);
+            (start, total, start)
+        }
+    };
+
+    let body = read_window(response.bytes_stream(), skip, range.limit())
+        .await
+        .map_err(read_error)?;
+
+    debug!(
+        "[StreamProxy] Got {} bytes at offset {}",
+        body.len(),
+        body_start
+    );
+
+    Ok(Window {
+        start: body_start,
+        total,
+        content_type,
+        body,
+    })
+}
+
+/// Where the windows of a proxied stream are cached, keyed on the track rather than its URL.
+struct CacheTarget {
+    cache: AudioCache,
+    key: String,
+}
+
+// The cache does blocking file I/O, so it runs on the blocking pool rather than the runtime.
+impl CacheTarget {
+    async fn read(&self, start: u64, max_len: u64) -> Option<CachedRange> {
+        let cache = self.cache.clone();
+        let key = self.key.clone();
+        tokio::task::spawn_blocking(move || cache.read(&key, start, max_len))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    async fn next_cached_offset(&self, start: u64) -> Option<u64> {
+        let cache = self.cache.clone();
+        let key = self.key.clone();
+        tokio::task::spawn_blocking(move || cache.next_cached_offset(&key, start))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    async fn write(&self, window: Window) -> Result<Window, (StatusCode, String)> {
+        let cache = self.cache.clone();
+        let key = self.key.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            let result = cache.write(
+                &key,
+                window.start,
+                &window.body,
+                window.total,
+                window.content_type.as_deref(),
+            );
+            (window, result)
+        })
+        .await;
+
+        let (window, result) = written.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Cache write task failed: {}", e),
+            )
+        })?;
+        if let Err(e) = result {
+            warn!("[StreamProxy] Failed to cache window: {}", e);
+        }
+        Ok(window)
+    }
+}
+
+/// Serves at most `MAX_RANGE_BYTES` of the requested range as a 206 response, so the webview
+/// starts playback after the first window instead of the whole file. Cached bytes are served
+/// from disk and only the gap up to the next cached range is fetched from upstream. Requests
+/// without a usable Range header get the first window, as if they had asked for `bytes=0-`,
+/// and the media element asks for the rest.
+async fn proxy_request(
+    client: &Client,
+    cache: Option<&CacheTarget>,
+    upstream: &Upstream,
+    range_header: Option<&str>,
+) -> Result<Response<Vec<u8>>, (StatusCode, String)> {
+    if let Some(length) = range_header.and_then(parse_suffix_range) {
+        let window = fetch_window(client, upstream, &WindowRange::Suffix { length }).await?;
+        return respond_with_window(cache, window).await;
+    }
+
+    let range = range_header.and_then(parse_range).unwrap_or(ByteRange {
+        start: 0,
+        end: None,
+    });
+    let mut end = window_end(&range);
+
+    if let Some(target) = cache {
+        if let Some(hit) = target.read(range.start, end - range.start + 1).await {
+            debug!("[StreamProxy] Cache hit at offset {}", range.start);
+            return Ok(window_response(Window {
+                start: range.start,
+                total: hit.total,
+                content_type: hit.content_type,
+                body: hit.body,
+            }));
+        }
+
+        if let Some(next_cached) = target.next_cached_offset(range.start).await {
+            end = end.min(next_cached - 1);
+        }
+    }
+
+    let window = fetch_window(
+        client,
+        upstream,
+        &WindowRange::From {
+            start: range.start,
+            end,
+        },
+    )
+    .await?;
+    respond_with_window(cache, window).await
+}
+
+// Caches a window fetched from upstream and answers with it, or with a 416 when it's empty
+async fn respond_with_window(
+    cache: Option<&CacheTarget>,
+    window: Window,
+) -> Result<Response<Vec<u8>>, (StatusCode, String)> {
+    if window.body.is_empty() {
+        debug!("[StreamProxy] Range starts past the end: {}", window.start);
+        return Ok(unsatisfiable_response(window.total));
+    }
+
+    let window = match cache {
+        Some(target) => target.write(window).await?,
+        None => window,
+    };
+
+    Ok(window_response(window))
+}
+
+fn query_param(uri: &http::Uri, name: &str) -> Option<String> {
+    uri.query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| percent_decode_str(value).decode_utf8_lossy().into_owned())
+    })
 }
 
 pub fn handle_stream_request<R: Runtime>(
-    _app: &AppHandle<R>,
+    app: &AppHandle<R>,
     request: http::Request<Vec<u8>>,
     responder: UriSchemeResponder,
 ) {
@@ -109,67 +704,746 @@ pub fn handle_stream_request<R: Runtime>(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    let cache_target = query_param(uri, "cache_key").and_then(|key| {
+        app.try_state::<AudioCache>().map(|cache| CacheTarget {
+            cache: cache.inner().clone(),
+            key,
+        })
+    });
+
     debug!("[StreamProxy] Fetching URL, range: {:?}", range_header);
 
-    let client = HTTP_CLIENT.clone();
+    let client = match http_client(network::current(app)) {
+        Ok(client) => client,
+        Err((status, message)) => {
+            error!("[StreamProxy] {}", message);
+            return respond_error(responder, status, message);
+        }
+    };
+    let resolver = ytdlp::stream_resolver(app);
 
     RUNTIME.spawn(async move {
-        let mut req_builder = client.get(&url);
-
-        if let Some(ref range) = range_header {
-            req_builder = req_builder.header(header::RANGE, range);
+        match proxy_request(
+            &client,
+            cache_target.as_ref(),
+            &Upstream::new(url, resolver),
+            range_header.as_deref(),
+        )
+        .await
+        {
+            Ok(response) => responder.respond(response),
+            Err((status, message)) => respond_error(responder, status, message),
         }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
 
-        match req_builder.send().await {
-            Ok(response) => {
-                let status = response.status();
-                let headers = response.headers().clone();
+    const TRACK_SIZE: usize = 8 * 1024 * 1024;
+    const UPSTREAM_CHUNK: usize = 64 * 1024;
+    const PAGE_URL: &str = "https://www.youtube.com/watch?v=abc";
 
-                if !status.is_success() && status != StatusCode::PARTIAL_CONTENT {
-                    warn!("[StreamProxy] Upstream returned: {}", status);
-                    return respond_error(
-                        responder,
-                        StatusCode::BAD_GATEWAY,
-                        format!("Upstream returned error: {}", status),
-                    );
+    fn track_bytes() -> Vec<u8> {
+        (0..TRACK_SIZE).map(|i| (i % 251) as u8).collect()
+    }
+
+    async fn serve(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}/track", addr)
+    }
+
+    // Stand-in for a CDN that honors Range requests, counting how many it receives.
+    async fn ranged_upstream_with_counter(requests: Arc<AtomicUsize>) -> String {
+        let router = Router::new().route(
+            "/track",
+            get(move |headers: HeaderMap| async move {
+                requests.fetch_add(1, Ordering::SeqCst);
+                let track = track_bytes();
+                let Some(range) = headers
+                    .get(header::RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_range)
+                else {
+                    return Response::builder()
+                        .header(header::CONTENT_TYPE, "audio/mp4")
+                        .body(Body::from(track))
+                        .unwrap();
+                };
+                if range.start >= TRACK_SIZE as u64 {
+                    return Response::builder()
+                        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                        .header(header::CONTENT_RANGE, format!("bytes */{}", TRACK_SIZE))
+                        .body(Body::empty())
+                        .unwrap();
                 }
+                let end = range
+                    .end
+                    .unwrap_or(TRACK_SIZE as u64 - 1)
+                    .min(TRACK_SIZE as u64 - 1);
+                Response::builder()
+                    .status(StatusCode::PARTIAL_CONTENT)
+                    .header(header::CONTENT_TYPE, "audio/mp4")
+                    .header(
+                        header::CONTENT_RANGE,
+                        format!("bytes {}-{}/{}", range.start, end, TRACK_SIZE),
+                    )
+                    .body(Body::from(
+                        track[range.start as usize..=end as usize].to_vec(),
+                    ))
+                    .unwrap()
+            }),
+        );
+        serve(router).await
+    }
 
-                match response.bytes().await {
-                    Ok(body) => {
-                        debug!("[StreamProxy] Got {} bytes", body.len());
+    async fn ranged_upstream() -> String {
+        ranged_upstream_with_counter(Arc::new(AtomicUsize::new(0))).await
+    }
 
-                        let resp_builder = Response::builder()
-                            .status(status.as_u16())
-                            .header(CORS_ALLOW_ORIGIN, "*")
-                            .header(CORS_ALLOW_METHODS, "GET, HEAD, OPTIONS")
-                            .header(CORS_ALLOW_HEADERS, "Range, Content-Type")
-                            .header(
-                                CORS_EXPOSE_HEADERS,
-                                "Content-Length, Content-Range, Accept-Ranges",
-                            );
+    // Stand-in for a server that ignores Range and streams the whole file.
+    async fn unranged_upstream() -> String {
+        let router = Router::new().route(
+            "/track",
+            get(|| async move {
+                let chunks = track_bytes()
+                    .chunks(UPSTREAM_CHUNK)
+                    .map(|chunk| chunk.to_vec())
+                    .collect::<Vec<_>>();
+                let stream = futures::stream::iter(chunks).map(Ok::<_, std::io::Error>);
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "audio/mp4")
+                    .header(header::CONTENT_LENGTH, TRACK_SIZE)
+                    .body(Body::from_stream(stream))
+                    .unwrap()
+            }),
+        );
+        serve(router).await
+    }
 
-                        let resp_builder = forward_headers(resp_builder, &headers);
+    struct FakeResolver {
+        fresh_url: String,
+        calls: AtomicUsize,
+    }
 
-                        responder.respond(resp_builder.body(body.to_vec()).unwrap());
-                    }
-                    Err(e) => {
-                        error!("[StreamProxy] Failed to read body: {}", e);
-                        respond_error(
-                            responder,
-                            StatusCode::BAD_GATEWAY,
-                            format!("Failed to read response: {}", e),
-                        );
-                    }
-                }
+    impl StreamResolver for FakeResolver {
+        fn resolve(&self, _stream: &TrackedStream) -> Result<String, String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(self.fresh_url.clone())
+        }
+    }
+
+    // Takes long enough that concurrent requests overlap while it runs.
+    struct SlowResolver {
+        fresh_url: String,
+        calls: AtomicUsize,
+    }
+
+    impl StreamResolver for SlowResolver {
+        fn resolve(&self, _stream: &TrackedStream) -> Result<String, String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            Ok(self.fresh_url.clone())
+        }
+    }
+
+    // Remembers what it was asked to resolve.
+    struct RecordingResolver {
+        fresh_url: String,
+        streams: Mutex<Vec<TrackedStream>>,
+    }
+
+    impl StreamResolver for RecordingResolver {
+        fn resolve(&self, stream: &TrackedStream) -> Result<String, String> {
+            self.streams.lock().unwrap().push(stream.clone());
+            Ok(self.fresh_url.clone())
+        }
+    }
+
+    struct FailingResolver;
+
+    impl StreamResolver for FailingResolver {
+        fn resolve(&self, stream: &TrackedStream) -> Result<String, String> {
+            Err(format!(
+                "ERROR: [youtube] {}: Video unavailable",
+                stream.page_url
+            ))
+        }
+    }
+
+    fn upstream(url: &str) -> Upstream {
+        Upstream::new(url.to_string(), Arc::new(FailingResolver))
+    }
+
+    fn header_str(response: &Response<Vec<u8>>, name: header::HeaderName) -> &str {
+        response.headers().get(name).unwrap().to_str().unwrap()
+    }
+
+    mod parse_range {
+        use super::*;
+
+        #[test]
+        fn parses_open_ended_range() {
+            assert_eq!(
+                parse_range("bytes=1024-"),
+                Some(ByteRange {
+                    start: 1024,
+                    end: None
+                })
+            );
+        }
+
+        #[test]
+        fn parses_closed_range() {
+            assert_eq!(
+                parse_range("bytes=0-1"),
+                Some(ByteRange {
+                    start: 0,
+                    end: Some(1)
+                })
+            );
+        }
+
+        #[test]
+        fn takes_the_first_part_of_multipart_ranges() {
+            assert_eq!(
+                parse_range("bytes=0-1, 5-9"),
+                Some(ByteRange {
+                    start: 0,
+                    end: Some(1)
+                })
+            );
+        }
+
+        #[test]
+        fn rejects_suffix_and_reversed_ranges() {
+            assert_eq!(parse_range("bytes=-500"), None);
+            assert_eq!(parse_range("bytes=9-5"), None);
+            assert_eq!(parse_suffix_range("bytes=-500"), Some(500));
+            assert_eq!(parse_suffix_range("bytes=0-500"), None);
+        }
+    }
+
+    mod window_end {
+        use super::*;
+
+        #[test]
+        fn saturates_at_the_largest_offset() {
+            let range = ByteRange {
+                start: u64::MAX,
+                end: None,
+            };
+
+            assert_eq!(window_end(&range), u64::MAX);
+        }
+    }
+
+    mod read_window {
+        use super::*;
+
+        #[tokio::test]
+        async fn stops_reading_once_the_window_is_full() {
+            let pulled = Arc::new(AtomicUsize::new(0));
+            let counter = pulled.clone();
+            let chunks = track_bytes()
+                .chunks(UPSTREAM_CHUNK)
+                .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+                .collect::<Vec<_>>();
+            let stream = futures::stream::iter(chunks).inspect(move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+            let skip = 3 * 1024 * 1024;
+
+            let body = read_window(stream, skip, MAX_RANGE_BYTES).await.unwrap();
+
+            assert_eq!(
+                pulled.load(Ordering::SeqCst) as u64,
+                (skip + MAX_RANGE_BYTES) / UPSTREAM_CHUNK as u64
+            );
+            assert!(body.capacity() as u64 <= MAX_RANGE_BYTES);
+            assert_eq!(
+                body.as_slice(),
+                &track_bytes()[skip as usize..(skip + MAX_RANGE_BYTES) as usize]
+            );
+        }
+    }
+
+    mod proxy_request {
+        use super::*;
+
+        #[tokio::test]
+        async fn serves_at_most_one_window_for_open_range() {
+            let url = ranged_upstream().await;
+
+            let response = proxy_request(&Client::new(), None, &upstream(&url), Some("bytes=0-"))
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(response.body().len() as u64, MAX_RANGE_BYTES);
+            assert_eq!(
+                header_str(&response, header::CONTENT_RANGE),
+                format!("bytes 0-{}/{}", MAX_RANGE_BYTES - 1, TRACK_SIZE)
+            );
+            assert_eq!(
+                header_str(&response, header::CONTENT_LENGTH),
+                MAX_RANGE_BYTES.to_string()
+            );
+            assert_eq!(header_str(&response, header::CONTENT_TYPE), "audio/mp4");
+        }
+
+        #[tokio::test]
+        async fn serves_small_ranges_exactly() {
+            let url = ranged_upstream().await;
+
+            let response =
+                proxy_request(&Client::new(), None, &upstream(&url), Some("bytes=100-199"))
+                    .await
+                    .unwrap();
+
+            assert_eq!(response.body(), &track_bytes()[100..200]);
+            assert_eq!(
+                header_str(&response, header::CONTENT_RANGE),
+                format!("bytes 100-199/{}", TRACK_SIZE)
+            );
+        }
+
+        #[tokio::test]
+        async fn serves_final_window_of_the_track() {
+            let url = ranged_upstream().await;
+            let start = TRACK_SIZE - 1000;
+
+            let response = proxy_request(
+                &Client::new(),
+                None,
+                &upstream(&url),
+                Some(&format!("bytes={}-", start)),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(response.body(), &track_bytes()[start..]);
+            assert_eq!(
+                header_str(&response, header::CONTENT_RANGE),
+                format!("bytes {}-{}/{}", start, TRACK_SIZE - 1, TRACK_SIZE)
+            );
+        }
+
+        #[tokio::test]
+        async fn bounds_buffered_bytes_when_upstream_ignores_range() {
+            let url = unranged_upstream().await;
+            let start = 3 * 1024 * 1024;
+
+            let response = proxy_request(
+                &Client::new(),
+                None,
+                &upstream(&url),
+                Some(&format!("bytes={}-", start)),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                response.body().as_slice(),
+                &track_bytes()[start..start + MAX_RANGE_BYTES as usize]
+            );
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                header_str(&response, header::CONTENT_RANGE),
+                format!(
+                    "bytes {}-{}/{}",
+                    start,
+                    start as u64 + MAX_RANGE_BYTES - 1,
+                    TRACK_SIZE
+                )
+            );
+        }
+
+        #[tokio::test]
+        async fn serves_the_first_window_without_a_range_header() {
+            let url = ranged_upstream().await;
+
+            let response = proxy_request(&Client::new(), None, &upstream(&url), None)
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+            assert_eq!(
+                header_str(&response, header::CONTENT_RANGE),
+                format!("bytes 0-{}/{}", MAX_RANGE_BYTES - 1, TRACK_SIZE)
+            );
+            assert_eq!(response.body().len() as u64, MAX_RANGE_BYTES);
+        }
+
+        #[tokio::test]
+        async fn serves_the_end_of_the_track_for_suffix_ranges() {
+            for url in [ranged_upstream().await, unranged_upstream().await] {
+                let response =
+                    proxy_request(&Client::new(), None, &upstream(&url), Some("bytes=-1000"))
+                        .await
+                        .unwrap();
+
+                assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+                assert_eq!(response.body(), &track_bytes()[TRACK_SIZE - 1000..]);
+                assert_eq!(
+                    header_str(&response, header::CONTENT_RANGE),
+                    format!(
+                        "bytes {}-{}/{}",
+                        TRACK_SIZE - 1000,
+                        TRACK_SIZE - 1,
+                        TRACK_SIZE
+                    )
+                );
             }
-            Err(e) => {
-                error!("[StreamProxy] Request failed: {}", e);
-                respond_error(
-                    responder,
-                    StatusCode::BAD_GATEWAY,
-                    format!("Failed to fetch: {}", e),
+        }
+
+        #[tokio::test]
+        async fn bounds_buffered_bytes_for_any_range_header() {
+            let url = unranged_upstream().await;
+            let suffix = format!("bytes=-{}", TRACK_SIZE);
+
+            for range in [
+                None,
+                Some(suffix.as_str()),
+                Some("bytes=0-10,20-"),
+                Some("items=0-"),
+            ] {
+                let response = proxy_request(&Client::new(), None, &upstream(&url), range)
+                    .await
+                    .unwrap();
+
+                assert_eq!(
+                    response.status(),
+                    StatusCode::PARTIAL_CONTENT,
+                    "{:?}",
+                    range
+                );
+                assert!(
+                    response.body().len() as u64 <= MAX_RANGE_BYTES,
+                    "{:?} buffered {} bytes",
+                    range,
+                    response.body().len()
                 );
             }
         }
-    });
+
+        #[tokio::test]
+        async fn returns_416_for_ranges_past_the_end() {
+            for url in [ranged_upstream().await, unranged_upstream().await] {
+                let response = proxy_request(
+                    &Client::new(),
+                    None,
+                    &upstream(&url),
+                    Some(&format!("bytes={}-", TRACK_SIZE)),
+                )
+                .await
+                .unwrap();
+
+                assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+                assert_eq!(
+                    header_str(&response, header::CONTENT_RANGE),
+                    format!("bytes */{}", TRACK_SIZE)
+                );
+                assert!(response.body().is_empty());
+            }
+        }
+
+        #[tokio::test]
+        async fn returns_bad_gateway_for_upstream_errors() {
+            let router = Router::new().route("/track", get(|| async { StatusCode::FORBIDDEN }));
+            let url = serve(router).await;
+
+            let (status, message) =
+                proxy_request(&Client::new(), None, &upstream(&url), Some("bytes=0-"))
+                    .await
+                    .unwrap_err();
+
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+            assert!(message.contains("403"));
+        }
+    }
+
+    mod caching {
+        use super::*;
+
+        fn cache_target(root: &std::path::Path) -> CacheTarget {
+            CacheTarget {
+                cache: AudioCache::open(root.to_path_buf(), u64::MAX).unwrap(),
+                key: "youtube:abc".to_string(),
+            }
+        }
+
+        #[tokio::test]
+        async fn serves_repeated_ranges_from_cache() {
+            let temp = tempdir().unwrap();
+            let target = cache_target(temp.path());
+            let requests = Arc::new(AtomicUsize::new(0));
+            let url = ranged_upstream_with_counter(requests.clone()).await;
+            let client = Client::new();
+
+            let first = proxy_request(&client, Some(&target), &upstream(&url), Some("bytes=0-"))
+                .await
+                .unwrap();
+            let second = proxy_request(&client, Some(&target), &upstream(&url), Some("bytes=0-"))
+                .await
+                .unwrap();
+
+            assert_eq!(requests.load(Ordering::SeqCst), 1);
+            assert_eq!(first.body(), second.body());
+            assert_eq!(
+                header_str(&second, header::CONTENT_RANGE),
+                header_str(&first, header::CONTENT_RANGE)
+            );
+            assert_eq!(header_str(&second, header::CONTENT_TYPE), "audio/mp4");
+        }
+
+        #[tokio::test]
+        async fn cache_survives_url_changes() {
+            let temp = tempdir().unwrap();
+            let target = cache_target(temp.path());
+            let requests = Arc::new(AtomicUsize::new(0));
+            let url = ranged_upstream_with_counter(requests.clone()).await;
+            let client = Client::new();
+
+            proxy_request(&client, Some(&target), &upstream(&url), Some("bytes=0-99"))
+                .await
+                .unwrap();
+            let response = proxy_request(
+                &client,
+                Some(&target),
+                &upstream("http://127.0.0.1:1/expired"),
+                Some("bytes=0-99"),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(requests.load(Ordering::SeqCst), 1);
+            assert_eq!(response.body(), &track_bytes()[..100]);
+        }
+
+        #[tokio::test]
+        async fn fetches_only_the_gap_before_the_next_cached_range() {
+            let temp = tempdir().unwrap();
+            let target = cache_target(temp.path());
+            let url = ranged_upstream().await;
+            let client = Client::new();
+
+            proxy_request(
+                &client,
+                Some(&target),
+                &upstream(&url),
+                Some("bytes=1000-1999"),
+            )
+            .await
+            .unwrap();
+            let gap = proxy_request(&client, Some(&target), &upstream(&url), Some("bytes=0-"))
+                .await
+                .unwrap();
+
+            assert_eq!(gap.body(), &track_bytes()[..1000]);
+            assert_eq!(
+                header_str(&gap, header::CONTENT_RANGE),
+                format!("bytes 0-999/{}", TRACK_SIZE)
+            );
+
+            let cached = proxy_request(
+                &client,
+                Some(&target),
+                &upstream(&url),
+                Some("bytes=0-1999"),
+            )
+            .await
+            .unwrap();
+            assert_eq!(cached.body(), &track_bytes()[..2000]);
+        }
+    }
+
+    mod recent_map {
+        use super::*;
+
+        #[test]
+        fn evicts_the_least_recently_used_entry_when_full() {
+            let mut map = RecentMap::default();
+            for i in 0..MAX_TRACKED_STREAMS {
+                map.insert(format!("url-{}", i), i);
+            }
+            map.get("url-0");
+
+            map.insert("new".to_string(), MAX_TRACKED_STREAMS);
+
+            assert_eq!(map.entries.len(), MAX_TRACKED_STREAMS);
+            assert_eq!(map.get("url-0"), Some(&0));
+            assert_eq!(map.get("url-1"), None);
+            assert_eq!(map.get("new"), Some(&MAX_TRACKED_STREAMS));
+        }
+    }
+
+    mod expired_urls {
+        use super::*;
+
+        async fn expiring_upstream(requests: Arc<AtomicUsize>) -> (String, String) {
+            let fresh_url = ranged_upstream_with_counter(requests).await;
+            let router = Router::new().route("/track", get(|| async { StatusCode::FORBIDDEN }));
+            (serve(router).await, fresh_url)
+        }
+
+        #[tokio::test]
+        async fn re_resolves_and_retries_the_same_range() {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let (expired_url, fresh_url) = expiring_upstream(requests.clone()).await;
+            register_ytdlp_stream(&expired_url, PAGE_URL, &YtdlpFormatPreference::default());
+            let resolver = Arc::new(FakeResolver {
+                fresh_url,
+                calls: AtomicUsize::new(0),
+            });
+
+            let response = proxy_request(
+                &Client::new(),
+                None,
+                &Upstream::new(expired_url, resolver.clone()),
+                Some("bytes=100-199"),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(resolver.calls.load(Ordering::SeqCst), 1);
+            assert_eq!(requests.load(Ordering::SeqCst), 1);
+            assert_eq!(response.body(), &track_bytes()[100..200]);
+        }
+
+        #[tokio::test]
+        async fn later_requests_for_the_old_url_use_the_fresh_one() {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let (expired_url, fresh_url) = expiring_upstream(requests.clone()).await;
+            register_ytdlp_stream(&expired_url, PAGE_URL, &YtdlpFormatPreference::default());
+            let resolver = Arc::new(FakeResolver {
+                fresh_url: fresh_url.clone(),
+                calls: AtomicUsize::new(0),
+            });
+
+            proxy_request(
+                &Client::new(),
+                None,
+                &Upstream::new(expired_url.clone(), resolver.clone()),
+                Some("bytes=0-99"),
+            )
+            .await
+            .unwrap();
+            let later = Upstream::new(expired_url, resolver.clone());
+
+            assert_eq!(later.url, fresh_url);
+            assert_eq!(later.refresh.unwrap().stream.page_url, PAGE_URL);
+            assert_eq!(resolver.calls.load(Ordering::SeqCst), 1);
+        }
+
+        #[tokio::test]
+        async fn re_resolves_with_the_format_the_stream_was_registered_with() {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let (expired_url, fresh_url) = expiring_upstream(requests).await;
+            let format = YtdlpFormatPreference {
+                max_bitrate_kbps: Some(96),
+                ..Default::default()
+            };
+            register_ytdlp_stream(&expired_url, PAGE_URL, &format);
+            register_ytdlp_stream(
+                "https://other.invalid/stream",
+                "https://www.youtube.com/watch?v=xyz",
+                &Default::default(),
+            );
+            let resolver = Arc::new(RecordingResolver {
+                fresh_url,
+                streams: Mutex::new(Vec::new()),
+            });
+
+            proxy_request(
+                &Client::new(),
+                None,
+                &Upstream::new(expired_url, resolver.clone()),
+                Some("bytes=0-99"),
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                *resolver.streams.lock().unwrap(),
+                vec![TrackedStream {
+                    page_url: PAGE_URL.to_string(),
+                    format,
+                }]
+            );
+        }
+
+        #[tokio::test]
+        async fn concurrent_requests_share_one_re_resolution() {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let (expired_url, fresh_url) = expiring_upstream(requests.clone()).await;
+            register_ytdlp_stream(&expired_url, PAGE_URL, &YtdlpFormatPreference::default());
+            let resolver = Arc::new(SlowResolver {
+                fresh_url,
+                calls: AtomicUsize::new(0),
+            });
+            let client = Client::new();
+            let first = Upstream::new(expired_url.clone(), resolver.clone());
+            let second = Upstream::new(expired_url, resolver.clone());
+
+            let (first, second) = tokio::join!(
+                proxy_request(&client, None, &first, Some("bytes=0-99")),
+                proxy_request(&client, None, &second, Some("bytes=100-199")),
+            );
+
+            assert_eq!(resolver.calls.load(Ordering::SeqCst), 1);
+            assert_eq!(first.unwrap().body(), &track_bytes()[0..100]);
+            assert_eq!(second.unwrap().body(), &track_bytes()[100..200]);
+        }
+
+        #[tokio::test]
+        async fn does_not_re_resolve_unknown_urls() {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let (expired_url, fresh_url) = expiring_upstream(requests).await;
+            let resolver = Arc::new(FakeResolver {
+                fresh_url,
+                calls: AtomicUsize::new(0),
+            });
+
+            let (status, message) = proxy_request(
+                &Client::new(),
+                None,
+                &Upstream::new(expired_url, resolver.clone()),
+                Some("bytes=0-"),
+            )
+            .await
+            .unwrap_err();
+
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+            assert!(message.contains("403"));
+            assert_eq!(resolver.calls.load(Ordering::SeqCst), 0);
+        }
+
+        #[tokio::test]
+        async fn reports_resolution_failures() {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let (expired_url, _) = expiring_upstream(requests).await;
+            register_ytdlp_stream(&expired_url, "gone", &YtdlpFormatPreference::default());
+
+            let (status, message) = proxy_request(
+                &Client::new(),
+                None,
+                &upstream(&expired_url),
+                Some("bytes=0-"),
+            )
+            .await
+            .unwrap_err();
+
+            assert_eq!(status, StatusCode::BAD_GATEWAY);
+            assert!(message.contains("Video unavailable"));
+        }
+    }
 }
@@ -0,0 +1,630 @@
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{command, AppHandle, Manager};
+
+use super::{classify_ytdlp_error, spawn_error, CommandRunner, RealCommandRunner};
+use crate::caller::Callers;
+use crate::download::download_to_path;
+use crate::error::{AppError, AppResult, ErrorKind};
+use crate::http::client::HttpClients;
+use crate::network::NetworkSettings;
+
+// Locates the yt-dlp binary and keeps an app-managed copy up to date.
+// Resolution order: user-configured path, then the managed copy in the app data dir, then PATH.
+
+const RELEASES_URL: &str = "https://github.com/yt-dlp/yt-dlp/releases/latest/download";
+const CHECKSUMS_FILE: &str = "SHA2-256SUMS";
+const MANAGED_DIR_NAME: &str = "bin";
+const SETTINGS_FILE: &str = "ytdlp.json";
+
+#[cfg(windows)]
+const BINARY_NAME: &str = "yt-dlp.exe";
+#[cfg(not(windows))]
+const BINARY_NAME: &str = "yt-dlp";
+
+fn release_asset_name() -> &'static str {
+    if cfg!(windows) {
+        "yt-dlp.exe"
+    } else if cfg!(target_os = "macos") {
+        "yt-dlp_macos"
+    } else if cfg!(target_arch = "aarch64") {
+        "yt-dlp_linux_aarch64"
+    } else {
+        "yt-dlp_linux"
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum YtdlpBinarySource {
+    Custom,
+    Managed,
+    System,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResolvedBinary {
+    pub path: PathBuf,
+    pub source: YtdlpBinarySource,
+}
+
+// Corresponds to the YtdlpBinaryInfo Typescript type in packages/plugin-sdk/src/types/ytdlp.ts
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct YtdlpBinaryInfo {
+    pub path: String,
+    pub source: YtdlpBinarySource,
+    pub version: Option<String>,
+}
+
+// What the user configured, persisted so it survives restarts.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+struct YtdlpSettings {
+    custom_path: Option<PathBuf>,
+    cookies_file: Option<PathBuf>,
+}
+
+impl YtdlpSettings {
+    fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("[yt-dlp] Ignoring invalid settings {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self, path: &Path) -> AppResult<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+pub struct YtdlpManager {
+    managed_dir: PathBuf,
+    releases_url: String,
+    settings_path: Option<PathBuf>,
+    custom_path: Mutex<Option<PathBuf>>,
+    // cookies.txt passed to every yt-dlp run via --cookies
+    cookies_file: Mutex<Option<PathBuf>>,
+    // Held for a whole update so concurrent updates don't write the same .download files
+    update_lock: tokio::sync::Mutex<()>,
+}
+
+fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// Finds the checksum for `asset` in a `<sha256>  <filename>` listing.
+fn find_checksum(listing: &str, asset: &str) -> Option<String> {
+    listing.lines().find_map(|line| {
+        let (checksum, name) = line.trim().split_once(char::is_whitespace)?;
+        (name.trim().trim_start_matches('*') == asset).then(|| checksum.to_lowercase())
+    })
+}
+
+fn version_with_runner(runner: &impl CommandRunner, path: &Path) -> AppResult<String> {
+    let program = path.to_string_lossy();
+    let output = runner.run(&program, &["--version"]).map_err(spawn_error)?;
+
+    if !output.status.success() {
+        return Err(classify_ytdlp_error(&String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl YtdlpManager {
+    pub fn new(managed_dir: PathBuf, releases_url: String) -> Self {
+        Self {
+            managed_dir,
+            releases_url,
+            settings_path: None,
+            custom_path: Mutex::new(None),
+            cookies_file: Mutex::new(None),
+            update_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Loads the configured binary path and cookies file from `path` and saves later changes back to it.
+    pub fn with_settings_file(mut self, path: PathBuf) -> Self {
+        let settings = YtdlpSettings::load(&path);
+        *self
+            .custom_path
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner()) = settings.custom_path;
+        *self
+            .cookies_file
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner()) = settings.cookies_file;
+        self.settings_path = Some(path);
+        self
+    }
+
+    fn save_settings(&self) -> AppResult<()> {
+        let Some(path) = &self.settings_path else {
+            return Ok(());
+        };
+        YtdlpSettings {
+            custom_path: self
+                .custom_path
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+            cookies_file: self.cookies_file(),
+        }
+        .save(path)
+    }
+
+    pub fn managed_path(&self) -> PathBuf {
+        self.managed_dir.join(BINARY_NAME)
+    }
+
+    pub fn set_custom_path(&self, path: Option<PathBuf>) -> AppResult<()> {
+        *self.custom_path.lock().unwrap_or_else(|e| e.into_inner()) = path;
+        self.save_settings()
+    }
+
+    pub fn set_cookies_file(&self, path: Option<PathBuf>) -> AppResult<()> {
+        *self.cookies_file.lock().unwrap_or_else(|e| e.into_inner()) = path;
+        self.save_settings()
+    }
+
+    pub fn cookies_file(&self) -> Option<PathBuf> {
+        self.cookies_file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn resolve(&self) -> ResolvedBinary {
+        let custom_path = self
+            .custom_path
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        if let Some(path) = custom_path {
+            if path.is_file() {
+                return ResolvedBinary {
+                    path,
+                    source: YtdlpBinarySource::Custom,
+                };
+            }
+            warn!("[yt-dlp] Configured binary {:?} does not exist", path);
+        }
+
+        let managed_path = self.managed_path();
+        if managed_path.is_file() {
+            return ResolvedBinary {
+                path: managed_path,
+                source: YtdlpBinarySource::Managed,
+            };
+        }
+
+        ResolvedBinary {
+            path: PathBuf::from(super::YTDLP),
+            source: YtdlpBinarySource::System,
+        }
+    }
+
+    fn info_with_runner(&self, runner: &impl CommandRunner) -> YtdlpBinaryInfo {
+        let resolved = self.resolve();
+        let version = version_with_runner(runner, &resolved.path).ok();
+
+        YtdlpBinaryInfo {
+            path: resolved.path.to_string_lossy().into_owned(),
+            source: resolved.source,
+            version,
+        }
+    }
+
+    /// Downloads the latest release into the managed dir, verifying it against the published
+    /// SHA-256 checksums. Returns false if the managed copy was already up to date.
+    pub async fn update(&self, network: &NetworkSettings) -> AppResult<bool> {
+        let _guard = self.update_lock.lock().await;
+        let asset = release_asset_name();
+        fs::create_dir_all(&self.managed_dir)?;
+
+        let checksums_path = self
+            .managed_dir
+            .join(format!("{}.download", CHECKSUMS_FILE));
+        download_to_path(
+            network,
+            &format!("{}/{}", self.releases_url, CHECKSUMS_FILE),
+            &checksums_path,
+        )
+        .await?;
+        let listing = fs::read_to_string(&checksums_path);
+        let _ = fs::remove_file(&checksums_path);
+        let expected = find_checksum(&listing?, asset)
+            .ok_or_else(|| AppError::parse(format!("No checksum published for {}", asset)))?;
+
+        let managed_path = self.managed_path();
+        if sha256_file(&managed_path).ok().as_deref() == Some(expected.as_str()) {
+            info!("[yt-dlp] Managed binary is up to date");
+            return Ok(false);
+        }
+
+        let download_path = self.managed_dir.join(format!("{}.download", BINARY_NAME));
+        download_to_path(
+            network,
+            &format!("{}/{}", self.releases_url, asset),
+            &download_path,
+        )
+        .await?;
+
+        let actual = sha256_file(&download_path)?;
+        if actual != expected {
+            let _ = fs::remove_file(&download_path);
+            error!(
+                "[yt-dlp] Checksum mismatch for {}: expected {}, got {}",
+                asset, expected, actual
+            );
+            return Err(AppError::new(
+                ErrorKind::VerificationFailed,
+                format!("Checksum mismatch for downloaded {}", asset),
+            ));
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&download_path, fs::Permissions::from_mode(0o755))?;
+        }
+
+        fs::rename(&download_path, &managed_path)?;
+        info!("[yt-dlp] Installed {} to {:?}", asset, managed_path);
+        Ok(true)
+    }
+}
+
+pub fn init_ytdlp_manager(app_handle: AppHandle) {
+    let data_dir = match app_handle.path().app_data_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            error!("[yt-dlp] Failed to resolve app data dir: {}", e);
+            return;
+        }
+    };
+
+    app_handle.manage(
+        YtdlpManager::new(data_dir.join(MANAGED_DIR_NAME), RELEASES_URL.to_string())
+            .with_settings_file(data_dir.join(SETTINGS_FILE)),
+    );
+}
+
+#[command]
+pub async fn ytdlp_binary_info(
+    manager: tauri::State<'_, YtdlpManager>,
+) -> AppResult<YtdlpBinaryInfo> {
+    Ok(manager.info_with_runner(&RealCommandRunner::new(&manager)))
+}
+
+/// Points yt-dlp at a binary the user picked, or back at the managed one when None.
+/// Reserved for the app, since the binary is run with the user's privileges.
+#[command]
+pub async fn ytdlp_set_binary_path(
+    manager: tauri::State<'_, YtdlpManager>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    path: Option<PathBuf>,
+) -> AppResult<YtdlpBinaryInfo> {
+    callers.require_app(token.as_deref())?;
+    manager.set_custom_path(path)?;
+    Ok(manager.info_with_runner(&RealCommandRunner::new(&manager)))
+}
+
+/// Sets the cookies.txt yt-dlp uses, e.g. a jar from `http_cookies_file`. Pass None to stop sending cookies.
+/// Reserved for the app, since yt-dlp reads and rewrites the file.
+#[command]
+pub async fn ytdlp_set_cookies_file(
+    manager: tauri::State<'_, YtdlpManager>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+    path: Option<PathBuf>,
+) -> AppResult<()> {
+    callers.require_app(token.as_deref())?;
+    if let Some(path) = &path {
+        if !path.is_file() {
+            return Err(AppError::io(format!(
+                "Cookies file {:?} does not exist",
+                path
+            )));
+        }
+    }
+    manager.set_cookies_file(path)
+}
+
+#[command]
+pub async fn ytdlp_update(
+    manager: tauri::State<'_, YtdlpManager>,
+    clients: tauri::State<'_, HttpClients>,
+    callers: tauri::State<'_, Callers>,
+    token: Option<String>,
+) -> AppResult<YtdlpBinaryInfo> {
+    callers.require_app(token.as_deref())?;
+    manager.update(&clients.network().settings()).await?;
+    Ok(manager.info_with_runner(&RealCommandRunner::new(&manager)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ytdlp::MockCommandRunner;
+    use axum::{routing::get, Router};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    const FAKE_RELEASE: &[u8] = b"#!/bin/sh\necho 2025.06.30\n";
+
+    fn version_output(stdout: &str) -> Output {
+        Output {
+            status: ExitStatus::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        }
+    }
+
+    fn sha256(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    // Stand-in for the GitHub release download endpoints, counting binary downloads.
+    async fn serve_release(checksum: String, downloads: Arc<AtomicUsize>) -> String {
+        let listing = format!(
+            "{}  yt-dlp.tar.gz\n{}  {}\n",
+            sha256(b"other"),
+            checksum,
+            release_asset_name()
+        );
+        let router = Router::new()
+            .route(
+                &format!("/{}", CHECKSUMS_FILE),
+                get(move || async move { listing }),
+            )
+            .route(
+                &format!("/{}", release_asset_name()),
+                get(move || async move {
+                    downloads.fetch_add(1, Ordering::SeqCst);
+                    FAKE_RELEASE
+                }),
+            );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    mod resolve {
+        use super::*;
+
+        #[test]
+        fn falls_back_to_system_binary() {
+            let temp = tempdir().unwrap();
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new());
+
+            assert_eq!(
+                manager.resolve(),
+                ResolvedBinary {
+                    path: PathBuf::from("yt-dlp"),
+                    source: YtdlpBinarySource::System,
+                }
+            );
+        }
+
+        #[test]
+        fn prefers_managed_copy_over_system() {
+            let temp = tempdir().unwrap();
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new());
+            fs::write(manager.managed_path(), FAKE_RELEASE).unwrap();
+
+            assert_eq!(manager.resolve().source, YtdlpBinarySource::Managed);
+        }
+
+        #[test]
+        fn prefers_configured_path_over_managed_copy() {
+            let temp = tempdir().unwrap();
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new());
+            let custom = temp.path().join("custom-yt-dlp");
+            fs::write(manager.managed_path(), FAKE_RELEASE).unwrap();
+            fs::write(&custom, FAKE_RELEASE).unwrap();
+
+            manager.set_custom_path(Some(custom.clone())).unwrap();
+
+            assert_eq!(
+                manager.resolve(),
+                ResolvedBinary {
+                    path: custom,
+                    source: YtdlpBinarySource::Custom,
+                }
+            );
+        }
+
+        #[test]
+        fn remembers_configured_path_across_restarts() {
+            let temp = tempdir().unwrap();
+            let settings = temp.path().join(SETTINGS_FILE);
+            let custom = temp.path().join("custom-yt-dlp");
+            fs::write(&custom, FAKE_RELEASE).unwrap();
+            YtdlpManager::new(temp.path().to_path_buf(), String::new())
+                .with_settings_file(settings.clone())
+                .set_custom_path(Some(custom.clone()))
+                .unwrap();
+
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new())
+                .with_settings_file(settings);
+
+            assert_eq!(manager.resolve().path, custom);
+        }
+
+        #[test]
+        fn remembers_cookies_file_across_restarts() {
+            let temp = tempdir().unwrap();
+            let settings = temp.path().join(SETTINGS_FILE);
+            let cookies = temp.path().join("cookies.txt");
+            YtdlpManager::new(temp.path().to_path_buf(), String::new())
+                .with_settings_file(settings.clone())
+                .set_cookies_file(Some(cookies.clone()))
+                .unwrap();
+
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new())
+                .with_settings_file(settings);
+
+            assert_eq!(manager.cookies_file(), Some(cookies));
+        }
+
+        #[test]
+        fn ignores_missing_configured_path() {
+            let temp = tempdir().unwrap();
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new());
+
+            manager
+                .set_custom_path(Some(temp.path().join("missing")))
+                .unwrap();
+
+            assert_eq!(manager.resolve().source, YtdlpBinarySource::System);
+        }
+    }
+
+    mod version {
+        use super::*;
+
+        #[test]
+        fn reports_trimmed_version() {
+            let mut mock = MockCommandRunner::new();
+            mock.expect_run()
+                .withf(|program, args| program == "yt-dlp" && args == ["--version"])
+                .returning(|_, _| Ok(version_output("2025.06.30\n")));
+
+            let version = version_with_runner(&mock, Path::new("yt-dlp")).unwrap();
+
+            assert_eq!(version, "2025.06.30");
+        }
+
+        #[test]
+        fn reports_missing_binary() {
+            let temp = tempdir().unwrap();
+            let manager = YtdlpManager::new(temp.path().to_path_buf(), String::new());
+            let mut mock = MockCommandRunner::new();
+            mock.expect_run().returning(|_, _| {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "not found",
+                ))
+            });
+
+            let info = manager.info_with_runner(&mock);
+
+            assert_eq!(info.version, None);
+            assert_eq!(info.source, YtdlpBinarySource::System);
+        }
+    }
+
+    mod find_checksum {
+        use super::*;
+
+        #[test]
+        fn finds_checksum_for_asset() {
+            let listing = "abc123  yt-dlp\nDEF456  yt-dlp_linux\n";
+
+            assert_eq!(
+                find_checksum(listing, "yt-dlp_linux"),
+                Some("def456".to_string())
+            );
+            assert_eq!(find_checksum(listing, "yt-dlp_macos"), None);
+        }
+    }
+
+    mod update {
+        use super::*;
+
+        #[tokio::test]
+        async fn installs_verified_release() {
+            let temp = tempdir().unwrap();
+            let downloads = Arc::new(AtomicUsize::new(0));
+            let url = serve_release(sha256(FAKE_RELEASE), downloads.clone()).await;
+            let manager = YtdlpManager::new(temp.path().join("bin"), url);
+
+            let updated = manager.update(&NetworkSettings::default()).await.unwrap();
+
+            assert!(updated);
+            assert_eq!(fs::read(manager.managed_path()).unwrap(), FAKE_RELEASE);
+            assert_eq!(manager.resolve().source, YtdlpBinarySource::Managed);
+            assert_eq!(
+                version_with_runner(&RealCommandRunner::new(&manager), &manager.managed_path())
+                    .unwrap(),
+                "2025.06.30"
+            );
+        }
+
+        #[tokio::test]
+        async fn skips_download_when_up_to_date() {
+            let temp = tempdir().unwrap();
+            let downloads = Arc::new(AtomicUsize::new(0));
+            let url = serve_release(sha256(FAKE_RELEASE), downloads.clone()).await;
+            let manager = YtdlpManager::new(temp.path().join("bin"), url);
+
+            manager.update(&NetworkSettings::default()).await.unwrap();
+            let updated = manager.update(&NetworkSettings::default()).await.unwrap();
+
+            assert!(!updated);
+            assert_eq!(downloads.load(Ordering::SeqCst), 1);
+        }
+
+        #[tokio::test]
+        async fn serializes_concurrent_updates() {
+            let temp = tempdir().unwrap();
+            let downloads = Arc::new(AtomicUsize::new(0));
+            let url = serve_release(sha256(FAKE_RELEASE), downloads.clone()).await;
+            let manager = YtdlpManager::new(temp.path().join("bin"), url);
+            let network = NetworkSettings::default();
+
+            let (first, second) = tokio::join!(manager.update(&network), manager.update(&network));
+
+            assert_eq!(
+                [first.unwrap(), second.unwrap()]
+                    .iter()
+                    .filter(|u| **u)
+                    .count(),
+                1
+            );
+            assert_eq!(downloads.load(Ordering::SeqCst), 1);
+            assert_eq!(fs::read(manager.managed_path()).unwrap(), FAKE_RELEASE);
+        }
+
+        #[tokio::test]
+        async fn rejects_checksum_mismatch() {
+            let temp = tempdir().unwrap();
+            let downloads = Arc::new(AtomicUsize::new(0));
+            let url = serve_release(sha256(b"something else"), downloads).await;
+            let manager = YtdlpManager::new(temp.path().join("bin"), url);
+
+            let result = manager.update(&NetworkSettings::default()).await;
+
+            assert!(result.unwrap_err().message.contains("Checksum mismatch"));
+            assert!(!manager.managed_path().exists());
+            assert_eq!(fs::read_dir(temp.path().join("bin")).unwrap().count(), 0);
+        }
+    }
+}
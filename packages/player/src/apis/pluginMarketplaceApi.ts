@@ -47,9 +47,12 @@ export type PluginRelease = {
   publishedAt: string;
   downloadUrl: string;
   size: number;
+  // Detached minisign signature of the archive, if the release ships one
+  signatureUrl?: string;
 };
 
 const PLUGIN_ASSET_NAME = 'plugin.zip';
+const SIGNATURE_ASSET_NAME = `${PLUGIN_ASSET_NAME}.minisig`;
 
 class PluginRegistryApi extends ApiClient {
   constructor() {
@@ -83,6 +86,9 @@ class GitHubReleasesApi extends ApiClient {
     if (!asset) {
       throw new Error(`No ${PLUGIN_ASSET_NAME} in release for ${repo}`);
     }
+    const signature = release.assets.find(
+      (a) => a.name === SIGNATURE_ASSET_NAME,
+    );
 
     return {
       version: release.tag_name.replace(/^v/i, ''),
@@ -90,6 +96,7 @@ class GitHubReleasesApi extends ApiClient {
       publishedAt: release.published_at,
       downloadUrl: asset.browser_download_url,
       size: asset.size,
+      signatureUrl: signature?.browser_download_url,
     };
   }
 }
@@ -0,0 +1,95 @@
+import { FC, useState } from 'react';
+
+import { useTranslation } from '@nuclearplayer/i18n';
+import { Button, Dialog, Toggle } from '@nuclearplayer/ui';
+
+import type { PendingHost } from '../services/tauri/commands';
+import { useHostApprovalStore } from '../stores/hostApprovalStore';
+import { usePluginStore } from '../stores/pluginStore';
+import { reportError } from '../utils/logging';
+
+const NO_HOSTS: PendingHost[] = [];
+
+export const ConnectedHostApprovalDialog: FC = () => {
+  const { t } = useTranslation('plugins');
+  const { pluginId, pending, close, approve } = useHostApprovalStore();
+  const plugin = usePluginStore((state) =>
+    pluginId ? state.plugins[pluginId] : undefined,
+  );
+  const hosts = (pluginId && pending[pluginId]) || NO_HOSTS;
+  // Choices the user made, private hosts stay off unless picked
+  const [choices, setChoices] = useState<Record<string, boolean>>({});
+
+  const isSelected = (host: PendingHost) => choices[host.host] ?? !host.private;
+
+  const handleClose = () => {
+    close();
+    setChoices({});
+  };
+
+  const handleAllow = async () => {
+    if (!pluginId) {
+      return;
+    }
+    try {
+      await approve(
+        pluginId,
+        hosts.filter(isSelected).map((host) => host.host),
+      );
+    } catch (error) {
+      await reportError('plugins', {
+        userMessage: 'Failed to approve hosts',
+        error,
+      });
+    }
+    handleClose();
+  };
+
+  return (
+    <Dialog.Root isOpen={hosts.length > 0} onClose={handleClose}>
+      <Dialog.Title>
+        {t('hostApproval.title', {
+          name: plugin?.metadata.displayName ?? pluginId,
+        })}
+      </Dialog.Title>
+      <Dialog.Description>{t('hostApproval.description')}</Dialog.Description>
+      <ul className="mt-4 flex flex-col gap-2">
+        {hosts.map((host) => (
+          <li
+            key={host.host}
+            className="flex items-center justify-between gap-4"
+            data-testid="pending-host"
+          >
+            <span className="font-mono text-sm break-all">{host.host}</span>
+            <div className="flex shrink-0 items-center gap-2">
+              {host.private && (
+                <span className="text-accent-orange text-xs font-semibold">
+                  {t('hostApproval.private')}
+                </span>
+              )}
+              <Toggle
+                checked={isSelected(host)}
+                onChange={(checked) =>
+                  setChoices((current) => ({
+                    ...current,
+                    [host.host]: checked,
+                  }))
+                }
+                aria-label={host.host}
+              />
+            </div>
+          </li>
+        ))}
+      </ul>
+      {hosts.some((host) => host.private) && (
+        <p className="text-foreground-secondary mt-4 text-sm">
+          {t('hostApproval.privateWarning')}
+        </p>
+      )}
+      <Dialog.Actions>
+        <Dialog.Close>{t('hostApproval.notNow')}</Dialog.Close>
+        <Button onClick={handleAllow}>{t('hostApproval.allow')}</Button>
+      </Dialog.Actions>
+    </Dialog.Root>
+  );
+};
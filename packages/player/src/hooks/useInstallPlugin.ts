@@ -8,10 +8,14 @@ import {
   type MarketplacePlugin,
 } from '../apis/pluginMarketplaceApi';
 import {
-  cleanupDownload,
-  downloadAndExtractPlugin,
+  downloadPluginArchive,
+  removePluginArchive,
 } from '../services/plugins/pluginDownloader';
-import { upsertRegistryEntry } from '../services/plugins/pluginRegistry';
+import {
+  getRegistryEntry,
+  upsertRegistryEntry,
+} from '../services/plugins/pluginRegistry';
+import { installPluginArchive } from '../services/tauri/commands';
 import { usePluginStore } from '../stores/pluginStore';
 import { resolveErrorMessage } from '../utils/logging';
 
@@ -28,27 +32,42 @@ export const useInstallPlugin = () => {
     mutationFn: async ({ plugin }: InstallPluginParams) => {
       const release = await pluginMarketplaceApi.getLatestRelease(plugin.repo);
 
-      const extractedPath = await downloadAndExtractPlugin({
+      const archivePath = await downloadPluginArchive({
         pluginId: plugin.id,
         downloadUrl: release.downloadUrl,
+        expectedSize: release.size,
+        signatureUrl: release.signatureUrl,
       });
 
+      const previous = await getRegistryEntry(plugin.id);
       try {
+        // Extracts, validates and swaps the plugin into the managed dir in one step,
+        // so a failed install leaves the previous version in place
+        const installed = await installPluginArchive(archivePath, plugin.id);
+
         const now = new Date().toISOString();
         await upsertRegistryEntry({
           id: plugin.id,
           version: release.version,
-          path: extractedPath,
+          path: installed.path,
           installationMethod: 'store',
           enabled: false,
           installedAt: now,
           lastUpdatedAt: now,
         });
 
-        await loadPluginFromPath(extractedPath);
-        await enablePlugin(plugin.id);
+        try {
+          await loadPluginFromPath(installed.path);
+          await enablePlugin(plugin.id);
+        } catch (error) {
+          // Each version has its own directory, so the one that was active is still on disk
+          if (previous) {
+            await upsertRegistryEntry(previous);
+          }
+          throw error;
+        }
       } finally {
-        await cleanupDownload(plugin.id);
+        await removePluginArchive(plugin.id);
       }
 
       return { plugin, version: release.version };
@@ -3,7 +3,12 @@ import { useEffect, useRef } from 'react';
 import { AudioSource } from '@nuclearplayer/hifi';
 import type { TFunction } from '@nuclearplayer/i18n';
 import { useTranslation } from '@nuclearplayer/i18n';
-import type { QueueItem, StreamCandidate, Track } from '@nuclearplayer/model';
+import type {
+  QueueItem,
+  Stream,
+  StreamCandidate,
+  Track,
+} from '@nuclearplayer/model';
 
 import { streamingHost } from '../services/streamingHost';
 import { useQueueStore } from '../stores/queueStore';
@@ -11,12 +16,28 @@ import { useSoundStore } from '../stores/soundStore';
 
 // Encode the URL in base64 and use our custom protocol to bypass CORS
 // Check packages/player/src-tauri/src/stream_proxy.rs to see how this works
-const proxyStreamUrl = (url: string): string => {
-  const encoded = btoa(url)
+// The cache key identifies the track and its encoding across re-resolutions, so the audio cache
+// survives URL changes but never mixes bytes of two formats in one entry
+const cacheKeyOf = (stream: Stream): string => {
+  const format = [
+    stream.codec,
+    stream.container ?? stream.mimeType,
+    stream.bitrateKbps,
+    stream.contentLengthBytes,
+  ]
+    .filter((part) => part !== undefined)
+    .join(',');
+  const track = `${stream.source.provider}:${stream.source.id}`;
+  return format ? `${track}:${format}` : track;
+};
+
+const proxyStreamUrl = (stream: Stream): string => {
+  const encoded = btoa(stream.url)
     .replace(/\+/g, '-')
     .replace(/\//g, '_')
     .replace(/=+$/, '');
-  return `nuclear-stream://localhost/${encoded}`;
+  const cacheKey = encodeURIComponent(cacheKeyOf(stream));
+  return `nuclear-stream://localhost/${encoded}?cache_key=${cacheKey}`;
 };
 
 const buildAudioSource = (candidate: StreamCandidate): AudioSource => {
@@ -29,7 +50,7 @@ const buildAudioSource = (candidate: StreamCandidate): AudioSource => {
     return { url: stream.url, protocol: 'hls' };
   }
 
-  return { url: proxyStreamUrl(stream.url), protocol: stream.protocol };
+  return { url: proxyStreamUrl(stream), protocol: stream.protocol };
 };
 
 const setItemError = (itemId: string, errorKey: string, t: TFunction): void => {
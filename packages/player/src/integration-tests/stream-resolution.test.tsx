@@ -71,7 +71,7 @@ describe('Stream Resolution Integration', () => {
 
       const src = StreamResolutionWrapper.getSoundState().src;
       expect(src).toEqual({
-        url: 'nuclear-stream://localhost/aHR0cHM6Ly9leGFtcGxlLmNvbS95dC0xLm1wMw',
+        url: 'nuclear-stream://localhost/aHR0cHM6Ly9leGFtcGxlLmNvbS95dC0xLm1wMw?cache_key=test-streaming-provider%3Ayt-1%3Aaudio%2Fmpeg',
         protocol: 'https',
       });
 
@@ -80,6 +80,34 @@ describe('Stream Resolution Integration', () => {
       expect(currentItem?.track.streamCandidates).toHaveLength(1);
     });
 
+    it('keys the audio cache on the stream format', async () => {
+      setupMetadataProvider();
+
+      const streamingProvider = new StreamingProviderBuilder()
+        .withSearchForTrack(async (artist, title) => [
+          createMockCandidate('yt-1', `${artist} - ${title}`),
+        ])
+        .withGetStreamUrl(async (candidateId) =>
+          createMockStream(candidateId, {
+            codec: 'opus',
+            container: 'webm',
+            bitrateKbps: 160,
+          }),
+        )
+        .build();
+
+      providersHost.register(streamingProvider);
+
+      await AlbumWrapper.mountDirectly();
+      await AlbumWrapper.addTrackToQueueByTitle('Countdown');
+
+      await StreamResolutionWrapper.waitForPlayback();
+
+      expect(StreamResolutionWrapper.getSoundState().src?.url).toContain(
+        'cache_key=test-streaming-provider%3Ayt-1%3Aopus%2Cwebm%2C160',
+      );
+    });
+
     it('shows loading state while resolving stream', async () => {
       setupMetadataProvider();
 
@@ -188,7 +216,7 @@ describe('Stream Resolution Integration', () => {
       await StreamResolutionWrapper.waitForPlayback();
 
       expect(StreamResolutionWrapper.getSoundState().src).toEqual({
-        url: 'nuclear-stream://localhost/aHR0cHM6Ly9leGFtcGxlLmNvbS95dC1nb29kLm1wMw',
+        url: 'nuclear-stream://localhost/aHR0cHM6Ly9leGFtcGxlLmNvbS95dC1nb29kLm1wMw?cache_key=test-streaming-provider%3Ayt-good',
         protocol: 'https',
       });
 
@@ -230,7 +258,7 @@ describe('Stream Resolution Integration', () => {
       });
 
       expect(StreamResolutionWrapper.getSoundState().src).toEqual({
-        url: 'nuclear-stream://localhost/aHR0cHM6Ly9leGFtcGxlLmNvbS95dC1HaWFudCBTdGVwcy5tcDM',
+        url: 'nuclear-stream://localhost/aHR0cHM6Ly9leGFtcGxlLmNvbS95dC1HaWFudCBTdGVwcy5tcDM?cache_key=test-streaming-provider%3Ayt-Giant%20Steps',
         protocol: 'https',
       });
     });
@@ -3,6 +3,7 @@ import ReactDOM from 'react-dom/client';
 
 import App from './App';
 import { registerBuiltInCoreSettings } from './services/coreSettings';
+import { claimAppToken } from './services/tauri/commands';
 import { initializeFavoritesStore } from './stores/favoritesStore';
 import { initializePlaylistStore } from './stores/playlistStore';
 import { initializeQueueStore } from './stores/queueStore';
@@ -15,6 +16,10 @@ import '@nuclearplayer/i18n';
 import { initLogStream } from './hooks/useLogStream';
 import { startAdvancedThemeWatcher } from './services/advancedThemeDirService';
 import { applyAdvancedThemeFromSettingsIfAny } from './services/advancedThemeService';
+import {
+  applyAudioCacheSizeFromSettings,
+  initAudioCacheSizeWatcher,
+} from './services/audioCacheService';
 import {
   applyLanguageFromSettings,
   initLanguageWatcher,
@@ -23,10 +28,20 @@ import { initMcpHandler } from './services/mcp';
 import { hydratePluginsFromRegistry } from './services/plugins/pluginBootstrap';
 import { applyThemeFromSettings } from './services/themeBootstrap';
 import { useUpdaterStore } from './stores/updaterStore';
+import { reportError } from './utils/logging';
 
 initLogStream();
 
-initializeSettingsStore()
+// Claimed before anything else runs, plugins are only loaded further down the chain.
+// Without it the app still starts, privileged actions like loading plugins just fail
+claimAppToken()
+  .catch((error) =>
+    reportError('app', {
+      userMessage: 'Failed to claim the app token',
+      error,
+    }),
+  )
+  .then(() => initializeSettingsStore())
   .then(() => initializeQueueStore())
   .then(() => initializeFavoritesStore())
   .then(() => initializePlaylistStore())
@@ -34,6 +49,8 @@ initializeSettingsStore()
   .then(() => initMcpHandler())
   .then(() => applyLanguageFromSettings())
   .then(() => initLanguageWatcher())
+  .then(() => applyAudioCacheSizeFromSettings())
+  .then(() => initAudioCacheSizeWatcher())
   .then(() => startAdvancedThemeWatcher())
   .then(() => applyThemeFromSettings())
   .then(() => applyAdvancedThemeFromSettingsIfAny())
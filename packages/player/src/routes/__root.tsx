@@ -18,6 +18,7 @@ import {
   Toaster,
 } from '@nuclearplayer/ui';
 
+import { ConnectedHostApprovalDialog } from '../components/ConnectedHostApprovalDialog';
 import { ConnectedPlayerBar } from '../components/ConnectedPlayerBar';
 import {
   ConnectedQueuePanel,
@@ -116,6 +117,7 @@ const RootComponent = () => {
       <ConnectedPlayerBar />
       <Toaster />
       <ConnectedSettingsModal />
+      <ConnectedHostApprovalDialog />
       <DevTools />
     </PlayerShell>
   );
@@ -0,0 +1,31 @@
+import { reportError } from '../utils/logging';
+import { coreSettingsHost } from './settingsHost';
+import { setAudioCacheMaxBytes } from './tauri/commands';
+
+const BYTES_PER_MB = 1024 * 1024;
+
+const applyAudioCacheSize = async (sizeMb: unknown) => {
+  if (typeof sizeMb !== 'number' || sizeMb <= 0) {
+    return;
+  }
+  try {
+    await setAudioCacheMaxBytes(Math.round(sizeMb * BYTES_PER_MB));
+  } catch (error) {
+    await reportError('playback', {
+      userMessage: 'Failed to update the audio cache size',
+      error,
+    });
+  }
+};
+
+export const applyAudioCacheSizeFromSettings = async () => {
+  await applyAudioCacheSize(
+    await coreSettingsHost.get<number>('playback.audioCacheSizeMb'),
+  );
+};
+
+export const initAudioCacheSizeWatcher = () => {
+  coreSettingsHost.subscribe('playback.audioCacheSizeMb', (value) => {
+    void applyAudioCacheSize(value);
+  });
+};
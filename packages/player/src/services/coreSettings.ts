@@ -120,6 +120,15 @@ export const CORE_SETTINGS: SettingDefinition[] = [
     default: 3,
     widget: { type: 'number-input', min: 1, max: 10, step: 1 },
   },
+  {
+    id: 'playback.audioCacheSizeMb',
+    title: 'preferences.playback.audioCacheSizeMb.title',
+    description: 'preferences.playback.audioCacheSizeMb.description',
+    category: 'playback',
+    kind: 'number',
+    default: 1024,
+    widget: { type: 'number-input', min: 64, max: 65536, step: 64, unit: 'MB' },
+  },
   {
     id: 'layout.leftSidebarWidth',
     title: 'preferences.layout.leftSidebarWidth.title',
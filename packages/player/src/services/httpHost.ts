@@ -1,14 +1,19 @@
-import { invoke } from '@tauri-apps/api/core';
+import { Channel } from '@tauri-apps/api/core';
 
 import type {
   HttpHost,
   HttpRequestInit,
   HttpResponseData,
+  HttpStreamEvent,
+  HttpStreamHandle,
 } from '@nuclearplayer/plugin-sdk';
 
 import { Logger } from './logger';
+import { getPluginToken, invokeCommand } from './tauri/commands';
 
-export const httpHost: HttpHost = {
+// Requests carry the plugin's token, which tells the backend whose client, cookie jar and
+// network policy to use
+export const createHttpHost = (pluginId: string): HttpHost => ({
   fetch: async (
     url: string,
     init?: HttpRequestInit,
@@ -16,17 +21,37 @@ export const httpHost: HttpHost = {
     const method = init?.method ?? 'GET';
     Logger.http.debug(`${method} ${url}`);
 
-    const response = await invoke<HttpResponseData>('http_fetch', {
-      request: {
-        url,
-        method,
-        headers: init?.headers,
-        body: init?.body,
-      },
+    const response = await invokeCommand<HttpResponseData>('http_fetch', {
+      request: { ...init, url, method },
+      token: await getPluginToken(pluginId),
     });
 
-    Logger.http.debug(`${method} ${url} -> ${response.status}`);
+    Logger.http.debug(
+      `${method} ${url} -> ${response.status} (cache ${response.cache})`,
+    );
 
     return response;
   },
-};
+  stream: async (
+    url: string,
+    init: HttpRequestInit | undefined,
+    onEvent: (event: HttpStreamEvent) => void,
+  ): Promise<HttpStreamHandle> => {
+    const method = init?.method ?? 'GET';
+    Logger.http.debug(`${method} ${url} (stream)`);
+
+    const channel = new Channel<HttpStreamEvent>();
+    channel.onmessage = onEvent;
+
+    const requestId = await invokeCommand<string>('http_fetch_stream', {
+      request: { ...init, url, method },
+      token: await getPluginToken(pluginId),
+      onEvent: channel,
+    });
+
+    return {
+      requestId,
+      cancel: () => invokeCommand<void>('http_cancel', { requestId }),
+    };
+  },
+});
@@ -17,6 +17,7 @@ import {
 import { errorMessage } from '../../utils/error';
 import { Logger } from '../logger';
 import { createPluginAPI } from '../plugins/createPluginAPI';
+import { invokeCommand } from '../tauri/commands';
 import { dispatch } from './mcpDispatcher';
 
 const MCP_ENABLED_SETTING = 'core.integrations.mcp.enabled';
@@ -108,13 +109,13 @@ const handleToolCall = async (request: BridgeRequest): Promise<void> => {
 };
 
 const startServer = async () => {
-  const port = await invoke<number>('mcp_start');
+  const port = await invokeCommand<number>('mcp_start');
   const url = `http://127.0.0.1:${port}/mcp`;
   await setSetting(MCP_SERVER_URL_SETTING, url);
   Logger.mcp.info(`MCP server started on ${url}`);
 };
 
-const stopServer = () => invoke('mcp_stop');
+const stopServer = () => invokeCommand('mcp_stop');
 
 const watchSettings = () => {
   let previouslyEnabled = getSetting(MCP_ENABLED_SETTING) === true;
@@ -10,6 +10,7 @@ import type {
 } from '@nuclearplayer/plugin-sdk';
 
 import { Logger } from '../logger';
+import { requestPluginHosts } from '../tauri/commands';
 import { compilePlugin } from './pluginCompiler';
 import { safeParsePluginManifest } from './pluginManifest';
 
@@ -58,6 +59,7 @@ export class PluginLoader {
       category: manifest.nuclear?.category,
       icon: manifest.nuclear?.icon,
       permissions: manifest.nuclear?.permissions || [],
+      hosts: manifest.nuclear?.hosts || [],
     };
   }
 
@@ -158,6 +160,13 @@ export class PluginLoader {
     this.entryPath = await this.resolveEntryPath(manifest);
     const code = await this.readPluginCode(this.entryPath);
     const instance = this.evaluatePlugin(code);
+    // Recorded so the user can approve them, requests to other hosts are refused until then.
+    // Recorded even when empty, a plugin that declares no hosts isn't held to a list
+    await requestPluginHosts(metadata.id, metadata.hosts).catch((error) =>
+      Logger.plugins.warn(
+        `Failed to register hosts for ${metadata.id}: ${error instanceof Error ? error.message : String(error)}`,
+      ),
+    );
     if (instance.onLoad && api) {
       Logger.plugins.debug(`Calling onLoad for ${metadata.id}`);
       await instance.onLoad(api);
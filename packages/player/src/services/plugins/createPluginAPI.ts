@@ -2,7 +2,7 @@ import { NuclearPluginAPI } from '@nuclearplayer/plugin-sdk';
 
 import { dashboardHost } from '../../services/dashboardHost';
 import { favoritesHost } from '../../services/favoritesHost';
-import { httpHost } from '../../services/httpHost';
+import { createHttpHost } from '../../services/httpHost';
 import { createLoggerHost } from '../../services/loggerHost';
 import { metadataHost } from '../../services/metadataHost';
 import { playbackHost } from '../../services/playbackHost';
@@ -11,7 +11,7 @@ import { providersHost } from '../../services/providersHost';
 import { queueHost } from '../../services/queueHost';
 import { createPluginSettingsHost } from '../../services/settingsHost';
 import { streamingHost } from '../../services/streamingHost';
-import { ytdlpHost } from '../../services/ytdlpHost';
+import { createYtdlpHost } from '../../services/ytdlpHost';
 
 export const createPluginAPI = (
   pluginId: string,
@@ -23,8 +23,8 @@ export const createPluginAPI = (
     providersHost,
     streamingHost,
     metadataHost,
-    httpHost,
-    ytdlpHost,
+    httpHost: createHttpHost(pluginId),
+    ytdlpHost: createYtdlpHost(pluginId),
     favoritesHost,
     playbackHost,
     playlistsHost,
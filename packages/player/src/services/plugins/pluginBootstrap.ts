@@ -1,5 +1,6 @@
 import { normalize } from '@tauri-apps/api/path';
 
+import { useHostApprovalStore } from '../../stores/hostApprovalStore';
 import { usePluginStore } from '../../stores/pluginStore';
 import { useStartupStore } from '../../stores/startupStore';
 import { resolveErrorMessage } from '../../utils/logging';
@@ -54,6 +55,9 @@ export const hydratePluginsFromRegistry = async (): Promise<void> => {
           },
         },
       }));
+      // Pending hosts are listed with the installed plugins for the user to
+      // review, rather than prompting on every start
+      await useHostApprovalStore.getState().refresh(entry.id);
       if (entry.enabled) {
         await usePluginStore.getState().enablePlugin(entry.id);
       }
@@ -35,6 +35,14 @@ export const installPluginToManagedDir = async (
 ): Promise<string> => {
   Logger.plugins.debug(`Installing plugin ${id}@${version} from ${fromPath}`);
   const destination = await getManagedPluginPath(id, version);
+  const appData = await appDataDir();
+  const absoluteDestination = await join(appData, destination);
+
+  // Store installs are unpacked into the managed dir by install_plugin_archive
+  if ((await normalize(fromPath)) === (await normalize(absoluteDestination))) {
+    Logger.plugins.debug(`Plugin ${id}@${version} is already installed`);
+    return absoluteDestination;
+  }
 
   // Remove existing plugin version if present
   try {
@@ -63,8 +71,6 @@ export const installPluginToManagedDir = async (
     });
   }
 
-  const appData = await appDataDir();
-  const absoluteDestination = await join(appData, destination);
   Logger.plugins.debug(`Copying plugin files to ${absoluteDestination}`);
   await copyDirRecursive(fromPath, absoluteDestination);
   Logger.plugins.debug(
@@ -2,13 +2,16 @@ import { appDataDir, join } from '@tauri-apps/api/path';
 import { BaseDirectory, remove } from '@tauri-apps/plugin-fs';
 
 import { ensureDir } from '../../utils/path';
-import { downloadFile, extractZip } from '../tauri/commands';
+import { downloadFile } from '../tauri/commands';
 
 const DOWNLOADS_DIR = 'plugins/.downloads';
 
 type DownloadPluginOptions = {
   pluginId: string;
   downloadUrl: string;
+  // Size of the release asset, checked before the archive is installed
+  expectedSize?: number;
+  signatureUrl?: string;
 };
 
 const getDownloadsDir = async (): Promise<string> => {
@@ -17,29 +20,35 @@ const getDownloadsDir = async (): Promise<string> => {
   return join(base, DOWNLOADS_DIR);
 };
 
-export const downloadAndExtractPlugin = async ({
+// Downloads the release archive, and its signature next to it as <archive>.minisig where
+// install_plugin_archive looks for it. The signature is checked before anything is extracted.
+export const downloadPluginArchive = async ({
   pluginId,
   downloadUrl,
+  expectedSize,
+  signatureUrl,
 }: DownloadPluginOptions): Promise<string> => {
   const downloadsDir = await getDownloadsDir();
   const zipPath = await join(downloadsDir, `${pluginId}.zip`);
-  const extractPath = await join(downloadsDir, pluginId);
-  const relativeZipPath = await join(DOWNLOADS_DIR, `${pluginId}.zip`);
 
-  await downloadFile(downloadUrl, zipPath);
-  await extractZip(zipPath, extractPath);
-  await remove(relativeZipPath, { baseDir: BaseDirectory.AppData });
+  await downloadFile(downloadUrl, zipPath, {
+    expected: { size: expectedSize },
+  });
+  if (signatureUrl) {
+    await downloadFile(signatureUrl, `${zipPath}.minisig`);
+  }
 
-  return extractPath;
+  return zipPath;
 };
 
-export const cleanupDownload = async (pluginId: string): Promise<void> => {
-  const downloadsDir = await getDownloadsDir();
-  const extractPath = await join(downloadsDir, pluginId);
+export const removePluginArchive = async (pluginId: string): Promise<void> => {
+  const relativeZipPath = await join(DOWNLOADS_DIR, `${pluginId}.zip`);
 
-  try {
-    await remove(extractPath, { recursive: true });
-  } catch {
-    // Ignore cleanup errors - directory may not exist
+  for (const path of [relativeZipPath, `${relativeZipPath}.minisig`]) {
+    try {
+      await remove(path, { baseDir: BaseDirectory.AppData });
+    } catch {
+      // Ignore cleanup errors - the download may have failed before creating it
+    }
   }
 };
@@ -14,6 +14,7 @@ const NuclearSchema = z
     category: z.string().min(1).optional(),
     icon: PluginIconSchema.optional(),
     permissions: z.array(z.string().min(1)).optional(),
+    hosts: z.array(z.string().min(1)).optional(),
   })
   .passthrough();
 
@@ -48,11 +49,22 @@ const normalizePermissions = (
   return deduped.sort((a, b) => a.localeCompare(b));
 };
 
+const normalizeHosts = (hosts: unknown): string[] | undefined => {
+  if (!Array.isArray(hosts)) {
+    return undefined;
+  }
+  const normalized = hosts
+    .map((h) => (typeof h === 'string' ? h.trim().toLowerCase() : ''))
+    .filter((h) => h.length > 0);
+  return Array.from(new Set(normalized)).sort((a, b) => a.localeCompare(b));
+};
+
 const collectUnknownNuclearKeys = (
   nuclear: Record<string, unknown>,
 ): string[] =>
   Object.keys(nuclear).filter(
-    (k) => !['displayName', 'category', 'icon', 'permissions'].includes(k),
+    (k) =>
+      !['displayName', 'category', 'icon', 'permissions', 'hosts'].includes(k),
   );
 
 const normalizeNuclear = (
@@ -72,6 +84,7 @@ const normalizeNuclear = (
     category: nuclear.category?.trim(),
     icon: nuclear.icon as NuclearType['icon'],
     permissions,
+    hosts: normalizeHosts(nuclear.hosts),
   };
 };
 
@@ -1,26 +1,493 @@
-import { invoke } from '@tauri-apps/api/core';
+import { invoke, type InvokeArgs } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+import {
+  toCommandError,
+  type HttpCacheStatus,
+  type PluginManifest,
+  type YtdlpBinaryInfo,
+} from '@nuclearplayer/plugin-sdk';
+
+// Invokes a Tauri command, rethrowing its structured error as a CommandError.
+export const invokeCommand = async <T>(
+  command: string,
+  args?: InvokeArgs,
+): Promise<T> => {
+  try {
+    return await invoke<T>(command, args);
+  } catch (error) {
+    throw toCommandError(error);
+  }
+};
+
+// Tells privileged commands that a call comes from the app rather than from plugin code.
+// Claimed at startup, and again after every reload, before any plugin is loaded, and never
+// handed to plugins. Plugins run in the same webview though, so this keeps well-behaved plugins
+// in their lane rather than containing a hostile one: plugin code can still reach the IPC
+// transport underneath invoke, and with it anything the app can do.
+let appToken: string | undefined;
+
+const lockTauriInvoke = () => {
+  const internals = (window as unknown as { __TAURI_INTERNALS__?: object })
+    .__TAURI_INTERNALS__;
+  if (internals) {
+    Object.defineProperty(internals, 'invoke', {
+      writable: false,
+      configurable: false,
+    });
+  }
+};
+
+export const claimAppToken = async (): Promise<void> => {
+  appToken = await invokeCommand<string>('caller_claim_app_token');
+  // Keeps plugins from simply wrapping invoke to read the token off later calls. It doesn't stop
+  // them from wrapping the fetch or postMessage calls invoke is built on
+  lockTauriInvoke();
+};
+
+// For commands reserved for the app's own UI
+const invokePrivileged = async <T>(
+  command: string,
+  args: Record<string, unknown> = {},
+): Promise<T> => {
+  return invokeCommand<T>(command, { ...args, token: appToken });
+};
+
+// Tokens identifying plugins to plugin-facing commands like http_fetch, one per plugin for as
+// long as it stays installed
+const pluginTokens = new Map<string, Promise<string>>();
+
+export const getPluginToken = (pluginId: string): Promise<string> => {
+  let token = pluginTokens.get(pluginId);
+  if (!token) {
+    token = invokePrivileged<string>('caller_issue_plugin_token', {
+      pluginId,
+    });
+    token.catch(() => pluginTokens.delete(pluginId));
+    pluginTokens.set(pluginId, token);
+  }
+  return token;
+};
+
+export const revokePluginToken = async (pluginId: string): Promise<void> => {
+  pluginTokens.delete(pluginId);
+  await invokePrivileged('caller_revoke_plugin_token', { pluginId });
+};
 
 export const isFlatpak = async (): Promise<boolean> => {
-  return invoke<boolean>('is_flatpak');
+  return invokeCommand<boolean>('is_flatpak');
 };
 
 export const copyDirRecursive = async (
   from: string,
   to: string,
 ): Promise<void> => {
-  await invoke('copy_dir_recursive', { from, to });
+  await invokePrivileged('copy_dir_recursive', { from, to });
 };
 
-export const extractZip = async (
-  zipPath: string,
-  destPath: string,
-): Promise<void> => {
-  await invoke('extract_zip', { zipPath, destPath });
+// Corresponds to the download:progress event payload in src-tauri/src/download.rs
+export type DownloadProgress = {
+  id: string;
+  downloadedBytes: number;
+  totalBytes: number | null;
+  bytesPerSecond: number;
+};
+
+export type ExpectedFile = {
+  sha256?: string;
+  size?: number;
+};
+
+export type DownloadOptions = {
+  // Client-chosen ID used for progress events and cancellation
+  downloadId?: string;
+  onProgress?: (progress: DownloadProgress) => void;
+  // Checked before the download is moved into place
+  expected?: ExpectedFile;
 };
 
 export const downloadFile = async (
   url: string,
   destPath: string,
+  options: DownloadOptions = {},
+): Promise<void> => {
+  const { downloadId, onProgress, expected } = options;
+  const unlisten =
+    downloadId && onProgress
+      ? await listen<DownloadProgress>('download:progress', (event) => {
+          if (event.payload.id === downloadId) {
+            onProgress(event.payload);
+          }
+        })
+      : undefined;
+
+  try {
+    await invokePrivileged('download_file', {
+      url,
+      destPath,
+      downloadId,
+      expected,
+    });
+  } finally {
+    unlisten?.();
+  }
+};
+
+export const cancelDownload = async (downloadId: string): Promise<void> => {
+  await invokeCommand('cancel_download', { downloadId });
+};
+
+export const getYtdlpBinaryInfo = async (): Promise<YtdlpBinaryInfo> => {
+  return invokeCommand<YtdlpBinaryInfo>('ytdlp_binary_info');
+};
+
+export const setYtdlpBinaryPath = async (
+  path: string | null,
+): Promise<YtdlpBinaryInfo> => {
+  return invokePrivileged<YtdlpBinaryInfo>('ytdlp_set_binary_path', { path });
+};
+
+export const updateYtdlp = async (): Promise<YtdlpBinaryInfo> => {
+  return invokePrivileged<YtdlpBinaryInfo>('ytdlp_update');
+};
+
+// Pass a cookies.txt path, e.g. from getCookiesFile, or null to stop sending cookies
+export const setYtdlpCookiesFile = async (
+  path: string | null,
+): Promise<void> => {
+  await invokePrivileged('ytdlp_set_cookies_file', { path });
+};
+
+// Corresponds to CookieInfo in src-tauri/src/http/cookies.rs
+export type CookieInfo = {
+  domain: string;
+  includeSubdomains: boolean;
+  path: string;
+  secure: boolean;
+  httpOnly: boolean;
+  // Unix timestamp, null for session cookies
+  expires: number | null;
+  name: string;
+  value: string;
+};
+
+// Cookie commands act on a plugin's jar, or the app-wide jar when pluginId is omitted
+export const listCookies = async (pluginId?: string): Promise<CookieInfo[]> => {
+  return invokePrivileged<CookieInfo[]>('http_cookies_list', { pluginId });
+};
+
+// Asks the user for a Netscape cookies.txt file, as exported by browsers, and imports it.
+// Returns the number of cookies imported, or null if the user cancelled.
+export const importCookies = async (
+  pluginId?: string,
+): Promise<number | null> => {
+  return invokePrivileged<number | null>('http_cookies_import', { pluginId });
+};
+
+export const clearCookies = async (pluginId?: string): Promise<void> => {
+  await invokePrivileged('http_cookies_clear', { pluginId });
+};
+
+export const getCookiesFile = async (pluginId?: string): Promise<string> => {
+  return invokePrivileged<string>('http_cookies_file', { pluginId });
+};
+
+// Corresponds to RateLimit in src-tauri/src/http/rate_limit.rs
+export type RateLimit = {
+  requestsPerSecond: number;
+  burst: number;
+};
+
+export type RateLimitState = {
+  pluginId: string | null;
+  host: string;
+  limit: RateLimit | null;
+  availableTokens: number | null;
+  blockedForMs: number;
+};
+
+export const getRateLimits = async (): Promise<RateLimitState[]> => {
+  return invokeCommand<RateLimitState[]>('http_rate_limits');
+};
+
+// Overrides the limit for a host, per plugin when pluginId is given. null removes the limit
+export const setRateLimit = async (
+  host: string,
+  limit: RateLimit | null,
+  pluginId?: string,
+): Promise<void> => {
+  await invokePrivileged('http_set_rate_limit', { host, limit, pluginId });
+};
+
+// Corresponds to NetworkPolicy in src-tauri/src/http/policy.rs
+export type PluginNetworkPolicy = {
+  requestedHosts: string[];
+  approvedHosts: string[];
+};
+
+export type NetworkPolicy = {
+  plugins: Record<string, PluginNetworkPolicy>;
+  // Private or loopback hosts any request may reach
+  privateHosts: string[];
+};
+
+export const getNetworkPolicy = async (): Promise<NetworkPolicy> => {
+  return invokeCommand<NetworkPolicy>('http_policy');
+};
+
+// Corresponds to PendingHost in src-tauri/src/http/policy.rs. Private hosts
+// give the plugin access to the local network once approved
+export type PendingHost = {
+  host: string;
+  private: boolean;
+};
+
+export const getPendingPluginHosts = async (
+  pluginId: string,
+): Promise<PendingHost[]> => {
+  return invokeCommand<PendingHost[]>('http_policy_pending_hosts', {
+    pluginId,
+  });
+};
+
+export const requestPluginHosts = async (
+  pluginId: string,
+  hosts: string[],
+): Promise<NetworkPolicy> => {
+  return invokePrivileged<NetworkPolicy>('http_policy_request_hosts', {
+    pluginId,
+    hosts,
+  });
+};
+
+export const approvePluginHosts = async (
+  pluginId: string,
+  hosts: string[],
+): Promise<NetworkPolicy> => {
+  return invokePrivileged<NetworkPolicy>('http_policy_approve_hosts', {
+    pluginId,
+    hosts,
+  });
+};
+
+export const revokePluginHosts = async (
+  pluginId: string,
+  hosts: string[],
+): Promise<NetworkPolicy> => {
+  return invokePrivileged<NetworkPolicy>('http_policy_revoke_hosts', {
+    pluginId,
+    hosts,
+  });
+};
+
+export const setPrivateHosts = async (
+  hosts: string[],
+): Promise<NetworkPolicy> => {
+  return invokePrivileged<NetworkPolicy>('http_policy_set_private_hosts', {
+    hosts,
+  });
+};
+
+// Corresponds to RedactionRules in src-tauri/src/http/redaction.rs.
+// Added to the built-in lists of sensitive headers and query params
+export type RedactionRules = {
+  headers: string[];
+  queryParams: string[];
+  // `email` masks the key at any depth, `user.email` or `*.credentials.pin` only from the root
+  bodyKeys: string[];
+  // Logs JSON and form bodies with sensitive values masked instead of just their length
+  logBodies: boolean;
+};
+
+export const getRedactionRules = async (): Promise<RedactionRules> => {
+  return invokeCommand<RedactionRules>('http_redaction_rules');
+};
+
+export const setRedactionRules = async (
+  rules: RedactionRules,
+): Promise<void> => {
+  await invokePrivileged('http_set_redaction_rules', { rules });
+};
+
+// Corresponds to AudioCacheStats in src-tauri/src/audio_cache.rs
+export type AudioCacheStats = {
+  entries: number;
+  sizeBytes: number;
+  maxBytes: number;
+};
+
+export const getAudioCacheStats = async (): Promise<AudioCacheStats> => {
+  return invokeCommand<AudioCacheStats>('audio_cache_stats');
+};
+
+// Persisted by the backend, evicts right away when lowered
+export const setAudioCacheMaxBytes = async (
+  maxBytes: number,
+): Promise<void> => {
+  await invokePrivileged('audio_cache_set_max_bytes', { maxBytes });
+};
+
+export const clearAudioCache = async (): Promise<void> => {
+  await invokePrivileged('audio_cache_clear');
+};
+
+// Clears the response cache shared by all plugins, in memory and on disk
+export const clearHttpCache = async (): Promise<void> => {
+  await invokeCommand('http_cache_clear');
+};
+
+// Corresponds to ExchangeSummary in src-tauri/src/http/inspector.rs.
+// URLs and headers are redacted before they are recorded
+export type HttpExchangeSummary = {
+  id: number;
+  pluginId: string | null;
+  method: string;
+  url: string;
+  // null when no response arrived
+  status: number | null;
+  startedAt: string;
+  durationMs: number;
+  requestSize: number | null;
+  responseSize: number | null;
+  cache: HttpCacheStatus | null;
+  streamed: boolean;
+  error: string | null;
+};
+
+export type HttpExchange = HttpExchangeSummary & {
+  requestHeaders: Record<string, string>;
+  responseHeaders: Record<string, string>;
+};
+
+// The most recent exchanges, oldest first
+export const listHttpExchanges = async (): Promise<HttpExchangeSummary[]> => {
+  return invokePrivileged<HttpExchangeSummary[]>('http_inspector_list');
+};
+
+export const getHttpExchange = async (
+  id: number,
+): Promise<HttpExchange | null> => {
+  return invokePrivileged<HttpExchange | null>('http_inspector_get', { id });
+};
+
+export const clearHttpExchanges = async (): Promise<void> => {
+  await invokePrivileged('http_inspector_clear');
+};
+
+// Asks the user where to save the HAR file. Returns the path written, or null if they cancelled.
+export const exportHttpExchangesAsHar = async (): Promise<string | null> => {
+  return invokePrivileged<string | null>('http_inspector_export_har');
+};
+
+// Corresponds to TlsSettings in src-tauri/src/network/tls.rs
+export type TlsSettings = {
+  // PEM files with CA certificates trusted on top of the system roots
+  caFiles: string[];
+  // Host pattern to the hex SHA-256 fingerprint of its certificate, e.g. for self-signed servers
+  pinnedCertificates: Record<string, string>;
+  // Host patterns whose certificates are not checked at all. `*` and `*.com` are refused
+  insecureHosts: string[];
+};
+
+// Corresponds to NetworkSettings in src-tauri/src/network/mod.rs.
+// Applies to http_fetch, downloads, the stream proxy and yt-dlp, which only takes the proxy
+export type NetworkSettings = {
+  // http, https, socks5 or socks5h. null uses the system proxy, if any
+  proxyUrl: string | null;
+  proxyUsername: string | null;
+  // Kept in the OS keyring and read back masked. Sending the mask back keeps it
+  proxyPassword: string | null;
+  // Hosts reached without the proxy, in NO_PROXY syntax. yt-dlp ignores CIDR ranges
+  noProxy: string[];
+  tls: TlsSettings;
+};
+
+export const getNetworkSettings = async (): Promise<NetworkSettings> => {
+  return invokeCommand<NetworkSettings>('network_settings');
+};
+
+// Rejected if the proxy URL, a CA file or a pin is invalid
+export const setNetworkSettings = async (
+  settings: NetworkSettings,
 ): Promise<void> => {
-  await invoke('download_file', { url, destPath });
+  await invokePrivileged('network_set_settings', { settings });
+};
+
+// Corresponds to SignatureStatus in src-tauri/src/plugins/signature.rs
+export type SignatureStatus =
+  | { status: 'verified'; keyId: string }
+  | { status: 'unsigned' }
+  | { status: 'untrusted'; keyId: string };
+
+// Corresponds to InstalledPlugin in src-tauri/src/plugins/mod.rs
+export type InstalledPlugin = {
+  path: string;
+  manifest: PluginManifest;
+  signature: SignatureStatus;
+};
+
+// Extracts, validates and swaps a plugin archive into plugins/<name>/<version> in one step.
+// The signature defaults to <archivePath>.minisig.
+// Fails unless the archive contains the plugin named `expectedName`
+export const installPluginArchive = async (
+  archivePath: string,
+  expectedName: string,
+  options: { signaturePath?: string } = {},
+): Promise<InstalledPlugin> => {
+  return invokePrivileged<InstalledPlugin>('install_plugin_archive', {
+    archivePath,
+    expectedName,
+    signaturePath: options.signaturePath,
+  });
+};
+
+// Corresponds to TrustStore in src-tauri/src/plugins/signature.rs
+export type TrustedKey = {
+  keyId: string;
+  publicKey: string;
+  comment: string | null;
+};
+
+export type PluginTrustStore = {
+  requireSignatures: boolean;
+  keys: TrustedKey[];
+};
+
+export const getPluginTrustStore = async (): Promise<PluginTrustStore> => {
+  return invokeCommand<PluginTrustStore>('plugin_trust_store');
+};
+
+// Accepts the base64 key line or the whole contents of a minisign.pub file
+export const trustPluginKey = async (
+  publicKey: string,
+  comment?: string,
+): Promise<PluginTrustStore> => {
+  return invokePrivileged<PluginTrustStore>('plugin_trust_key', {
+    publicKey,
+    comment,
+  });
+};
+
+export const untrustPluginKey = async (
+  keyId: string,
+): Promise<PluginTrustStore> => {
+  return invokePrivileged<PluginTrustStore>('plugin_untrust_key', { keyId });
+};
+
+export const setRequirePluginSignatures = async (
+  required: boolean,
+): Promise<PluginTrustStore> => {
+  return invokePrivileged<PluginTrustStore>('plugin_set_require_signatures', {
+    required,
+  });
+};
+
+export const verifyPluginArchive = async (
+  archivePath: string,
+  signaturePath?: string,
+): Promise<SignatureStatus> => {
+  return invokeCommand<SignatureStatus>('verify_plugin_archive', {
+    archivePath,
+    signaturePath,
+  });
 };
@@ -1,23 +1,53 @@
-import { invoke } from '@tauri-apps/api/core';
-
 import type {
+  YtdlpFormatPreference,
   YtdlpHost,
+  YtdlpPlaylist,
   YtdlpSearchResult,
+  YtdlpSource,
   YtdlpStreamInfo,
 } from '@nuclearplayer/plugin-sdk';
 
-export const ytdlpHost: YtdlpHost = {
+import { getPluginToken, invokeCommand } from './tauri/commands';
+
+// Every command carries the plugin's token, so the plugin's network policy applies to what yt-dlp
+// fetches
+export const createYtdlpHost = (pluginId: string): YtdlpHost => ({
   search: async (
     query: string,
     maxResults?: number,
+    source?: YtdlpSource,
   ): Promise<YtdlpSearchResult[]> => {
-    return invoke<YtdlpSearchResult[]>('ytdlp_search', {
+    return invokeCommand<YtdlpSearchResult[]>('ytdlp_search', {
       query,
       maxResults: maxResults ?? 10,
+      source,
+      token: await getPluginToken(pluginId),
+    });
+  },
+
+  getStream: async (
+    videoId: string,
+    format?: YtdlpFormatPreference,
+    source?: YtdlpSource,
+  ): Promise<YtdlpStreamInfo> => {
+    return invokeCommand<YtdlpStreamInfo>('ytdlp_get_stream', {
+      videoId,
+      format,
+      source,
+      token: await getPluginToken(pluginId),
     });
   },
 
-  getStream: async (videoId: string): Promise<YtdlpStreamInfo> => {
-    return invoke<YtdlpStreamInfo>('ytdlp_get_stream', { videoId });
+  resolveUrl: async (
+    url: string,
+    offset?: number,
+    limit?: number,
+  ): Promise<YtdlpPlaylist> => {
+    return invokeCommand<YtdlpPlaylist>('ytdlp_resolve_url', {
+      url,
+      offset,
+      limit,
+      token: await getPluginToken(pluginId),
+    });
   },
-};
+});
@@ -0,0 +1,62 @@
+import { produce } from 'immer';
+import { create } from 'zustand';
+
+import { Logger } from '../services/logger';
+import {
+  approvePluginHosts,
+  getPendingPluginHosts,
+  type PendingHost,
+} from '../services/tauri/commands';
+
+type HostApprovalStore = {
+  // Hosts each plugin requested that the user hasn't approved yet
+  pending: Record<string, PendingHost[]>;
+  // The plugin whose pending hosts are shown for approval
+  pluginId: string | null;
+  refresh: (pluginId: string) => Promise<void>;
+  open: (pluginId: string) => void;
+  close: () => void;
+  approve: (pluginId: string, hosts: string[]) => Promise<void>;
+};
+
+export const useHostApprovalStore = create<HostApprovalStore>((set, get) => ({
+  pending: {},
+  pluginId: null,
+
+  refresh: async (pluginId: string) => {
+    try {
+      const hosts = await getPendingPluginHosts(pluginId);
+      set(
+        produce((state: HostApprovalStore) => {
+          state.pending[pluginId] = hosts ?? [];
+        }),
+      );
+    } catch (error) {
+      Logger.plugins.warn(
+        `Failed to get pending hosts for ${pluginId}: ${error instanceof Error ? error.message : String(error)}`,
+      );
+    }
+  },
+
+  open: (pluginId: string) => set({ pluginId }),
+  close: () => set({ pluginId: null }),
+
+  approve: async (pluginId: string, hosts: string[]) => {
+    if (hosts.length > 0) {
+      await approvePluginHosts(pluginId, hosts);
+      Logger.plugins.info(
+        `Approved hosts for ${pluginId}: ${hosts.join(', ')}`,
+      );
+    }
+    await get().refresh(pluginId);
+  },
+}));
+
+// Shows the plugin's pending hosts to the user, if it has any
+export const promptForPendingHosts = async (pluginId: string) => {
+  const store = useHostApprovalStore.getState();
+  await store.refresh(pluginId);
+  if ((useHostApprovalStore.getState().pending[pluginId] ?? []).length > 0) {
+    store.open(pluginId);
+  }
+};
@@ -18,7 +18,9 @@ import {
   setRegistryEntryEnabled,
   upsertRegistryEntry,
 } from '../services/plugins/pluginRegistry';
+import { revokePluginToken } from '../services/tauri/commands';
 import { reportError } from '../utils/logging';
+import { promptForPendingHosts } from './hostApprovalStore';
 
 const allowedPermissions: string[] = [];
 
@@ -163,6 +165,8 @@ export const usePluginStore = create<PluginStore>((set, get) => ({
       Logger.plugins.info(
         `Plugin ${id}@${loadedMetadata.version} loaded successfully`,
       );
+      // Hosts the plugin declares are only approved once the user allows them
+      await promptForPendingHosts(id);
 
       if (enabled) {
         Logger.plugins.debug(
@@ -353,6 +357,7 @@ export const usePluginStore = create<PluginStore>((set, get) => ({
       Logger.plugins.info(
         `Plugin ${id} reloaded successfully (${currentVersion} -> ${loadedMetadata.version})`,
       );
+      await promptForPendingHosts(id);
 
       if (wasEnabled) {
         Logger.plugins.debug(`Re-enabling plugin ${id} after reload`);
@@ -389,6 +394,7 @@ export const usePluginStore = create<PluginStore>((set, get) => ({
       }
       await removeManagedPluginInstall(managedPath);
       await removeRegistryEntry(id);
+      await revokePluginToken(id);
       Logger.plugins.info(`Plugin ${id} removed successfully`);
     } catch (error) {
       await reportError('plugins', {
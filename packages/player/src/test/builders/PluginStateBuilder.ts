@@ -10,6 +10,7 @@ const defaultMetadata: PluginMetadata = {
   description: 'Default description',
   author: 'Test Author',
   permissions: [],
+  hosts: [],
 };
 
 export class PluginStateBuilder {
@@ -6,6 +6,7 @@ import { useTranslation } from '@nuclearplayer/i18n';
 import { Button, PluginItem, ScrollableArea, Toggle } from '@nuclearplayer/ui';
 
 import { PluginIconComponent } from '../../components/PluginIcon';
+import { useHostApprovalStore } from '../../stores/hostApprovalStore';
 import { usePluginStore } from '../../stores/pluginStore';
 import { useStartupStore } from '../../stores/startupStore';
 
@@ -14,6 +15,7 @@ export const InstalledPlugins: FC = () => {
   const startupStore = useStartupStore();
   const store = usePluginStore();
   const plugins = store.getAllPlugins();
+  const hostApproval = useHostApprovalStore();
 
   const handleAdd = async () => {
     const path = await open({ directory: true, multiple: false });
@@ -51,17 +53,29 @@ export const InstalledPlugins: FC = () => {
                 await store.removePlugin(p.metadata.id);
               }}
               rightAccessory={
-                <Toggle
-                  data-testid={`toggle-enable-plugin-${p.metadata.id}`}
-                  data-enabled={p.enabled}
-                  checked={p.enabled}
-                  onChange={(checked) =>
-                    checked
-                      ? store.enablePlugin(p.metadata.id)
-                      : store.disablePlugin(p.metadata.id)
-                  }
-                  aria-label={`Toggle ${p.metadata.displayName}`}
-                />
+                <>
+                  <Toggle
+                    data-testid={`toggle-enable-plugin-${p.metadata.id}`}
+                    data-enabled={p.enabled}
+                    checked={p.enabled}
+                    onChange={(checked) =>
+                      checked
+                        ? store.enablePlugin(p.metadata.id)
+                        : store.disablePlugin(p.metadata.id)
+                    }
+                    aria-label={`Toggle ${p.metadata.displayName}`}
+                  />
+                  {(hostApproval.pending[p.metadata.id] ?? []).length > 0 && (
+                    <Button
+                      data-testid={`review-hosts-${p.metadata.id}`}
+                      className="mt-2"
+                      size="sm"
+                      onClick={() => hostApproval.open(p.metadata.id)}
+                    >
+                      {t('hostApproval.review')}
+                    </Button>
+                  )}
+                </>
               }
               loadTimeMs={startupStore.pluginDurations[p.metadata.id]}
             />
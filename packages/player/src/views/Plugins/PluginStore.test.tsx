@@ -116,15 +116,18 @@ describe('Plugin Store', () => {
       fakeGitHubRelease('NuclearPlayer/nuclear-youtube-plugin'),
     );
 
+    const commands: string[] = [];
     mockIPC((cmd) => {
+      commands.push(cmd);
       if (cmd === 'download_file') {
         return undefined;
       }
-      if (cmd === 'extract_zip') {
-        return undefined;
-      }
-      if (cmd === 'copy_dir_recursive') {
-        return undefined;
+      if (cmd === 'install_plugin_archive') {
+        return {
+          path: '/home/user/.local/share/com.nuclearplayer/plugins/nuclear-youtube-plugin/1.0.0',
+          manifest: JSON.parse(fakeYouTubePluginManifest),
+          signature: { status: 'unsigned' },
+        };
       }
     });
 
@@ -149,5 +152,8 @@ describe('Plugin Store', () => {
     const installedPlugins = PluginsWrapper.getPlugins();
     expect(installedPlugins).toHaveLength(1);
     expect(installedPlugins[0].name).toBe('YouTube Music');
+    expect(commands).toContain('install_plugin_archive');
+    expect(commands).not.toContain('extract_zip');
+    expect(commands).not.toContain('copy_dir_recursive');
   });
 });
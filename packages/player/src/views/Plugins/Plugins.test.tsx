@@ -2,6 +2,8 @@ import { mockIPC } from '@tauri-apps/api/mocks';
 import { screen } from '@testing-library/react';
 import userEvent from '@testing-library/user-event';
 
+import { useHostApprovalStore } from '../../stores/hostApprovalStore';
+import { usePluginStore } from '../../stores/pluginStore';
 import { PluginDialogMock } from '../../test/mocks/plugin-dialog';
 import { PluginFsMock } from '../../test/mocks/plugin-fs';
 import { resetInMemoryTauriStore } from '../../test/utils/inMemoryTauriStore';
@@ -72,6 +74,56 @@ describe('Plugins view', () => {
     expect(PluginsWrapper.getPlugins()[0].enabled).toBe(true);
   });
 
+  it('asks before approving the hosts a plugin declares', async () => {
+    usePluginStore.setState({ plugins: {} });
+    useHostApprovalStore.setState({ pending: {}, pluginId: null });
+    PluginDialogMock.setOpen('/path/to/plugin');
+    PluginFsMock.setReadTextFile(fakePluginManifest);
+    PluginFsMock.setExistsFor('plugins', AppData, true);
+    const approvals: unknown[] = [];
+    mockIPC((cmd, args) => {
+      if (cmd === 'copy_dir_recursive') {
+        return true;
+      }
+      if (cmd === 'http_policy_pending_hosts') {
+        return approvals.length > 0
+          ? [{ host: 'localhost', private: true }]
+          : [
+              { host: 'api.example.com', private: false },
+              { host: 'localhost', private: true },
+            ];
+      }
+      if (cmd === 'http_policy_approve_hosts') {
+        approvals.push(args);
+      }
+    });
+
+    await PluginsWrapper.mount();
+    await userEvent.click(screen.getByText('Add Plugin'));
+    await screen.findByText('Network access for Fake plugin');
+
+    // Private hosts are left out unless the user picks them
+    expect(screen.getByRole('switch', { name: 'localhost' })).not.toBeChecked();
+    await userEvent.click(
+      screen.getByRole('button', { name: 'Allow selected' }),
+    );
+
+    expect(approvals).toEqual([
+      expect.objectContaining({
+        pluginId: 'nuclear-fake-plugin',
+        hosts: ['api.example.com'],
+      }),
+    ]);
+
+    // The hosts still pending can be reviewed later
+    await userEvent.click(
+      await screen.findByTestId('review-hosts-nuclear-fake-plugin'),
+    );
+    expect(
+      await screen.findByRole('switch', { name: 'localhost' }),
+    ).toBeInTheDocument();
+  });
+
   it('shows reload and remove actions for dev plugins', async () => {
     PluginDialogMock.setOpen('/dev/plugin');
 
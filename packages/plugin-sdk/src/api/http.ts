@@ -1,4 +1,5 @@
-import type { FetchFunction, HttpHost } from '../types/http';
+import { toCommandError } from '../types/errors';
+import type { FetchFunction, HttpHost, HttpRequestInit } from '../types/http';
 
 const headersToRecord = (headers: HeadersInit): Record<string, string> => {
   if (headers instanceof Headers) {
@@ -14,40 +15,198 @@ const headersToRecord = (headers: HeadersInit): Record<string, string> => {
   return headers;
 };
 
+const encodeBase64 = (bytes: Uint8Array): string => {
+  let binary = '';
+  for (const byte of bytes) {
+    binary += String.fromCharCode(byte);
+  }
+  return btoa(binary);
+};
+
+const decodeBase64 = (data: string): Uint8Array =>
+  Uint8Array.from(atob(data), (char) => char.charCodeAt(0));
+
+type HostBody = Pick<HttpRequestInit, 'body' | 'bodyEncoding'> & {
+  contentType?: string;
+};
+
+// Strings go over as text, binary bodies as base64. Blobs and form data are serialized the way
+// fetch would send them, along with the content type that goes with them (for form data, the
+// multipart boundary).
+const toHostBody = async (
+  body: BodyInit | null | undefined,
+): Promise<HostBody> => {
+  if (body === null || body === undefined) {
+    return {};
+  }
+  if (typeof body === 'string') {
+    return { body, bodyEncoding: 'text' };
+  }
+  if (body instanceof URLSearchParams) {
+    return { body: body.toString(), bodyEncoding: 'text' };
+  }
+  if (body instanceof ArrayBuffer) {
+    return { body: encodeBase64(new Uint8Array(body)), bodyEncoding: 'base64' };
+  }
+  if (ArrayBuffer.isView(body)) {
+    return {
+      body: encodeBase64(
+        new Uint8Array(body.buffer, body.byteOffset, body.byteLength),
+      ),
+      bodyEncoding: 'base64',
+    };
+  }
+  if (body instanceof Blob || body instanceof FormData) {
+    const serialized = new Response(body);
+    return {
+      body: encodeBase64(new Uint8Array(await serialized.arrayBuffer())),
+      bodyEncoding: 'base64',
+      contentType: serialized.headers.get('content-type') ?? undefined,
+    };
+  }
+  throw new TypeError(
+    'Unsupported request body, pass a string, URLSearchParams, ArrayBuffer, typed array, Blob or FormData',
+  );
+};
+
+const toHostRequest = async (
+  input: RequestInfo | URL,
+  init?: RequestInit,
+): Promise<[string, HttpRequestInit]> => {
+  const url = String(input instanceof Request ? input.url : input);
+  const headers = init?.headers ? headersToRecord(init.headers) : undefined;
+  const { contentType, ...body } = await toHostBody(init?.body);
+  const hasContentType = Object.keys(headers ?? {}).some(
+    (name) => name.toLowerCase() === 'content-type',
+  );
+  return [
+    url,
+    {
+      method: init?.method,
+      headers:
+        contentType && !hasContentType
+          ? { ...headers, 'content-type': contentType }
+          : headers,
+      redirect: init?.redirect,
+      cache: init?.cache,
+      ...body,
+    },
+  ];
+};
+
+// Statuses for which the Response constructor rejects a body
+const NULL_BODY_STATUSES = [101, 204, 205, 304];
+
+// The response body is requested as base64 so binary payloads like images arrive intact
 function createFetchFromHost(host: HttpHost): FetchFunction {
   return async (
     input: RequestInfo | URL,
     init?: RequestInit,
   ): Promise<Response> => {
-    const url = String(input instanceof Request ? input.url : input);
-    const headers = init?.headers ? headersToRecord(init.headers) : undefined;
-    const body = typeof init?.body === 'string' ? init.body : undefined;
-
+    const [url, request] = await toHostRequest(input, init);
     const response = await host.fetch(url, {
-      method: init?.method,
-      headers,
-      body,
+      ...request,
+      responseEncoding: 'base64',
     });
 
-    return new Response(response.body, {
-      status: response.status,
-      headers: new Headers(response.headers),
+    return new Response(
+      NULL_BODY_STATUSES.includes(response.status)
+        ? null
+        : decodeBase64(String(response.body)),
+      {
+        status: response.status,
+        headers: new Headers(response.headers),
+      },
+    );
+  };
+}
+
+// Resolves as soon as the response headers arrive. The body is a ReadableStream fed by the host,
+// cancelling it or aborting init.signal cancels the request.
+function createStreamingFetchFromHost(host: HttpHost): FetchFunction {
+  return async (
+    input: RequestInfo | URL,
+    init?: RequestInit,
+  ): Promise<Response> => {
+    const stream = host.stream;
+    if (!stream) {
+      throw new Error('Streaming HTTP is not supported by this host');
+    }
+    const [url, request] = await toHostRequest(input, init);
+
+    return new Promise<Response>((resolve, reject) => {
+      let controller: ReadableStreamDefaultController<Uint8Array>;
+      let started = false;
+      let handle: ReturnType<typeof stream> | undefined;
+      const cancel = () => {
+        handle?.then((streamHandle) => streamHandle.cancel()).catch(() => {});
+      };
+
+      const body = new ReadableStream<Uint8Array>({
+        start: (streamController) => {
+          controller = streamController;
+        },
+        cancel,
+      });
+
+      handle = stream(url, request, (event) => {
+        switch (event.event) {
+          case 'start':
+            started = true;
+            resolve(
+              new Response(
+                NULL_BODY_STATUSES.includes(event.status) ? null : body,
+                {
+                  status: event.status,
+                  headers: new Headers(event.headers),
+                },
+              ),
+            );
+            break;
+          case 'chunk':
+            controller.enqueue(decodeBase64(event.data));
+            break;
+          case 'end':
+            controller.close();
+            break;
+          case 'error': {
+            const error = toCommandError(event.error);
+            if (started) {
+              controller.error(error);
+            } else {
+              reject(error);
+            }
+            break;
+          }
+        }
+      });
+      handle.catch(reject);
+      init?.signal?.addEventListener('abort', cancel);
     });
   };
 }
 
+const NOT_CONFIGURED = 'HTTP host not configured';
+
 const noopHost: HttpHost = {
-  fetch: async () => ({
+  fetch: async (_url, init) => ({
     status: 501,
     headers: {},
-    body: 'HTTP host not configured',
+    body:
+      init?.responseEncoding === 'base64'
+        ? btoa(NOT_CONFIGURED)
+        : NOT_CONFIGURED,
+    cache: 'miss',
   }),
 };
 
 export class HttpAPI {
   readonly fetch: FetchFunction;
+  // Like fetch, but resolves once headers arrive and streams the body instead of buffering it
+  readonly fetchStream: FetchFunction;
 
   constructor(host?: HttpHost) {
     this.fetch = createFetchFromHost(host ?? noopHost);
+    this.fetchStream = createStreamingFetchFromHost(host ?? noopHost);
   }
 }
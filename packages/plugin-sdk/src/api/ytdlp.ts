@@ -1,6 +1,9 @@
 import type {
+  YtdlpFormatPreference,
   YtdlpHost,
+  YtdlpPlaylist,
   YtdlpSearchResult,
+  YtdlpSource,
   YtdlpStreamInfo,
 } from '../types/ytdlp';
 
@@ -18,17 +21,33 @@ export class YtdlpAPI {
   async search(
     query: string,
     maxResults?: number,
+    source?: YtdlpSource,
   ): Promise<YtdlpSearchResult[]> {
     if (!this.host) {
       throw new Error('YtdlpAPI: No host configured');
     }
-    return this.host.search(query, maxResults);
+    return this.host.search(query, maxResults, source);
   }
 
-  async getStream(videoId: string): Promise<YtdlpStreamInfo> {
+  async getStream(
+    videoId: string,
+    format?: YtdlpFormatPreference,
+    source?: YtdlpSource,
+  ): Promise<YtdlpStreamInfo> {
     if (!this.host) {
       throw new Error('YtdlpAPI: No host configured');
     }
-    return this.host.getStream(videoId);
+    return this.host.getStream(videoId, format, source);
+  }
+
+  async resolveUrl(
+    url: string,
+    offset?: number,
+    limit?: number,
+  ): Promise<YtdlpPlaylist> {
+    if (!this.host) {
+      throw new Error('YtdlpAPI: No host configured');
+    }
+    return this.host.resolveUrl(url, offset, limit);
   }
 }
@@ -8,16 +8,31 @@ export { PlaylistsAPI } from './api/playlists';
 export { YtdlpAPI } from './api/ytdlp';
 export type {
   FetchFunction,
+  HttpBodyEncoding,
+  HttpCacheStatus,
   HttpHost,
   HttpRequestInit,
   HttpResponseData,
+  HttpStreamEvent,
+  HttpStreamHandle,
 } from './types/http';
 export type {
+  YtdlpAudioCodec,
+  YtdlpBinaryInfo,
+  YtdlpBinarySource,
+  YtdlpChapter,
+  YtdlpContainer,
+  YtdlpFormatPreference,
   YtdlpHost,
+  YtdlpPlaylist,
+  YtdlpPlaylistEntry,
   YtdlpSearchResult,
+  YtdlpSource,
   YtdlpStreamInfo,
 } from './types/ytdlp';
 export type { LogLevel, LoggerHost } from './types/logger';
+export { CommandError, toCommandError } from './types/errors';
+export type { CommandErrorData, CommandErrorKind } from './types/errors';
 export * from './types';
 export * from './types/settings';
 export * from './types/search';
@@ -371,21 +371,98 @@ export const typeRegistry: TypeRegistry = {
   },
 
   YtdlpSearchResult: {
-    description: 'A YouTube search result from yt-dlp.',
+    description:
+      'A YouTube, YouTube Music or SoundCloud search result from yt-dlp.',
     fields: {
-      id: { type: 'string', description: 'YouTube video ID' },
+      id: { type: 'string', description: 'Video or track ID on the source' },
       title: { type: 'string' },
       duration: { type: 'number | null' },
       thumbnail: { type: 'string | null' },
+      source: { type: '"youtube" | "youtube_music" | "soundcloud"' },
+      url: { type: 'string | null', description: 'Page URL of the result' },
+      uploader: { type: 'string | null' },
+      channel: { type: 'string | null' },
+      artist: { type: 'string | null' },
+      track: { type: 'string | null' },
+      album: { type: 'string | null' },
+      release_year: { type: 'number | null' },
+      like_count: { type: 'number | null' },
     },
   },
 
   YtdlpStreamInfo: {
-    description: 'A resolved YouTube audio stream from yt-dlp.',
+    description: 'A resolved audio stream from yt-dlp.',
     fields: {
       stream_url: { type: 'string' },
       duration: { type: 'number | null' },
       title: { type: 'string | null' },
+      codec: {
+        type: 'string | null',
+        description: 'e.g. "opus", "mp4a.40.2"',
+      },
+      bitrate_kbps: { type: 'number | null' },
+      sample_rate: { type: 'number | null', description: 'In Hz' },
+      filesize: { type: 'number | null', description: 'In bytes' },
+      artist: { type: 'string | null' },
+      track: { type: 'string | null' },
+      album: { type: 'string | null' },
+      release_year: { type: 'number | null' },
+      uploader: { type: 'string | null' },
+      channel: { type: 'string | null' },
+      thumbnail: {
+        type: 'string | null',
+        description: 'The highest resolution thumbnail',
+      },
+      like_count: { type: 'number | null' },
+      chapters: { type: 'YtdlpChapter[]' },
+    },
+  },
+
+  YtdlpChapter: {
+    description: 'A chapter of a yt-dlp stream.',
+    fields: {
+      title: { type: 'string | null' },
+      start_time: {
+        type: 'number',
+        description: 'Seconds from the start of the stream',
+      },
+      end_time: { type: 'number | null' },
+    },
+  },
+
+  YtdlpPlaylistEntry: {
+    description: 'An entry of a playlist, channel or album resolved by yt-dlp.',
+    fields: {
+      id: { type: 'string' },
+      title: { type: 'string' },
+      url: {
+        type: 'string | null',
+        description:
+          'Page URL of the entry. Entries of a channel can be playlists themselves.',
+      },
+      duration: { type: 'number | null' },
+      thumbnail: { type: 'string | null' },
+      uploader: { type: 'string | null' },
+    },
+  },
+
+  YtdlpPlaylist: {
+    description: 'One page of the entries behind a URL resolved by yt-dlp.',
+    fields: {
+      id: { type: 'string | null' },
+      title: { type: 'string | null' },
+      uploader: { type: 'string | null' },
+      is_playlist: {
+        type: 'boolean',
+        description:
+          'False when the URL points at a single video, which is then the only entry',
+      },
+      entries: { type: 'YtdlpPlaylistEntry[]' },
+      offset: { type: 'number' },
+      next_offset: {
+        type: 'number | null',
+        description: 'Offset of the next page, null on the last one',
+      },
     },
   },
 
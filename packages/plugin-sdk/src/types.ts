@@ -13,6 +13,10 @@ export type PluginManifest = {
     category?: string;
     icon?: PluginIcon;
     permissions?: string[];
+    // Hosts the plugin fetches from, e.g. api.example.com or *.example.com.
+    // Requests to them only go through once the user approves them. Without
+    // the list the plugin may reach any public host
+    hosts?: string[];
   };
 };
 
@@ -33,6 +37,7 @@ export type PluginMetadata = {
   category?: string;
   icon?: PluginIcon;
   permissions: string[];
+  hosts: string[];
 };
 
 export type LoadedPlugin = {
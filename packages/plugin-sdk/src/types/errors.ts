@@ -0,0 +1,61 @@
+// These types correspond to Rust types in packages/player/src-tauri/src/error.rs
+export type CommandErrorKind =
+  | 'not_installed'
+  | 'network'
+  | 'timeout'
+  | 'http_status'
+  | 'parse_error'
+  | 'video_unavailable'
+  | 'age_restricted'
+  | 'geo_restricted'
+  | 'verification_failed'
+  | 'unsafe_archive'
+  | 'unsigned'
+  | 'unknown_signing_key'
+  | 'bad_signature'
+  | 'permission_denied'
+  | 'cancelled'
+  | 'io'
+  | 'unknown';
+
+export type CommandErrorData = {
+  kind: CommandErrorKind;
+  message: string;
+  // Only present for http_status errors
+  code?: number;
+  details?: string;
+};
+
+export class CommandError extends Error {
+  readonly kind: CommandErrorKind;
+  readonly code?: number;
+  readonly details?: string;
+
+  constructor(data: CommandErrorData) {
+    super(data.message);
+    this.name = 'CommandError';
+    this.kind = data.kind;
+    this.code = data.code;
+    this.details = data.details;
+  }
+}
+
+const isCommandErrorData = (error: unknown): error is CommandErrorData =>
+  typeof error === 'object' &&
+  error !== null &&
+  typeof (error as CommandErrorData).kind === 'string' &&
+  typeof (error as CommandErrorData).message === 'string';
+
+// Tauri rejects invoke() with the serialized Rust error. Wrap it so callers get a real Error.
+export const toCommandError = (error: unknown): CommandError => {
+  if (error instanceof CommandError) {
+    return error;
+  }
+  if (isCommandErrorData(error)) {
+    return new CommandError(error);
+  }
+  return new CommandError({
+    kind: 'unknown',
+    message: error instanceof Error ? error.message : String(error),
+  });
+};
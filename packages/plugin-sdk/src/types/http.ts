@@ -1,13 +1,35 @@
+import type { CommandErrorData } from './errors';
+
+// text and base64 bodies are strings, json bodies are any JSON value
+export type HttpBodyEncoding = 'text' | 'base64' | 'json';
+
+// Corresponds to CacheStatus in packages/player/src-tauri/src/http/cache.rs
+export type HttpCacheStatus = 'hit' | 'revalidated' | 'miss';
+
 export type HttpRequestInit = {
   method?: string;
   headers?: Record<string, string>;
-  body?: string;
+  body?: unknown;
+  // Defaults to text
+  bodyEncoding?: HttpBodyEncoding;
+  // Defaults to text. Ignored by stream, which always delivers base64 chunks
+  responseEncoding?: HttpBodyEncoding;
+  timeoutMs?: number;
+  // Defaults to follow
+  redirect?: RequestRedirect;
+  userAgent?: string;
+  // Retries of idempotent requests answered with 429 or 503. Defaults to 3
+  maxRetries?: number;
+  // Defaults to default. Only applies to GET requests made with fetch
+  cache?: RequestCache;
 };
 
 export type HttpResponseData = {
   status: number;
   headers: Record<string, string>;
-  body: string;
+  // Encoded according to the request's responseEncoding
+  body: unknown;
+  cache: HttpCacheStatus;
 };
 
 export type FetchFunction = (
@@ -15,6 +37,24 @@ export type FetchFunction = (
   init?: RequestInit,
 ) => Promise<Response>;
 
+// Corresponds to HttpStreamEvent in packages/player/src-tauri/src/http.rs
+export type HttpStreamEvent =
+  | { event: 'start'; status: number; headers: Record<string, string> }
+  // Base64 encoded body chunk
+  | { event: 'chunk'; data: string }
+  | { event: 'end'; bytes: number }
+  | { event: 'error'; error: CommandErrorData };
+
+export type HttpStreamHandle = {
+  requestId: string;
+  cancel: () => Promise<void>;
+};
+
 export type HttpHost = {
   fetch: (url: string, init?: HttpRequestInit) => Promise<HttpResponseData>;
+  stream?: (
+    url: string,
+    init: HttpRequestInit | undefined,
+    onEvent: (event: HttpStreamEvent) => void,
+  ) => Promise<HttpStreamHandle>;
 };
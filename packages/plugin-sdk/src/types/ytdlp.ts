@@ -1,18 +1,105 @@
-// These types correspond to Rust types in packages/player/src-tauri/src/ytdlp.rs
+// These types correspond to Rust types in packages/player/src-tauri/src/ytdlp/
+export type YtdlpSource = 'youtube' | 'youtube_music' | 'soundcloud';
+
 export type YtdlpSearchResult = {
   id: string;
   title: string;
   duration: number | null;
   thumbnail: string | null;
+  source: YtdlpSource;
+  url: string | null;
+  uploader: string | null;
+  channel: string | null;
+  artist: string | null;
+  track: string | null;
+  album: string | null;
+  release_year: number | null;
+  like_count: number | null;
+};
+
+export type YtdlpPlaylistEntry = {
+  id: string;
+  title: string;
+  // Page URL of the entry. Entries of a channel can be playlists themselves and resolve further.
+  url: string | null;
+  duration: number | null;
+  thumbnail: string | null;
+  uploader: string | null;
+};
+
+export type YtdlpPlaylist = {
+  id: string | null;
+  title: string | null;
+  uploader: string | null;
+  // False when the URL points at a single video, which is then the only entry
+  is_playlist: boolean;
+  entries: YtdlpPlaylistEntry[];
+  offset: number;
+  // Offset of the next page, null on the last one
+  next_offset: number | null;
 };
 
 export type YtdlpStreamInfo = {
   stream_url: string;
   duration: number | null;
   title: string | null;
+  codec: string | null;
+  bitrate_kbps: number | null;
+  sample_rate: number | null;
+  filesize: number | null;
+  artist: string | null;
+  track: string | null;
+  album: string | null;
+  release_year: number | null;
+  uploader: string | null;
+  channel: string | null;
+  thumbnail: string | null;
+  like_count: number | null;
+  chapters: YtdlpChapter[];
+};
+
+export type YtdlpChapter = {
+  title: string | null;
+  // Seconds from the start of the stream
+  start_time: number;
+  end_time: number | null;
+};
+
+export type YtdlpAudioCodec = 'opus' | 'aac' | 'vorbis' | 'mp3';
+
+export type YtdlpContainer = 'm4a' | 'webm' | 'mp3';
+
+export type YtdlpFormatPreference = {
+  // Codecs in order of preference. Empty means any codec.
+  codecs?: YtdlpAudioCodec[];
+  max_bitrate_kbps?: number;
+  container?: YtdlpContainer;
+};
+
+export type YtdlpBinarySource = 'custom' | 'managed' | 'system';
+
+export type YtdlpBinaryInfo = {
+  path: string;
+  source: YtdlpBinarySource;
+  version: string | null;
 };
 
 export type YtdlpHost = {
-  search: (query: string, maxResults?: number) => Promise<YtdlpSearchResult[]>;
-  getStream: (videoId: string) => Promise<YtdlpStreamInfo>;
+  search: (
+    query: string,
+    maxResults?: number,
+    source?: YtdlpSource,
+  ) => Promise<YtdlpSearchResult[]>;
+  // videoId can also be a full URL, in which case source is ignored.
+  // The page's host must be one the plugin declares in `nuclear.hosts`, as with resolveUrl
+  getStream: (
+    videoId: string,
+    format?: YtdlpFormatPreference,
+    source?: YtdlpSource,
+  ) => Promise<YtdlpStreamInfo>;
+  resolveUrl: (
+    url: string,
+    offset?: number,
+    limit?: number,
+  ) => Promise<YtdlpPlaylist>;
 };